@@ -0,0 +1,125 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+use glob::{MatchOptions, Pattern};
+use tracing::{error, info};
+
+/// A glob of companion asset files to copy alongside the shader artifact.
+///
+/// Matched files are copied relative to the glob's base, the leading directories
+/// before the first wildcard, so `assets/**/*.png` copies `assets/lut/a.png` to `<output>/lut/a.png`.
+#[derive(Debug, Clone)]
+pub struct AssetGlob {
+    base: PathBuf,
+    pattern: Pattern,
+}
+
+impl FromStr for AssetGlob {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let path = Path::new(s);
+
+        let mut base = PathBuf::new();
+        let mut rest = PathBuf::new();
+        for component in path.components() {
+            let literal = !component
+                .as_os_str()
+                .to_string_lossy()
+                .contains(['*', '?', '[']);
+
+            if literal && rest.as_os_str().is_empty() {
+                base.push(component);
+            } else {
+                rest.push(component);
+            }
+        }
+
+        if rest.as_os_str().is_empty() {
+            // A plain file path, so its directory is the base
+            rest = base.file_name().map(PathBuf::from).unwrap_or_default();
+            base.pop();
+        }
+
+        if base.as_os_str().is_empty() {
+            base.push(Component::CurDir);
+        }
+
+        let base = crate::paths::canonicalize(&base)
+            .map_err(|e| format!("Failed to canonicalize asset base {base:?}: {e:}"))?;
+
+        let pattern = format!(
+            "{}/{}",
+            Pattern::escape(&base.to_string_lossy()),
+            rest.to_string_lossy()
+        );
+        let pattern =
+            Pattern::new(&pattern).map_err(|e| format!("Invalid asset glob {s:?}: {e:}"))?;
+
+        Ok(AssetGlob { base, pattern })
+    }
+}
+
+impl AssetGlob {
+    /// Directory containing every file the glob can match.
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// Whether the provided canonical path is matched by this glob.
+    pub fn matches(&self, path: &Path) -> bool {
+        self.pattern.matches_path_with(
+            path,
+            MatchOptions {
+                case_sensitive: !cfg!(windows),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// All files currently matched by this glob.
+    pub fn files(&self) -> Vec<PathBuf> {
+        glob::glob(self.pattern.as_str())
+            .map(|paths| paths.flatten().filter(|path| path.is_file()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Where the provided matched file should be copied to inside `asset_dir`.
+    pub fn destination(&self, path: &Path, asset_dir: &Path) -> PathBuf {
+        asset_dir.join(path.strip_prefix(&self.base).unwrap_or(path))
+    }
+}
+
+/// Copy a single matched asset into `asset_dir`.
+///
+/// Files already inside `asset_dir` are skipped, as when it sits under the glob's base
+/// the copies would otherwise match again and be copied into themselves on every pass.
+pub async fn copy(glob: &AssetGlob, path: &Path, asset_dir: &Path) {
+    if crate::paths::canonicalize(asset_dir).map_or(false, |asset_dir| {
+        crate::paths::starts_with(path, &asset_dir)
+    }) {
+        return;
+    }
+
+    let destination = glob.destination(path, asset_dir);
+
+    if let Some(parent) = destination.parent() {
+        async_fs::create_dir_all(parent).await.ok();
+    }
+
+    match async_fs::copy(path, &destination).await {
+        Ok(_) => info!("Copied asset {path:?} to {destination:?}"),
+        Err(e) => error!("Failed to copy asset {path:?} to {destination:?}: {e:}"),
+    }
+}
+
+/// Copy every file matched by `globs` into `asset_dir`.
+pub async fn copy_all(globs: &[AssetGlob], asset_dir: &Path) {
+    for glob in globs {
+        for path in glob.files() {
+            copy(glob, &path, asset_dir).await;
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! Repeated builds of one shader crate, timing each phase, for tracking rust-gpu compile times.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    ffi::OsString,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use futures_lite::future;
+use serde::Serialize;
+use tracing::info;
+
+use crate::{cache, cancel::CancelToken, timing::Phase, ShaderBuilder};
+
+/// Name of the whole build's samples, alongside each [`Phase`]'s.
+const TOTAL: &str = "total";
+
+/// Summary statistics of one phase's samples.
+#[derive(Debug, Clone)]
+pub struct Stats {
+    pub mean: Duration,
+    pub median: Duration,
+    /// Sample standard deviation, zero with a single sample.
+    pub std_dev: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl Stats {
+    pub fn new(samples: &[Duration]) -> Self {
+        let nanos = samples.iter().map(Duration::as_nanos).map(|n| n as f64);
+        let mean = nanos.clone().sum::<f64>() / samples.len() as f64;
+        let variance = nanos.map(|n| (n - mean).powi(2)).sum::<f64>()
+            / (samples.len().saturating_sub(1).max(1)) as f64;
+
+        Stats {
+            mean: Duration::from_nanos(mean as u64),
+            median: median(samples),
+            std_dev: Duration::from_nanos(variance.sqrt() as u64),
+            min: samples.iter().copied().min().unwrap_or_default(),
+            max: samples.iter().copied().max().unwrap_or_default(),
+        }
+    }
+}
+
+fn median(samples: &[Duration]) -> Duration {
+    let mut sorted = samples.to_vec();
+    sorted.sort();
+    match sorted.len() {
+        0 => Duration::ZERO,
+        n if n % 2 == 1 => sorted[n / 2],
+        n => (sorted[n / 2 - 1] + sorted[n / 2]) / 2,
+    }
+}
+
+/// Samples of every phase, keyed by phase name.
+#[derive(Debug, Default)]
+pub struct Samples(pub BTreeMap<&'static str, Vec<Duration>>);
+
+impl Samples {
+    fn push(&mut self, phase: &'static str, duration: Duration) {
+        self.0.entry(phase).or_default().push(duration);
+    }
+
+    /// A table of each phase's statistics, in seconds.
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "{:<12} {:>10} {:>10} {:>10} {:>10} {:>10}\n",
+            "phase", "mean", "median", "stddev", "min", "max"
+        );
+        let phases = Phase::ALL.iter().map(|phase| phase.name());
+        for phase in phases.chain([TOTAL]) {
+            let Some(samples) = self.0.get(phase) else {
+                continue
+            };
+            let stats = Stats::new(samples);
+            let secs = |d: Duration| format!("{:.3}s", d.as_secs_f64());
+            writeln!(
+                report,
+                "{phase:<12} {:>10} {:>10} {:>10} {:>10} {:>10}",
+                secs(stats.mean),
+                secs(stats.median),
+                secs(stats.std_dev),
+                secs(stats.min),
+                secs(stats.max)
+            )
+            .unwrap();
+        }
+        report
+    }
+}
+
+/// Make the next build recompile the shader crate itself,
+/// by rewriting its root source file as is so its modification time changes.
+fn touch(path_to_crate: &Path) -> Result<(), Box<dyn Error>> {
+    let lib = path_to_crate.join("src").join("lib.rs");
+    let source =
+        std::fs::read(&lib).map_err(|e| format!("Failed to read {lib:?} to touch it: {e}"))?;
+    std::fs::write(&lib, source)?;
+    Ok(())
+}
+
+/// Remove everything spirv-builder has compiled, so the next build starts from scratch,
+/// `core` and spirv-std included.
+fn clean() -> Result<(), Box<dyn Error>> {
+    let target_dir = cache::spirv_builder_target_dir()
+        .ok_or("Failed to locate the spirv-builder target directory")?;
+    match std::fs::remove_dir_all(target_dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Build the shader crate `iterations` times with the given build arguments, timing each phase.
+///
+/// Incremental runs start with an untimed warm-up build, then touch the crate before each build.
+/// With `clean`, every build starts from an empty spirv-builder target directory instead.
+pub fn run(
+    args: Vec<OsString>,
+    iterations: usize,
+    clean_builds: bool,
+    cancel: &CancelToken,
+) -> Result<Samples, Box<dyn Error>> {
+    if iterations == 0 {
+        return Err("--iterations must be at least 1".into());
+    }
+
+    let builder = ShaderBuilder::from_args(args)?;
+    let mut samples = Samples::default();
+
+    let warm_up = usize::from(!clean_builds);
+    for iteration in 0..iterations + warm_up {
+        if cancel.is_cancelled() {
+            return Err("Benchmark cancelled".into());
+        }
+
+        if clean_builds {
+            clean()?;
+        } else if iteration > 0 {
+            touch(&builder.path_to_crate)?;
+        }
+
+        let start = Instant::now();
+        let mut compiled = builder.build_shader(cancel)?;
+        future::block_on(crate::handle_compile_result(
+            compiled.result,
+            &mut compiled.timings,
+            builder.clone(),
+            None,
+        ))?;
+        let finished = Instant::now();
+
+        if iteration < warm_up {
+            info!("Warm-up build took {:.3?}", finished - start);
+            continue;
+        }
+
+        info!(
+            "Iteration {}/{iterations} took {:.3?}",
+            iteration + 1 - warm_up,
+            finished - start
+        );
+        for phase in Phase::ALL {
+            samples.push(phase.name(), compiled.timings.get(phase));
+        }
+        samples.push(TOTAL, finished - start);
+    }
+
+    Ok(samples)
+}
+
+/// One of criterion's point estimates, in nanoseconds.
+#[derive(Serialize)]
+struct Estimate {
+    confidence_interval: ConfidenceInterval,
+    point_estimate: f64,
+    standard_error: f64,
+}
+
+#[derive(Serialize)]
+struct ConfidenceInterval {
+    confidence_level: f64,
+    lower_bound: f64,
+    upper_bound: f64,
+}
+
+impl Estimate {
+    /// An estimate with a 95% interval assuming normally distributed errors,
+    /// where criterion would bootstrap one.
+    fn new(point_estimate: f64, standard_error: f64) -> Self {
+        Estimate {
+            confidence_interval: ConfidenceInterval {
+                confidence_level: 0.95,
+                lower_bound: point_estimate - 1.96 * standard_error,
+                upper_bound: point_estimate + 1.96 * standard_error,
+            },
+            point_estimate,
+            standard_error,
+        }
+    }
+}
+
+/// Write each phase's samples to `<dir>/<crate>/<phase>/new/` the way criterion lays out
+/// its results, so tools reading criterion's output, like critcmp, can compare runs.
+pub fn write_criterion(
+    dir: &Path,
+    crate_name: &str,
+    samples: &Samples,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut written = vec![];
+    for (phase, samples) in &samples.0 {
+        let stats = Stats::new(samples);
+        let nanos = |d: Duration| d.as_nanos() as f64;
+        let n = samples.len() as f64;
+        let std_error = nanos(stats.std_dev) / n.sqrt();
+
+        let deviations = samples
+            .iter()
+            .map(|&sample| sample.max(stats.median) - sample.min(stats.median))
+            .collect::<Vec<_>>();
+        // Scaled to estimate the standard deviation, as criterion does
+        let median_abs_dev = nanos(median(&deviations)) * 1.4826;
+
+        let estimates = serde_json::json!({
+            "mean": Estimate::new(nanos(stats.mean), std_error),
+            "median": Estimate::new(nanos(stats.median), std_error * 1.2533),
+            "median_abs_dev": Estimate::new(median_abs_dev, 0.0),
+            "slope": null,
+            "std_dev": Estimate::new(nanos(stats.std_dev), nanos(stats.std_dev) / (2.0 * n).sqrt()),
+        });
+        let benchmark = serde_json::json!({
+            "group_id": crate_name,
+            "function_id": phase,
+            "value_str": null,
+            "throughput": null,
+            "full_id": format!("{crate_name}/{phase}"),
+            "directory_name": format!("{crate_name}/{phase}"),
+            "title": format!("{crate_name}/{phase}"),
+        });
+        let sample = serde_json::json!({
+            "sampling_mode": "Flat",
+            "iters": vec![1.0; samples.len()],
+            "times": samples.iter().map(|sample| nanos(*sample)).collect::<Vec<_>>(),
+        });
+
+        let phase_dir = dir.join(crate_name).join(phase).join("new");
+        std::fs::create_dir_all(&phase_dir)?;
+        for (name, value) in [
+            ("estimates.json", estimates),
+            ("benchmark.json", benchmark),
+            ("sample.json", sample),
+        ] {
+            std::fs::write(phase_dir.join(name), serde_json::to_vec_pretty(&value)?)?;
+        }
+        written.push(phase_dir);
+    }
+    Ok(written)
+}
@@ -0,0 +1,138 @@
+use std::{collections::BTreeMap, fmt};
+
+use clap::Args;
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+
+use crate::reflect;
+
+/// Resource budgets enforced on every module after a successful compile.
+#[derive(Debug, Clone, Default, Args)]
+#[command(next_help_heading = "Budgets")]
+pub struct Budgets {
+    /// Fail the build if a module contains more than this many SPIR-V instructions.
+    #[arg(long)]
+    pub max_instructions: Option<usize>,
+    /// Fail the build if a module is larger than this many bytes.
+    #[arg(long)]
+    pub max_module_bytes: Option<usize>,
+    /// Fail the build if a module declares more than this many bindings in one descriptor set.
+    #[arg(long)]
+    pub max_bindings_per_set: Option<usize>,
+}
+
+/// A single budget exceeded by a module.
+#[derive(Debug, Clone)]
+pub struct Violation {
+    pub module: String,
+    pub budget: String,
+    pub limit: usize,
+    pub actual: usize,
+}
+
+/// Every budget violation found in a build.
+#[derive(Debug, Clone)]
+pub struct BudgetReport {
+    pub violations: Vec<Violation>,
+    /// Modules that couldn't be parsed to check, with why.
+    pub unchecked: Vec<String>,
+}
+
+impl fmt::Display for BudgetReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.violations.is_empty() {
+            write!(f, "{} budget(s) exceeded:", self.violations.len())?;
+            for violation in &self.violations {
+                write!(
+                    f,
+                    "\n  {}: {} is {} (limit {})",
+                    violation.module, violation.budget, violation.actual, violation.limit
+                )?;
+            }
+        }
+        if !self.unchecked.is_empty() {
+            if !self.violations.is_empty() {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "{} module(s) couldn't be checked against budgets:",
+                self.unchecked.len()
+            )?;
+            for module in &self.unchecked {
+                write!(f, "\n  {module}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Budgets {
+    /// Check each module against the configured budgets, failing for modules that can't be
+    /// parsed rather than letting them through unchecked.
+    pub fn check(
+        &self,
+        modules: &RustGpuBuilderModules,
+        single_name: &str,
+    ) -> Result<(), BudgetReport> {
+        let mut violations = vec![];
+        let mut unchecked = vec![];
+
+        for (name, bytes) in reflect::named_modules(modules, single_name) {
+            let mut check = |budget: String, limit: Option<usize>, actual: usize| {
+                if let Some(limit) = limit.filter(|limit| actual > *limit) {
+                    violations.push(Violation {
+                        module: name.clone(),
+                        budget,
+                        limit,
+                        actual,
+                    });
+                }
+            };
+
+            check("module bytes".into(), self.max_module_bytes, bytes.len());
+
+            if self.max_instructions.is_none() && self.max_bindings_per_set.is_none() {
+                continue;
+            }
+
+            let module = match reflect::parse(bytes) {
+                Ok(module) => module,
+                Err(e) => {
+                    unchecked.push(format!("{name}: failed to parse: {e:}"));
+                    continue;
+                }
+            };
+
+            check(
+                "instruction count".into(),
+                self.max_instructions,
+                module.all_inst_iter().count(),
+            );
+
+            let mut per_set = BTreeMap::<u32, Vec<u32>>::new();
+            for binding in reflect::bindings(&module) {
+                let bindings = per_set.entry(binding.set).or_default();
+                if !bindings.contains(&binding.binding) {
+                    bindings.push(binding.binding);
+                }
+            }
+
+            for (set, bindings) in per_set {
+                check(
+                    format!("bindings in set {set}"),
+                    self.max_bindings_per_set,
+                    bindings.len(),
+                );
+            }
+        }
+
+        if violations.is_empty() && unchecked.is_empty() {
+            Ok(())
+        } else {
+            Err(BudgetReport {
+                violations,
+                unchecked,
+            })
+        }
+    }
+}
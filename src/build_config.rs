@@ -0,0 +1,90 @@
+//! The fully resolved options an artifact was built with, recorded in it so an artifact
+//! that behaves oddly can be traced back to exactly how it was built.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildConfig {
+    /// Version of rust-gpu-builder that wrote the artifact.
+    pub builder_version: String,
+    pub target: String,
+    pub profile: String,
+    /// Every capability enabled, whether given with --capability or implied by the target.
+    pub capabilities: Vec<String>,
+    pub multimodule: bool,
+    pub spirv_metadata: String,
+    pub deny_warnings: bool,
+    /// Layout relaxations and other codegen passes enabled, by flag name.
+    pub passes: Vec<String>,
+    /// Profile overrides of the cargo build.
+    pub opt_level: Option<String>,
+    pub debug_assertions: Option<bool>,
+    pub lto: Option<String>,
+    /// Names of the variables set with --env. Their values are left out, as they may be secrets.
+    #[serde(default)]
+    pub env: Vec<String>,
+    pub preset: Option<String>,
+    /// `--entry-point-rename` rules, in the order they applied.
+    pub entry_point_renames: Vec<String>,
+    pub split_debug_info: bool,
+    /// Missing from artifacts written before modules could be deduplicated.
+    #[serde(default)]
+    pub dedup_modules: bool,
+    #[serde(default)]
+    pub compression: Option<String>,
+    /// ID of the zstd dictionary the artifact was compressed with.
+    #[serde(default)]
+    pub compression_dictionary: Option<u32>,
+}
+
+impl fmt::Display for BuildConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |items: &[String]| {
+            if items.is_empty() {
+                "none".to_string()
+            } else {
+                items.join(", ")
+            }
+        };
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "default".to_string());
+
+        let rows = [
+            ("builder version", self.builder_version.clone()),
+            ("target", self.target.clone()),
+            ("profile", self.profile.clone()),
+            ("opt-level", optional(self.opt_level.clone())),
+            (
+                "debug assertions",
+                optional(self.debug_assertions.map(|d| d.to_string())),
+            ),
+            ("lto", optional(self.lto.clone())),
+            ("env", list(&self.env)),
+            ("capabilities", list(&self.capabilities)),
+            ("multimodule", self.multimodule.to_string()),
+            ("spirv metadata", self.spirv_metadata.clone()),
+            ("deny warnings", self.deny_warnings.to_string()),
+            ("passes", list(&self.passes)),
+            (
+                "preset",
+                self.preset.clone().unwrap_or_else(|| "none".to_string()),
+            ),
+            ("entry point renames", list(&self.entry_point_renames)),
+            ("split debug info", self.split_debug_info.to_string()),
+            ("dedup modules", self.dedup_modules.to_string()),
+            (
+                "compression",
+                match (&self.compression, self.compression_dictionary) {
+                    (Some(compression), Some(id)) => format!("{compression}, dictionary {id}"),
+                    (Some(compression), None) => compression.clone(),
+                    (None, _) => "none".to_string(),
+                },
+            ),
+        ];
+        for (name, value) in rows {
+            writeln!(f, "{name:<20} {value}")?;
+        }
+        Ok(())
+    }
+}
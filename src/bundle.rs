@@ -0,0 +1,166 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use rust_gpu_builder_shared::RustGpuBuilderOutput;
+use serde::Serialize;
+
+use crate::{checksum::ModuleChecksums, output::Artifact};
+
+/// An artifact to bundle, as `<name>=<path>` or just `<path>`, named after its file stem.
+#[derive(Debug, Clone)]
+pub struct BundleInput {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl FromStr for BundleInput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, path)) if !name.is_empty() => Ok(BundleInput {
+                name: name.to_string(),
+                path: path.into(),
+            }),
+            _ => {
+                let path = PathBuf::from(s);
+                let name = path
+                    .file_stem()
+                    .ok_or_else(|| format!("Can't name artifact {s:?}, use <name>=<path>"))?
+                    .to_string_lossy()
+                    .into_owned();
+                Ok(BundleInput { name, path })
+            }
+        }
+    }
+}
+
+/// What to do about entry points with the same name in more than one crate.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Collisions {
+    /// Refuse to bundle them.
+    Error,
+    /// Key every entry point in the manifest as `<crate>::<entry point>`.
+    Namespace,
+}
+
+impl FromStr for Collisions {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "namespace" => Ok(Self::Namespace),
+            _ => Err("Expected error or namespace"),
+        }
+    }
+}
+
+/// Where an entry point listed in a bundle's manifest lives.
+#[derive(Serialize)]
+pub struct BundledEntryPoint<'a> {
+    #[serde(rename = "crate")]
+    pub crate_name: &'a str,
+    /// Name of the entry point within its crate's artifact.
+    pub entry_point: &'a str,
+}
+
+/// Several crates' artifacts in one file, keyed by crate name.
+#[derive(Serialize)]
+pub struct Bundle<'a> {
+    pub crates: BTreeMap<&'a str, Artifact<'a>>,
+    /// Every entry point of every crate, by a name unique across the bundle.
+    pub entry_points: BTreeMap<String, BundledEntryPoint<'a>>,
+}
+
+/// Read each input artifact, failing on duplicate names.
+pub async fn read_inputs<'a>(
+    inputs: &'a [BundleInput],
+    dictionary: Option<&[u8]>,
+) -> Result<Vec<(&'a str, RustGpuBuilderOutput)>, Box<dyn Error>> {
+    let mut outputs = vec![];
+    for input in inputs {
+        if outputs.iter().any(|(name, _)| *name == input.name) {
+            return Err(format!("More than one artifact is named {:?}", input.name).into());
+        }
+
+        let output =
+            crate::output::read_artifact_with_dictionary(Path::new(&input.path), dictionary)
+                .await?;
+        outputs.push((input.name.as_str(), output));
+    }
+    Ok(outputs)
+}
+
+/// The manifest of every entry point in `outputs`, keyed as `collisions` says.
+fn manifest<'a>(
+    outputs: &'a [(&'a str, RustGpuBuilderOutput)],
+    collisions: Collisions,
+) -> Result<BTreeMap<String, BundledEntryPoint<'a>>, String> {
+    let mut manifest = BTreeMap::new();
+    let mut collided = vec![];
+    for (crate_name, output) in outputs {
+        for entry_point in &output.entry_points {
+            let key = match collisions {
+                Collisions::Error => entry_point.clone(),
+                Collisions::Namespace => format!("{crate_name}::{entry_point}"),
+            };
+            let bundled = BundledEntryPoint {
+                crate_name,
+                entry_point,
+            };
+            if let Some(other) = manifest.insert(key, bundled) {
+                collided.push(format!(
+                    "{entry_point:?} is in both {:?} and {crate_name:?}",
+                    other.crate_name
+                ));
+            }
+        }
+    }
+
+    if collided.is_empty() {
+        return Ok(manifest);
+    }
+    Err(match collisions {
+        Collisions::Error => format!(
+            "Entry point names collide: {}. Pass --collisions namespace to key them by crate",
+            collided.join(", ")
+        ),
+        // Only possible if a crate's own entry points repeat
+        Collisions::Namespace => format!("Entry point names collide: {}", collided.join(", ")),
+    })
+}
+
+impl<'a> Bundle<'a> {
+    pub fn new(
+        outputs: &'a [(&'a str, RustGpuBuilderOutput)],
+        collisions: Collisions,
+    ) -> Result<Self, String> {
+        Ok(Bundle {
+            entry_points: manifest(outputs, collisions)?,
+            crates: outputs
+                .iter()
+                .map(|(name, output)| {
+                    let artifact = Artifact {
+                        entry_points: &output.entry_points,
+                        modules: Some(&output.modules),
+                        deduplicated_modules: None,
+                        stages: None,
+                        pipelines: None,
+                        wgsl: None,
+                        kernels: None,
+                        provenance: None,
+                        config: None,
+                        module_aliases: None,
+                        checksums: ModuleChecksums::new(&output.modules),
+                    };
+                    (*name, artifact)
+                })
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use tracing::info;
+
+/// Parse a duration such as `30s`, `15m`, `12h` or `7d`. A bare number is taken as seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (value, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("Invalid duration {s:?}"))?;
+
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        _ => {
+            return Err(format!(
+                "Unknown duration unit {unit:?}, expected s, m, h or d"
+            ))
+        }
+    };
+
+    Ok(Duration::from_secs(value * seconds))
+}
+
+/// The `--target-dir` spirv-builder compiles into, derived from this binary's build
+/// directory the same way spirv-builder does.
+pub fn spirv_builder_target_dir() -> Option<PathBuf> {
+    let mut dir = PathBuf::from(env!("OUT_DIR"));
+    // Strip `$profile/build/*/out`
+    for filter in ["out", "*", "build", env!("PROFILE")] {
+        if !(filter == "*" || dir.ends_with(filter)) || !dir.pop() {
+            return None;
+        }
+    }
+    Some(dir.join("spirv-builder"))
+}
+
+/// The target directory concurrent matrix job slot `slot` compiles into, nested in the shared
+/// one so cleaning the cache covers it. The first slot uses the shared one.
+pub fn matrix_target_dir(slot: usize) -> Option<PathBuf> {
+    let shared = spirv_builder_target_dir()?;
+    Some(match slot {
+        0 => shared,
+        slot => shared
+            .join("matrix")
+            .join(slot.to_string())
+            .join("spirv-builder"),
+    })
+}
+
+/// The `OUT_DIR` spirv-builder derives `target_dir` from. It passes `--target-dir` to cargo
+/// itself, so `CARGO_TARGET_DIR` has no effect.
+pub fn out_dir_for(target_dir: &Path) -> PathBuf {
+    target_dir
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(env!("PROFILE"))
+        .join("build")
+        .join("rust-gpu-builder")
+        .join("out")
+}
+
+/// Bytes and files removed by a clean.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Cleaned {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Remove files under `dir` last modified before `cutoff`, then any directories left empty.
+pub fn remove_stale(dir: &Path, cutoff: SystemTime, dry_run: bool) -> io::Result<Cleaned> {
+    let mut cleaned = Cleaned::default();
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            let inner = remove_stale(&path, cutoff, dry_run)?;
+            cleaned.files += inner.files;
+            cleaned.bytes += inner.bytes;
+
+            if !dry_run && std::fs::read_dir(&path)?.next().is_none() {
+                std::fs::remove_dir(&path)?;
+            }
+        } else if metadata.modified()? < cutoff {
+            cleaned.files += 1;
+            cleaned.bytes += metadata.len();
+
+            if !dry_run {
+                std::fs::remove_file(&path)?;
+            }
+        }
+    }
+
+    Ok(cleaned)
+}
+
+/// Remove versioned builds of `output_path` older than `cutoff`,
+/// keeping whichever build it currently points at.
+pub async fn remove_stale_versions(
+    output_path: &Path,
+    cutoff: SystemTime,
+    dry_run: bool,
+) -> io::Result<Cleaned> {
+    let versions = crate::output::versions(output_path).await?;
+    let current = async_fs::read_link(output_path).await.ok();
+
+    let mut cleaned = Cleaned::default();
+    for version in versions {
+        if current.as_deref().map(Path::new).and_then(Path::file_name) == version.file_name() {
+            continue;
+        }
+
+        let metadata = async_fs::metadata(&version).await?;
+        if metadata.modified()? >= cutoff {
+            continue;
+        }
+
+        cleaned.files += 1;
+        cleaned.bytes += metadata.len();
+        if !dry_run {
+            info!("Removing {version:?}");
+            async_fs::remove_file(&version).await?;
+        }
+    }
+
+    Ok(cleaned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_duration("12h"), Ok(Duration::from_secs(12 * 60 * 60)));
+        assert_eq!(
+            parse_duration("7d"),
+            Ok(Duration::from_secs(7 * 24 * 60 * 60))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("5w").is_err());
+        assert!(parse_duration("1.5h").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use std::sync::{Arc, Mutex, Weak};
+
+use async_channel::{Receiver, Sender};
+
+#[derive(Debug, Default)]
+struct State {
+    cancelled: bool,
+    /// Process groups to kill on cancellation.
+    processes: Vec<u32>,
+    children: Vec<Weak<Inner>>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: Mutex<State>,
+    /// Closed on cancellation, waking everything awaiting [`CancelToken::cancelled`].
+    closed: (Sender<()>, Receiver<()>),
+}
+
+/// Cooperative cancellation shared between the tasks of a build or the whole process.
+///
+/// Cancelling a token cancels every token created from it with [`CancelToken::child`],
+/// and kills any processes registered with it.
+#[derive(Debug, Clone)]
+pub struct CancelToken(Arc<Inner>);
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        CancelToken(Arc::new(Inner {
+            state: Default::default(),
+            closed: async_channel::bounded(1),
+        }))
+    }
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that is cancelled along with this one, but can also be cancelled on its own.
+    pub fn child(&self) -> CancelToken {
+        let child = CancelToken::new();
+
+        let mut state = self.0.state.lock().unwrap();
+        if state.cancelled {
+            drop(state);
+            child.cancel();
+        } else {
+            state.children.retain(|child| child.strong_count() > 0);
+            state.children.push(Arc::downgrade(&child.0));
+        }
+
+        child
+    }
+
+    pub fn cancel(&self) {
+        let (processes, children) = {
+            let mut state = self.0.state.lock().unwrap();
+            if state.cancelled {
+                return;
+            }
+            state.cancelled = true;
+            (
+                std::mem::take(&mut state.processes),
+                std::mem::take(&mut state.children),
+            )
+        };
+
+        self.0.closed.0.close();
+
+        for pid in processes {
+            kill(pid);
+        }
+
+        for child in children.iter().filter_map(Weak::upgrade) {
+            CancelToken(child).cancel();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.state.lock().unwrap().cancelled
+    }
+
+    /// Resolves once the token is cancelled.
+    pub async fn cancelled(&self) {
+        self.0.closed.1.recv().await.ok();
+    }
+
+    /// Kill the process group led by `pid` if the token is cancelled before the returned guard is dropped.
+    ///
+    /// If the token is already cancelled, the process is killed immediately.
+    pub fn register(&self, pid: u32) -> ProcessGuard {
+        let mut state = self.0.state.lock().unwrap();
+        if state.cancelled {
+            drop(state);
+            kill(pid);
+        } else {
+            state.processes.push(pid);
+        }
+
+        ProcessGuard {
+            token: self.clone(),
+            pid,
+        }
+    }
+}
+
+/// Keeps a process registered with a [`CancelToken`] while alive.
+pub struct ProcessGuard {
+    token: CancelToken,
+    pid: u32,
+}
+
+impl Drop for ProcessGuard {
+    fn drop(&mut self) {
+        let mut state = self.token.0.state.lock().unwrap();
+        state.processes.retain(|pid| *pid != self.pid);
+    }
+}
+
+/// Terminate a process and everything it spawned.
+#[cfg(unix)]
+fn kill(pid: u32) {
+    // Workers lead their own process group, so this reaches cargo and rustc too
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGTERM);
+    }
+}
+
+/// Terminate a process and everything it spawned.
+#[cfg(not(unix))]
+fn kill(pid: u32) {
+    std::process::Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .ok();
+}
@@ -0,0 +1,183 @@
+//! Options for the cargo invocation spirv-builder makes.
+//!
+//! spirv-builder doesn't take extra cargo arguments, so these are passed to it
+//! through cargo's environment variables where possible.
+
+use std::{error::Error, path::Path, process::Command};
+
+use clap::Args;
+
+#[derive(Debug, Clone, Default, Args)]
+#[command(next_help_heading = "Cargo")]
+pub struct CargoOptions {
+    /// Build without accessing the network, failing if a dependency isn't downloaded yet.
+    ///
+    /// Run the `fetch` subcommand beforehand to download them.
+    #[arg(long, default_value = "false")]
+    pub offline: bool,
+    /// Like --offline, and also fail if the shader crate's Cargo.lock is missing or out of date.
+    #[arg(long, default_value = "false")]
+    pub frozen: bool,
+    /// Fail if the shader crate's Cargo.lock is missing or out of date, instead of letting cargo
+    /// update it. Checked before every build, so dependencies can't drift mid-watch.
+    ///
+    /// spirv-builder can't pass `--locked` on to cargo, and cargo has no environment variable
+    /// for it, so this is a `cargo metadata --locked` check rather than a flag of the build.
+    /// Cargo.lock changing between the check and the build isn't caught.
+    #[arg(long, default_value = "false")]
+    pub locked: bool,
+    /// Override the `opt-level` of the profile the shader crate is built with, e.g. `s` for a
+    /// --release build optimized for size.
+    #[arg(long, value_parser = ["0", "1", "2", "3", "s", "z"])]
+    pub profile_opt_level: Option<String>,
+    /// Override whether the build profile enables debug assertions, e.g. to keep them
+    /// in a --release build.
+    ///
+    /// `--debug-assertions` alone enables them, `--debug-assertions=false` disables them.
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub debug_assertions: Option<bool>,
+    /// Override the build profile's `lto` setting.
+    #[arg(long, value_parser = ["true", "false", "fat", "thin", "off"])]
+    pub lto: Option<String>,
+    /// Set an environment variable for the shader crate's compile, as `KEY=VALUE`,
+    /// e.g. to configure it through `option_env!`.
+    ///
+    /// Can be specified multiple times.
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_env)]
+    pub env: Vec<(String, String)>,
+    /// Number of jobs cargo compiles the shader crate and its dependencies with.
+    ///
+    /// Defaults to `CARGO_BUILD_JOBS`, or every CPU if that isn't set either.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub cargo_jobs: Option<u32>,
+    /// Compile at reduced CPU and IO priority, so a long rebuild doesn't make an editor or
+    /// game running on the same machine stutter.
+    #[arg(long, default_value = "false")]
+    pub low_priority: bool,
+}
+
+/// Jobs cargo runs when left to its defaults: `CARGO_BUILD_JOBS` if set, otherwise one per CPU.
+pub fn default_jobs() -> usize {
+    std::env::var("CARGO_BUILD_JOBS")
+        .ok()
+        .and_then(|jobs| jobs.parse().ok())
+        .filter(|jobs| *jobs > 0)
+        .or_else(|| std::thread::available_parallelism().ok().map(usize::from))
+        .unwrap_or(1)
+}
+
+/// Parse a `KEY=VALUE` pair for --env.
+fn parse_env(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("Expected KEY=VALUE, got {s:?}")),
+    }
+}
+
+impl CargoOptions {
+    pub fn is_offline(&self) -> bool {
+        self.offline || self.frozen
+    }
+
+    /// Set the environment variables the build worker's cargo should see,
+    /// when building with the given cargo profile.
+    pub fn apply_env(&self, profile: &str) {
+        if self.is_offline() {
+            std::env::set_var("CARGO_NET_OFFLINE", "true");
+        }
+
+        let profile = profile.replace('-', "_").to_ascii_uppercase();
+        if let Some(opt_level) = &self.profile_opt_level {
+            std::env::set_var(format!("CARGO_PROFILE_{profile}_OPT_LEVEL"), opt_level);
+        }
+        if let Some(debug_assertions) = self.debug_assertions {
+            std::env::set_var(
+                format!("CARGO_PROFILE_{profile}_DEBUG_ASSERTIONS"),
+                debug_assertions.to_string(),
+            );
+        }
+        if let Some(lto) = &self.lto {
+            std::env::set_var(format!("CARGO_PROFILE_{profile}_LTO"), lto);
+        }
+
+        if let Some(jobs) = self.cargo_jobs {
+            std::env::set_var("CARGO_BUILD_JOBS", jobs.to_string());
+        }
+
+        for (key, value) in &self.env {
+            std::env::set_var(key, value);
+        }
+    }
+
+    /// The cargo flag enforcing the lockfile, if any.
+    fn lock_flag(&self) -> Option<&'static str> {
+        if self.frozen {
+            Some("--frozen")
+        } else if self.locked {
+            Some("--locked")
+        } else {
+            None
+        }
+    }
+
+    /// Check the shader crate's Cargo.lock would be used as is, before building.
+    ///
+    /// Resolving through `cargo metadata` finds the lockfile of the crate's workspace,
+    /// and fails the same way the build would.
+    pub fn check_lockfile(&self, path_to_crate: &Path) -> Result<(), String> {
+        let Some(flag) = self.lock_flag() else {
+            return Ok(())
+        };
+
+        let output = Command::new("cargo")
+            .args(["metadata", "--format-version", "1", flag])
+            .current_dir(path_to_crate)
+            .output()
+            .map_err(|e| format!("Failed to run cargo metadata: {e}"))?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Refusing to build {path_to_crate:?} with {flag}, cargo can't use its Cargo.lock as is. \
+                 If it's missing or out of date, run `cargo update --workspace` in the crate.\n{}",
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ))
+        }
+    }
+}
+
+/// Download the dependencies of the shader crate at `path_to_crate`, including those
+/// of the `core` library rust-gpu builds from source, so later builds can run --offline.
+///
+/// With `locked`, cargo fails instead of updating an out of date Cargo.lock.
+pub fn fetch(path_to_crate: &Path, target: &str, locked: bool) -> Result<(), Box<dyn Error>> {
+    let mut command = Command::new("cargo");
+    command
+        .args(["fetch", "-Zbuild-std=core", "--target", target])
+        .current_dir(path_to_crate);
+    if locked {
+        command.arg("--locked");
+    }
+
+    let status = command.status()?;
+    if !status.success() {
+        return Err(format!("cargo fetch failed for {path_to_crate:?} ({status})").into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_env_pairs() {
+        let pair = |key: &str, value: &str| Ok((key.to_string(), value.to_string()));
+        assert_eq!(parse_env("KEY=VALUE"), pair("KEY", "VALUE"));
+        assert_eq!(parse_env("KEY="), pair("KEY", ""));
+        assert_eq!(parse_env("KEY=a=b"), pair("KEY", "a=b"));
+        assert!(parse_env("=VALUE").is_err());
+        assert!(parse_env("KEY").is_err());
+    }
+}
@@ -0,0 +1,215 @@
+use std::{collections::BTreeMap, error::Error, fmt};
+
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Length and hash of a single SPIR-V module.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleChecksum {
+    pub len: usize,
+    /// Hex-encoded SHA-256 of the module bytes.
+    pub sha256: String,
+}
+
+impl ModuleChecksum {
+    pub fn new(module: &[u8]) -> Self {
+        ModuleChecksum {
+            len: module.len(),
+            sha256: Sha256::digest(module)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+        }
+    }
+}
+
+/// Checksums of an artifact's modules, in the same shape as `RustGpuBuilderModules`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModuleChecksums {
+    Single(ModuleChecksum),
+    Multi(BTreeMap<String, ModuleChecksum>),
+}
+
+impl ModuleChecksums {
+    pub fn new(modules: &RustGpuBuilderModules) -> Self {
+        match modules {
+            RustGpuBuilderModules::Single(module) => {
+                ModuleChecksums::Single(ModuleChecksum::new(module))
+            }
+            RustGpuBuilderModules::Multi(multi) => ModuleChecksums::Multi(
+                multi
+                    .iter()
+                    .map(|(name, module)| (name.clone(), ModuleChecksum::new(module)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Check `modules` against these checksums.
+    pub fn verify(&self, modules: &RustGpuBuilderModules) -> Result<(), ChecksumError> {
+        let pairs = match (self, modules) {
+            (ModuleChecksums::Single(expected), RustGpuBuilderModules::Single(module)) => {
+                vec![(None, expected, module)]
+            }
+            (ModuleChecksums::Multi(expected), RustGpuBuilderModules::Multi(multi)) => {
+                if let Some(name) = expected.keys().find(|name| !multi.contains_key(*name)) {
+                    return Err(ChecksumError::Missing(name.clone()));
+                }
+                multi
+                    .iter()
+                    .map(|(name, module)| {
+                        expected
+                            .get(name)
+                            .map(|expected| (Some(name.clone()), expected, module))
+                            .ok_or_else(|| ChecksumError::Unexpected(name.clone()))
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+            _ => return Err(ChecksumError::Layout),
+        };
+
+        for (module, expected, bytes) in pairs {
+            let actual = ModuleChecksum::new(bytes);
+            if actual.len != expected.len {
+                return Err(ChecksumError::Truncated {
+                    module,
+                    expected: expected.len,
+                    actual: actual.len,
+                });
+            }
+            if actual.sha256 != expected.sha256 {
+                return Err(ChecksumError::Mismatch { module });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Ways an artifact's modules can disagree with its embedded checksums.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChecksumError {
+    /// Single and multimodule layouts were mixed up.
+    Layout,
+    /// A module listed in the checksums is absent.
+    Missing(String),
+    /// A module has no recorded checksum.
+    Unexpected(String),
+    /// A module is a different length than recorded, e.g. from a partial read.
+    Truncated {
+        module: Option<String>,
+        expected: usize,
+        actual: usize,
+    },
+    /// A module's bytes don't hash to the recorded value.
+    Mismatch { module: Option<String> },
+}
+
+impl fmt::Display for ChecksumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let describe = |module: &Option<String>| match module {
+            Some(name) => format!("Module {name:?}"),
+            None => "Module".to_string(),
+        };
+
+        match self {
+            ChecksumError::Layout => write!(f, "Module layout doesn't match its checksums"),
+            ChecksumError::Missing(name) => write!(f, "Module {name:?} is missing"),
+            ChecksumError::Unexpected(name) => write!(f, "Module {name:?} has no checksum"),
+            ChecksumError::Truncated {
+                module,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} is {actual} bytes, expected {expected}",
+                describe(module)
+            ),
+            ChecksumError::Mismatch { module } => {
+                write!(f, "{} doesn't match its checksum", describe(module))
+            }
+        }
+    }
+}
+
+impl Error for ChecksumError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multi() -> RustGpuBuilderModules {
+        RustGpuBuilderModules::Multi(
+            [("main_fs", vec![1, 2, 3, 4]), ("main_vs", vec![5, 6, 7, 8])]
+                .map(|(name, module)| (name.to_string(), module))
+                .into(),
+        )
+    }
+
+    #[test]
+    fn accepts_intact_modules() {
+        let single = RustGpuBuilderModules::Single(vec![1, 2, 3, 4]);
+        assert_eq!(ModuleChecksums::new(&single).verify(&single), Ok(()));
+        assert_eq!(ModuleChecksums::new(&multi()).verify(&multi()), Ok(()));
+    }
+
+    #[test]
+    fn rejects_a_truncated_module() {
+        let checksums = ModuleChecksums::new(&multi());
+        let RustGpuBuilderModules::Multi(mut modules) = multi() else {
+            unreachable!()
+        };
+        modules.get_mut("main_vs").unwrap().pop();
+        assert_eq!(
+            checksums.verify(&RustGpuBuilderModules::Multi(modules)),
+            Err(ChecksumError::Truncated {
+                module: Some("main_vs".to_string()),
+                expected: 4,
+                actual: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_a_corrupted_byte() {
+        let checksums = ModuleChecksums::new(&RustGpuBuilderModules::Single(vec![1, 2, 3, 4]));
+        assert_eq!(
+            checksums.verify(&RustGpuBuilderModules::Single(vec![1, 2, 0, 4])),
+            Err(ChecksumError::Mismatch { module: None })
+        );
+    }
+
+    #[test]
+    fn rejects_missing_and_unexpected_modules() {
+        let checksums = ModuleChecksums::new(&multi());
+        let RustGpuBuilderModules::Multi(mut modules) = multi() else {
+            unreachable!()
+        };
+
+        modules.insert("main_cs".to_string(), vec![9]);
+        assert_eq!(
+            checksums.verify(&RustGpuBuilderModules::Multi(modules.clone())),
+            Err(ChecksumError::Unexpected("main_cs".to_string()))
+        );
+
+        modules.remove("main_fs");
+        assert_eq!(
+            checksums.verify(&RustGpuBuilderModules::Multi(modules)),
+            Err(ChecksumError::Missing("main_fs".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_a_different_layout() {
+        let single = RustGpuBuilderModules::Single(vec![1, 2, 3, 4]);
+        assert_eq!(
+            ModuleChecksums::new(&single).verify(&multi()),
+            Err(ChecksumError::Layout)
+        );
+        assert_eq!(
+            ModuleChecksums::new(&multi()).verify(&single),
+            Err(ChecksumError::Layout)
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! zstd compression of written artifacts, optionally with a dictionary trained by `train-dict`
+//! on past ones, which SPIR-V compresses far better with than on its own.
+
+use std::{
+    io::{self, Read},
+    path::Path,
+    str::FromStr,
+};
+
+/// zstd's frame magic number, which every compressed artifact starts with.
+const MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Artifacts are small and written rarely, so favour size over speed.
+const LEVEL: i32 = 19;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compression {
+    Zstd,
+}
+
+impl FromStr for Compression {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zstd" => Ok(Self::Zstd),
+            _ => Err("Expected zstd"),
+        }
+    }
+}
+
+pub fn is_compressed(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// The ID zstd records in frames compressed with `dictionary`, or `None` if it isn't one.
+pub fn dictionary_id(dictionary: &[u8]) -> Option<u32> {
+    zstd::zstd_safe::get_dict_id_from_dict(dictionary).map(u32::from)
+}
+
+/// Read a dictionary written by `train-dict`.
+pub fn read_dictionary(path: &Path) -> Result<Vec<u8>, String> {
+    let dictionary = std::fs::read(path).map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+    dictionary_id(&dictionary).ok_or_else(|| format!("{path:?} isn't a zstd dictionary"))?;
+    Ok(dictionary)
+}
+
+pub fn compress(bytes: &[u8], dictionary: Option<&[u8]>) -> io::Result<Vec<u8>> {
+    match dictionary {
+        Some(dictionary) => {
+            zstd::bulk::Compressor::with_dictionary(LEVEL, dictionary)?.compress(bytes)
+        }
+        None => zstd::bulk::compress(bytes, LEVEL),
+    }
+}
+
+/// Decompress an artifact written with `--compress zstd`, and `dictionary` if it was given one.
+pub fn decompress(bytes: &[u8], dictionary: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    let Some(id) = zstd::zstd_safe::get_dict_id_from_frame(bytes) else {
+        return zstd::stream::decode_all(bytes)
+            .map_err(|e| format!("Failed to decompress the artifact: {e}"))
+    };
+    let Some(dictionary) = dictionary.filter(|dictionary| dictionary_id(dictionary) == Some(id.get()))
+    else {
+        return Err(format!(
+            "The artifact was compressed with zstd dictionary {id}, which is needed to read it"
+        ));
+    };
+
+    let mut decompressed = vec![];
+    zstd::stream::read::Decoder::with_dictionary(bytes, dictionary)
+        .and_then(|mut decoder| decoder.read_to_end(&mut decompressed))
+        .map_err(|e| format!("Failed to decompress the artifact: {e}"))?;
+    Ok(decompressed)
+}
+
+/// Train a dictionary of at most `max_size` bytes on uncompressed artifacts.
+pub fn train(samples: &[impl AsRef<Path>], max_size: usize) -> Result<Vec<u8>, String> {
+    let mut contents = vec![];
+    for path in samples {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+        if is_compressed(&bytes) {
+            return Err(format!(
+                "{path:?} is already compressed, train on artifacts built without --compress"
+            ));
+        }
+        contents.push(bytes);
+    }
+
+    // zstd needs plenty of samples to find anything worth putting in a dictionary
+    zstd::dict::from_samples(&contents, max_size)
+        .map_err(|e| format!("Failed to train on {} artifact(s): {e}", samples.len()))
+}
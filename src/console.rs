@@ -0,0 +1,210 @@
+//! Human-readable build output: per-crate banners, status lines and aligned tables,
+//! colored and truncated to the terminal's width when writing to one.
+
+use std::{
+    fmt::Write as _,
+    str::FromStr,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+use is_terminal::IsTerminal;
+
+static COLOR: AtomicBool = AtomicBool::new(false);
+static CHOICE: AtomicU8 = AtomicU8::new(ColorChoice::Auto as u8);
+
+/// Width banners are drawn at when stdout isn't a terminal.
+const DEFAULT_WIDTH: usize = 80;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal, unless `NO_COLOR` is set.
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err("Expected auto, always or never"),
+        }
+    }
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// Decide once whether output is colored, for everything printed from here on.
+pub fn init(choice: ColorChoice) {
+    CHOICE.store(choice as u8, Ordering::Relaxed);
+    COLOR.store(choice.enabled(), Ordering::Relaxed);
+}
+
+/// `CARGO_TERM_COLOR` for the build worker's cargo, if --color was given explicitly.
+pub fn cargo_color() -> Option<&'static str> {
+    match CHOICE.load(Ordering::Relaxed) {
+        c if c == ColorChoice::Always as u8 => Some("always"),
+        c if c == ColorChoice::Never as u8 => Some("never"),
+        _ => None,
+    }
+}
+
+pub fn color_enabled() -> bool {
+    COLOR.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum Style {
+    Bold,
+    Dim,
+    Green,
+    Red,
+    Cyan,
+}
+
+/// `text` wrapped in the escape codes for `style`, if color is enabled.
+pub fn paint(style: Style, text: &str) -> String {
+    if !color_enabled() {
+        return text.to_string();
+    }
+
+    let code = match style {
+        Style::Bold => "1",
+        Style::Dim => "2",
+        Style::Green => "1;32",
+        Style::Red => "1;31",
+        Style::Cyan => "1;36",
+    };
+    format!("\x1b[{code}m{text}\x1b[0m")
+}
+
+/// Columns of the terminal stdout is writing to, if it is one.
+pub fn width() -> Option<usize> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+
+    #[cfg(unix)]
+    {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        // Safe, as TIOCGWINSZ only writes to the provided winsize
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+            && size.ws_col > 0
+        {
+            return Some(size.ws_col as usize);
+        }
+    }
+
+    std::env::var("COLUMNS").ok()?.parse().ok()
+}
+
+/// Shorten `text` to at most `max` characters, marking the cut with an ellipsis.
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated = text.chars().take(max.saturating_sub(1)).collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+/// Print a rule introducing a build of `crate_name`, with details such as its target.
+pub fn banner(crate_name: &str, details: &str) {
+    let width = width().unwrap_or(DEFAULT_WIDTH);
+    let title = truncate(
+        &format!("━━ {crate_name} · {details} "),
+        width.saturating_sub(2),
+    );
+    let rule = "━".repeat(width.saturating_sub(title.chars().count()));
+
+    println!();
+    println!("{}{}", paint(Style::Cyan, &title), paint(Style::Dim, &rule));
+}
+
+/// Print a cargo-style status line, e.g. `    Finished shader in 1.2s`.
+pub fn status(succeeded: bool, verb: &str, message: &str) {
+    let style = if succeeded { Style::Green } else { Style::Red };
+    println!("{} {message}", paint(style, &format!("{verb:>12}")));
+}
+
+/// Columns of text printed with each column aligned.
+#[derive(Debug)]
+pub struct Table {
+    title: String,
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(title: impl Into<String>, headers: Vec<&'static str>) -> Self {
+        Table {
+            title: title.into(),
+            headers,
+            rows: vec![],
+        }
+    }
+
+    pub fn row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    /// The table as text, with the last column truncated to fit within `width`.
+    pub fn render(&self, width: Option<usize>) -> String {
+        let mut widths = self.headers.iter().map(|h| h.len()).collect::<Vec<_>>();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let mut out = format!(
+            "{} ({})\n",
+            paint(Style::Bold, &self.title),
+            self.rows.len()
+        );
+        let line = |cells: &[String]| {
+            let mut line = String::from("  ");
+            for (i, cell) in cells.iter().enumerate() {
+                if i + 1 == cells.len() {
+                    line.push_str(cell);
+                } else {
+                    write!(line, "{cell:<width$}  ", width = widths[i]).unwrap();
+                }
+            }
+            match width {
+                Some(width) => truncate(&line, width),
+                None => line,
+            }
+        };
+
+        let headers = self
+            .headers
+            .iter()
+            .map(|h| h.to_string())
+            .collect::<Vec<_>>();
+        writeln!(out, "{}", paint(Style::Dim, &line(&headers))).unwrap();
+        for row in &self.rows {
+            writeln!(out, "{}", line(row)).unwrap();
+        }
+        out
+    }
+
+    pub fn print(&self) {
+        print!("{}", self.render(width()));
+    }
+}
@@ -0,0 +1,402 @@
+//! Instructions and features that must never ship, e.g. to keep modules within what
+//! minimum-spec GPUs support, checked on every module after a successful compile.
+//!
+//! Features are detected by use rather than by declared capability, as rust-gpu declares
+//! every enabled capability whether or not a module needs it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    str::FromStr,
+};
+
+use clap::Args;
+use rspirv::{
+    binary::Disassemble,
+    dr::{Instruction, Module, Operand},
+    grammar::CoreInstructionTable,
+    spirv::{Op, Word},
+};
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+
+use crate::reflect;
+
+#[derive(Debug, Clone, Default, Args)]
+#[command(next_help_heading = "Deny lists")]
+pub struct DenyList {
+    /// Fail the build if a module contains this instruction, e.g. `OpAtomicIAdd`.
+    ///
+    /// `OpTypeInt` and `OpTypeFloat` take a width too, e.g. `OpTypeFloat64`.
+    /// Can be specified multiple times.
+    #[arg(long, value_parser = DeniedOp::from_str)]
+    pub deny_op: Vec<DeniedOp>,
+    /// Fail the build if a module uses this feature: `atomics`, `float16`, `float64`,
+    /// `int8`, `int16`, `int64`, `subgroups` or `ray-tracing`.
+    ///
+    /// Can be specified multiple times.
+    #[arg(long, value_parser = Feature::from_str)]
+    pub deny_feature: Vec<Feature>,
+}
+
+/// An instruction given to --deny-op, optionally only of one width.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DeniedOp {
+    op: Op,
+    width: Option<u32>,
+}
+
+impl FromStr for DeniedOp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.strip_prefix("Op").unwrap_or(s);
+        let lookup = |name: &str| {
+            CoreInstructionTable::iter()
+                .find(|inst| inst.opname == name)
+                .map(|inst| inst.opcode)
+        };
+
+        if let Some(op) = lookup(name) {
+            return Ok(DeniedOp { op, width: None });
+        }
+        let digits = name.trim_start_matches(|c: char| !c.is_ascii_digit());
+        let op = lookup(&name[..name.len() - digits.len()])
+            .filter(|op| matches!(op, Op::TypeInt | Op::TypeFloat));
+        match (op, digits.parse()) {
+            (Some(op), Ok(width)) => Ok(DeniedOp {
+                op,
+                width: Some(width),
+            }),
+            _ => Err(format!("Unknown SPIR-V instruction {s:?}")),
+        }
+    }
+}
+
+impl DeniedOp {
+    fn matches(&self, inst: &Instruction) -> bool {
+        inst.class.opcode == self.op
+            && self
+                .width
+                .map_or(true, |denied| width(inst) == Some(denied))
+    }
+}
+
+impl fmt::Display for DeniedOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Op{:?}", self.op)?;
+        if let Some(width) = self.width {
+            write!(f, "{width}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Feature {
+    Atomics,
+    Float16,
+    Float64,
+    Int8,
+    Int16,
+    Int64,
+    /// Subgroup operations, i.e. `OpGroupNonUniform*` and `OpSubgroup*`.
+    Subgroups,
+    RayTracing,
+}
+
+impl FromStr for Feature {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "atomics" => Ok(Self::Atomics),
+            "float16" => Ok(Self::Float16),
+            "float64" => Ok(Self::Float64),
+            "int8" => Ok(Self::Int8),
+            "int16" => Ok(Self::Int16),
+            "int64" => Ok(Self::Int64),
+            "subgroups" => Ok(Self::Subgroups),
+            "ray-tracing" => Ok(Self::RayTracing),
+            _ => Err(
+                "Expected atomics, float16, float64, int8, int16, int64, subgroups or ray-tracing",
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Feature::Atomics => "atomics",
+            Feature::Float16 => "float16",
+            Feature::Float64 => "float64",
+            Feature::Int8 => "int8",
+            Feature::Int16 => "int16",
+            Feature::Int64 => "int64",
+            Feature::Subgroups => "subgroups",
+            Feature::RayTracing => "ray-tracing",
+        })
+    }
+}
+
+/// Width operand of an `OpTypeInt` or `OpTypeFloat`.
+fn width(inst: &Instruction) -> Option<u32> {
+    match inst.operands.first() {
+        Some(Operand::LiteralInt32(width)) => Some(*width),
+        _ => None,
+    }
+}
+
+impl Feature {
+    fn is_used_by(&self, inst: &Instruction) -> bool {
+        let op = inst.class.opcode;
+        let name = inst.class.opname;
+        match self {
+            Feature::Atomics => name.starts_with("Atomic"),
+            Feature::Float16 => op == Op::TypeFloat && width(inst) == Some(16),
+            Feature::Float64 => op == Op::TypeFloat && width(inst) == Some(64),
+            Feature::Int8 => op == Op::TypeInt && width(inst) == Some(8),
+            Feature::Int16 => op == Op::TypeInt && width(inst) == Some(16),
+            Feature::Int64 => op == Op::TypeInt && width(inst) == Some(64),
+            Feature::Subgroups => {
+                name.starts_with("GroupNonUniform") || name.starts_with("Subgroup")
+            }
+            Feature::RayTracing => {
+                [
+                    "TraceRay",
+                    "RayQuery",
+                    "TypeRayQuery",
+                    "TypeAccelerationStructure",
+                ]
+                .iter()
+                .any(|prefix| name.starts_with(prefix))
+                    || matches!(
+                        op,
+                        Op::ReportIntersectionKHR
+                            | Op::IgnoreIntersectionKHR
+                            | Op::TerminateRayKHR
+                            | Op::ExecuteCallableKHR
+                    )
+            }
+        }
+    }
+}
+
+/// A single entry of the deny list.
+#[derive(Debug, Copy, Clone)]
+enum Denied {
+    Op(DeniedOp),
+    Feature(Feature),
+}
+
+impl Denied {
+    fn matches(&self, inst: &Instruction) -> bool {
+        match self {
+            Denied::Op(op) => op.matches(inst),
+            Denied::Feature(feature) => feature.is_used_by(inst),
+        }
+    }
+}
+
+impl fmt::Display for Denied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Denied::Op(op) => write!(f, "instruction {op}"),
+            Denied::Feature(feature) => write!(f, "feature {feature}"),
+        }
+    }
+}
+
+/// Instructions and features found in a build despite being denied.
+#[derive(Debug, Clone)]
+pub struct DenyReport(pub Vec<String>);
+
+impl fmt::Display for DenyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} denied instruction(s) or feature(s) used:",
+            self.0.len()
+        )?;
+        for finding in &self.0 {
+            write!(f, "\n  {finding}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Which entry points can reach each function, through calls.
+fn callers(module: &Module) -> BTreeMap<Word, BTreeSet<String>> {
+    let calls = module
+        .functions
+        .iter()
+        .filter_map(|function| {
+            let callees = function
+                .all_inst_iter()
+                .filter(|inst| inst.class.opcode == Op::FunctionCall)
+                .filter_map(|inst| match inst.operands.first() {
+                    Some(Operand::IdRef(callee)) => Some(*callee),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            Some((function.def_id()?, callees))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let mut callers = BTreeMap::<Word, BTreeSet<String>>::new();
+    for (entry_point, function) in reflect::entry_point_functions(module) {
+        let mut pending = vec![function];
+        while let Some(function) = pending.pop() {
+            if callers
+                .entry(function)
+                .or_default()
+                .insert(entry_point.clone())
+            {
+                pending.extend(calls.get(&function).into_iter().flatten());
+            }
+        }
+    }
+    callers
+}
+
+/// Ids an instruction refers to, including its result type.
+fn references(inst: &Instruction) -> impl Iterator<Item = Word> + '_ {
+    inst.result_type
+        .into_iter()
+        .chain(inst.operands.iter().filter_map(|operand| match operand {
+            Operand::IdRef(id) => Some(*id),
+            _ => None,
+        }))
+}
+
+/// Disassembly of one instruction, without the padding rspirv aligns operands with.
+fn show(inst: &Instruction) -> String {
+    inst.disassemble()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn list(entry_points: &BTreeSet<String>) -> String {
+    entry_points.iter().cloned().collect::<Vec<_>>().join(", ")
+}
+
+impl DenyList {
+    fn denied(&self) -> Vec<Denied> {
+        let ops = self.deny_op.iter().copied().map(Denied::Op);
+        let features = self.deny_feature.iter().copied().map(Denied::Feature);
+        ops.chain(features).collect()
+    }
+
+    fn findings(&self, name: &str, module: &Module) -> Vec<String> {
+        let denied = self.denied();
+        let callers = callers(module);
+        let mut findings = vec![];
+
+        // Types and constants, which entry points use through the functions referring to them
+        for inst in &module.types_global_values {
+            for rule in &denied {
+                if !rule.matches(inst) {
+                    continue;
+                }
+
+                // Globals are declared before they're referred to, so one pass finds every type
+                // and constant built on this one
+                let mut dependents = inst.result_id.into_iter().collect::<BTreeSet<_>>();
+                for global in &module.types_global_values {
+                    if references(global).any(|id| dependents.contains(&id)) {
+                        dependents.extend(global.result_id);
+                    }
+                }
+                let users = module
+                    .functions
+                    .iter()
+                    .filter(|function| {
+                        function
+                            .all_inst_iter()
+                            .any(|inst| references(inst).any(|id| dependents.contains(&id)))
+                    })
+                    .filter_map(|function| callers.get(&function.def_id()?))
+                    .flatten()
+                    .cloned()
+                    .collect::<BTreeSet<_>>();
+
+                let used_by = if users.is_empty() {
+                    "declared, though no entry point uses it".to_string()
+                } else {
+                    format!("used by {}", list(&users))
+                };
+                findings.push(format!("{name}: denied {rule} `{}` {used_by}", show(inst)));
+            }
+        }
+
+        for function in &module.functions {
+            let Some(id) = function.def_id() else {
+                continue
+            };
+            let function_name = reflect::name_of(module, id).unwrap_or_else(|| format!("%{id}"));
+            let entry_points = callers
+                .get(&id)
+                .map(list)
+                .unwrap_or_else(|| "no entry point".to_string());
+
+            // Numbered like `symbols` numbers them, skipping line instructions: `OpFunction`,
+            // its parameters, then each block's label and body
+            let mut index = 1 + function.parameters.len();
+            for block in &function.blocks {
+                index += 1;
+                let mut location = None;
+                for inst in &block.instructions {
+                    match inst.class.opcode {
+                        Op::Line => location = reflect::line(module, inst),
+                        Op::NoLine => location = None,
+                        _ => {
+                            for rule in &denied {
+                                if rule.matches(inst) {
+                                    let at = location
+                                        .as_ref()
+                                        .map(|location| format!(" ({location})"))
+                                        .unwrap_or_default();
+                                    findings.push(format!(
+                                        "{name}: denied {rule} `{}` in {entry_points}, \
+                                         function {function_name} instruction {index}{at}",
+                                        show(inst)
+                                    ));
+                                }
+                            }
+                            index += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        findings
+    }
+
+    /// Fail if any module uses a denied instruction or feature.
+    pub fn check(
+        &self,
+        modules: &RustGpuBuilderModules,
+        single_name: &str,
+    ) -> Result<(), DenyReport> {
+        if self.deny_op.is_empty() && self.deny_feature.is_empty() {
+            return Ok(());
+        }
+
+        let mut findings = vec![];
+        for (name, bytes) in reflect::named_modules(modules, single_name) {
+            // A module that can't be checked can't be let through either
+            match reflect::parse(bytes) {
+                Ok(module) => findings.extend(self.findings(&name, &module)),
+                Err(e) => findings.push(format!("{name}: failed to parse the module: {e}")),
+            }
+        }
+
+        if findings.is_empty() {
+            Ok(())
+        } else {
+            Err(DenyReport(findings))
+        }
+    }
+}
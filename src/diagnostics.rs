@@ -0,0 +1,118 @@
+//! Limiting how many of each compiler diagnostic a build prints, so a refactor that breaks
+//! hundreds of lines the same way doesn't scroll the one different error out of view.
+
+use std::{collections::BTreeMap, io::Write};
+
+use tracing::warn;
+
+use crate::ice::strip_ansi;
+
+/// Forwards cargo's stderr, printing only the first `max` diagnostics of each kind.
+///
+/// Diagnostics are told apart by their error code, or their message with quoted names
+/// blanked out if they don't have one. Everything else passes straight through.
+#[derive(Debug)]
+pub struct DiagnosticFilter {
+    max: Option<usize>,
+    /// The end of the output, up to where a read split a line.
+    partial: Vec<u8>,
+    /// Whether lines belong to a diagnostic that's being left out.
+    suppressing: bool,
+    /// How many of each kind of diagnostic were seen.
+    seen: BTreeMap<String, usize>,
+}
+
+impl DiagnosticFilter {
+    pub fn new(max: Option<usize>) -> Self {
+        DiagnosticFilter {
+            max,
+            partial: vec![],
+            suppressing: false,
+            seen: BTreeMap::new(),
+        }
+    }
+
+    /// Forward the next chunk of output to `out`.
+    pub fn write(&mut self, bytes: &[u8], out: &mut impl Write) {
+        let Some(max) = self.max else {
+            out.write_all(bytes).ok();
+            return;
+        };
+
+        self.partial.extend_from_slice(bytes);
+        let Some(end) = self.partial.iter().rposition(|&b| b == b'\n') else {
+            return;
+        };
+        let rest = self.partial.split_off(end + 1);
+        let lines = std::mem::replace(&mut self.partial, rest);
+        for line in lines.split_inclusive(|&b| b == b'\n') {
+            if self.keep(line, max) {
+                out.write_all(line).ok();
+            }
+        }
+    }
+
+    /// Forward what's left once the output has ended, and tally what was left out.
+    pub fn finish(mut self, out: &mut impl Write) {
+        let Some(max) = self.max else {
+            return;
+        };
+        let partial = std::mem::take(&mut self.partial);
+        if self.keep(&partial, max) {
+            out.write_all(&partial).ok();
+        }
+
+        let suppressed = self
+            .seen
+            .iter()
+            .filter(|(_, &count)| count > max)
+            .map(|(kind, &count)| (kind, count - max))
+            .collect::<Vec<_>>();
+        if suppressed.is_empty() {
+            return;
+        }
+        warn!(
+            "Left out {} diagnostic(s) past --max-diagnostics {max}:{}",
+            suppressed.iter().map(|(_, count)| count).sum::<usize>(),
+            suppressed
+                .iter()
+                .map(|(kind, count)| format!("\n  {count} more {kind}"))
+                .collect::<String>()
+        );
+    }
+
+    fn keep(&mut self, line: &[u8], max: usize) -> bool {
+        let line = strip_ansi(&String::from_utf8_lossy(line));
+        if let Some(kind) = kind(&line) {
+            let seen = self.seen.entry(kind).or_default();
+            *seen += 1;
+            self.suppressing = *seen > max;
+        } else if line.trim().is_empty() {
+            // Diagnostics end with a blank line, which goes with them
+            return !std::mem::take(&mut self.suppressing);
+        }
+        !self.suppressing
+    }
+}
+
+/// The kind of diagnostic `line` starts, if it's a diagnostic's first line.
+fn kind(line: &str) -> Option<String> {
+    let level = ["error", "warning"]
+        .into_iter()
+        .find(|level| line.starts_with(level))?;
+    let rest = &line[level.len()..];
+
+    if let Some(code) = rest.strip_prefix('[') {
+        let code = &code[..code.find(']')?];
+        return Some(format!("{level}[{code}]"));
+    }
+
+    let message = rest.strip_prefix(':')?.trim();
+    let blanked = message
+        .split('`')
+        .enumerate()
+        .map(|(i, part)| if i % 2 == 1 { "_" } else { part })
+        .collect::<Vec<_>>()
+        .join("`");
+    Some(format!("{level}: {blanked}"))
+}
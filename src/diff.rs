@@ -0,0 +1,149 @@
+use std::{collections::BTreeSet, fmt::Write};
+
+use rspirv::binary::Disassemble;
+use rust_gpu_builder_shared::{RustGpuBuilderModules, RustGpuBuilderOutput};
+use similar::{ChangeTag, TextDiff};
+
+use crate::reflect;
+
+/// Name used for the module of a single-module artifact.
+const SINGLE_MODULE: &str = "<module>";
+
+/// Human-readable comparison of two artifacts.
+pub struct ArtifactDiff {
+    pub report: String,
+    pub changed: bool,
+}
+
+fn modules(output: &RustGpuBuilderOutput) -> Vec<(String, &[u8])> {
+    reflect::named_modules(&output.modules, SINGLE_MODULE)
+}
+
+fn capabilities(bytes: &[u8]) -> BTreeSet<String> {
+    reflect::parse(bytes)
+        .map(|module| reflect::capabilities(&module).into_iter().collect())
+        .unwrap_or_default()
+}
+
+fn disassemble(bytes: &[u8]) -> String {
+    reflect::parse(bytes)
+        .map(|module| module.disassemble())
+        .unwrap_or_else(|e| format!("; failed to parse module: {e}"))
+}
+
+/// Write `+`/`-` lines for the difference between two sets.
+fn set_changes(
+    out: &mut String,
+    heading: &str,
+    a: &BTreeSet<String>,
+    b: &BTreeSet<String>,
+) -> bool {
+    let removed = a.difference(b).collect::<Vec<_>>();
+    let added = b.difference(a).collect::<Vec<_>>();
+    if removed.is_empty() && added.is_empty() {
+        return false;
+    }
+
+    writeln!(out, "{heading}:").unwrap();
+    for item in removed {
+        writeln!(out, "  - {item}").unwrap();
+    }
+    for item in added {
+        writeln!(out, "  + {item}").unwrap();
+    }
+    true
+}
+
+/// Compare entry points, module sizes, capabilities and optionally instructions of two artifacts.
+pub fn diff(
+    a: &RustGpuBuilderOutput,
+    b: &RustGpuBuilderOutput,
+    instructions: bool,
+) -> ArtifactDiff {
+    let mut out = String::new();
+    let mut changed = false;
+
+    let entry_points = |output: &RustGpuBuilderOutput| -> BTreeSet<String> {
+        output.entry_points.iter().cloned().collect()
+    };
+    changed |= set_changes(&mut out, "Entry points", &entry_points(a), &entry_points(b));
+
+    if matches!(a.modules, RustGpuBuilderModules::Single(_))
+        != matches!(b.modules, RustGpuBuilderModules::Single(_))
+    {
+        writeln!(out, "Module layout changed between single and multimodule").unwrap();
+        changed = true;
+    }
+
+    let modules_a = modules(a);
+    let modules_b = modules(b);
+    let names = modules_a
+        .iter()
+        .chain(&modules_b)
+        .map(|(name, _)| name.clone())
+        .collect::<BTreeSet<_>>();
+
+    for name in names {
+        let module_a = modules_a.iter().find(|(n, _)| *n == name).map(|(_, m)| *m);
+        let module_b = modules_b.iter().find(|(n, _)| *n == name).map(|(_, m)| *m);
+
+        let (module_a, module_b) = match (module_a, module_b) {
+            (Some(module_a), Some(module_b)) => (module_a, module_b),
+            (Some(module_a), None) => {
+                writeln!(out, "Module {name}: removed ({} bytes)", module_a.len()).unwrap();
+                changed = true;
+                continue;
+            }
+            (None, Some(module_b)) => {
+                writeln!(out, "Module {name}: added ({} bytes)", module_b.len()).unwrap();
+                changed = true;
+                continue;
+            }
+            (None, None) => unreachable!(),
+        };
+
+        if module_a == module_b {
+            continue;
+        }
+        changed = true;
+
+        let delta = module_b.len() as i64 - module_a.len() as i64;
+        writeln!(
+            out,
+            "Module {name}: {} -> {} bytes ({delta:+})",
+            module_a.len(),
+            module_b.len()
+        )
+        .unwrap();
+
+        set_changes(
+            &mut out,
+            &format!("Module {name} capabilities"),
+            &capabilities(module_a),
+            &capabilities(module_b),
+        );
+
+        if instructions {
+            let disassembly_a = disassemble(module_a);
+            let disassembly_b = disassemble(module_b);
+            let text_diff = TextDiff::from_lines(&disassembly_a, &disassembly_b);
+            for change in text_diff.iter_all_changes() {
+                let sign = match change.tag() {
+                    ChangeTag::Delete => "-",
+                    ChangeTag::Insert => "+",
+                    ChangeTag::Equal => continue,
+                };
+                write!(out, "  {sign} {change}").unwrap();
+            }
+        }
+    }
+
+    if !changed {
+        writeln!(out, "Artifacts are identical").unwrap();
+    }
+
+    ArtifactDiff {
+        report: out,
+        changed,
+    }
+}
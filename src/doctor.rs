@@ -0,0 +1,342 @@
+//! Environment checks for `rust-gpu-builder doctor`, covering the usual reasons builds fail before they start.
+
+use std::{path::Path, process::Command, sync::mpsc, time::Duration};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use rspirv::spirv::{AddressingModel, Capability, MemoryModel};
+use spirv_tools::{
+    val::{self, Validator},
+    TargetEnv,
+};
+
+use crate::{metadata, paths};
+
+/// The toolchain rustc_codegen_spirv was built against, which shader crates have to be compiled with.
+const TOOLCHAIN: &str = include_str!("../rust-toolchain.toml");
+
+/// This crate's manifest, for the spirv-builder version shader crates' spirv-std has to match.
+const MANIFEST: &str = include_str!("../Cargo.toml");
+
+/// How long to wait for the test watcher to see a file being written.
+const WATCH_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// The outcome of one check, with how to fix it if it didn't pass.
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self.status {
+            Status::Ok => "ok",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        };
+        write!(f, "{status:<5} {}: {}", self.name, self.detail)?;
+        if let Some(fix) = &self.fix {
+            write!(f, "\n      fix: {fix}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The value of `key = ...` in a TOML-ish file, without quotes. Also finds commented out keys.
+fn toml_value<'a>(file: &'a str, key: &str) -> Option<&'a str> {
+    file.lines().find_map(|line| {
+        let (k, v) = line.trim_start_matches(['#', ' ']).split_once('=')?;
+        (k.trim() == key).then(|| v.trim().trim_matches('"'))
+    })
+}
+
+fn required_channel() -> &'static str {
+    toml_value(TOOLCHAIN, "channel").expect("rust-toolchain.toml has a channel")
+}
+
+fn required_components() -> Vec<&'static str> {
+    toml_value(TOOLCHAIN, "components")
+        .unwrap_or_default()
+        .trim_matches(&['[', ']'][..])
+        .split(',')
+        .map(|component| component.trim().trim_matches('"'))
+        .filter(|component| !component.is_empty())
+        .collect()
+}
+
+fn output(command: &mut Command) -> Result<String, String> {
+    let output = command.output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Whether the required nightly and its components are installed.
+fn toolchain() -> Check {
+    const NAME: &str = "toolchain";
+    let channel = required_channel();
+    let components = required_components();
+    let install = format!(
+        "rustup toolchain install {channel} --component {}",
+        components.join(",")
+    );
+
+    let installed = match output(Command::new("rustup").args(["toolchain", "list"])) {
+        Ok(installed) => installed,
+        Err(e) => {
+            return Check::fail(
+                NAME,
+                format!("can't run rustup: {e}"),
+                "Install rustup from https://rustup.rs",
+            )
+        }
+    };
+    if !installed.lines().any(|line| line.starts_with(channel)) {
+        return Check::fail(NAME, format!("{channel} isn't installed"), install);
+    }
+
+    let installed = output(Command::new("rustup").args([
+        "component",
+        "list",
+        "--installed",
+        "--toolchain",
+        channel,
+    ]))
+    .unwrap_or_default();
+    let missing = components
+        .iter()
+        .filter(|component| !installed.lines().any(|line| line.starts_with(*component)))
+        .copied()
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        Check::ok(
+            NAME,
+            format!("{channel} is installed with {}", components.join(", ")),
+        )
+    } else {
+        Check::fail(
+            NAME,
+            format!("{channel} is missing {}", missing.join(", ")),
+            install,
+        )
+    }
+}
+
+/// Whether the shader crate resolves to the toolchain rustc_codegen_spirv needs.
+fn crate_toolchain(path_to_crate: &Path) -> Check {
+    const NAME: &str = "shader crate toolchain";
+    let channel = required_channel();
+    let fix = format!(
+        "Add a rust-toolchain.toml with `channel = \"{channel}\"` to the shader crate or its workspace"
+    );
+
+    let version = match output(Command::new("rustc").arg("-vV").current_dir(path_to_crate)) {
+        Ok(version) => version,
+        Err(e) => {
+            return Check::fail(
+                NAME,
+                format!("can't run rustc in {path_to_crate:?}: {e}"),
+                fix,
+            )
+        }
+    };
+    let commit = version
+        .lines()
+        .find_map(|line| line.strip_prefix("commit-hash:"))
+        .map(str::trim);
+
+    match (commit, toml_value(TOOLCHAIN, "commit_hash")) {
+        (Some(commit), Some(required)) if commit == required => {
+            Check::ok(NAME, format!("{path_to_crate:?} builds with {channel}"))
+        }
+        (_, Some(_)) => Check::fail(
+            NAME,
+            format!(
+                "{path_to_crate:?} builds with {}",
+                version.lines().next().unwrap_or("an unknown rustc")
+            ),
+            fix,
+        ),
+        (_, None) => Check::warn(
+            NAME,
+            "can't tell which commit the required toolchain is",
+            "Record `commit_hash` in rust-toolchain.toml",
+        ),
+    }
+}
+
+/// Whether the bundled SPIRV-Tools can validate a trivial module.
+fn spirv_tools() -> Check {
+    const NAME: &str = "spirv-tools";
+    let mut builder = rspirv::dr::Builder::new();
+    builder.set_version(1, 5);
+    builder.capability(Capability::Shader);
+    // Without entry points, the module has to be a library
+    builder.capability(Capability::Linkage);
+    builder.memory_model(AddressingModel::Logical, MemoryModel::GLSL450);
+    let words = rspirv::binary::Assemble::assemble(&builder.module());
+
+    match val::create(Some(TargetEnv::Universal_1_5)).validate(words.as_slice(), None) {
+        Ok(()) => Check::ok(NAME, "spirv-val works"),
+        Err(e) => Check::fail(
+            NAME,
+            format!("spirv-val rejected a trivial module: {e}"),
+            "Rebuild rust-gpu-builder, its bundled SPIRV-Tools may be broken",
+        ),
+    }
+}
+
+/// Whether a file watcher sees a file being written.
+fn watcher() -> Check {
+    const NAME: &str = "file watcher";
+    let fix = "Raise fs.inotify.max_user_watches / max_user_instances, or check the filesystem supports change notifications";
+    let dir = std::env::temp_dir().join(format!("rust-gpu-builder-doctor-{}", std::process::id()));
+
+    let result = (|| -> Result<bool, Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(&dir)?;
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = RecommendedWatcher::new(
+            move |res: notify::Result<notify::Event>| {
+                if res.is_ok() {
+                    tx.send(()).ok();
+                }
+            },
+            Default::default(),
+        )?;
+        watcher.watch(&dir, RecursiveMode::Recursive)?;
+        std::fs::write(dir.join("probe"), b"probe")?;
+        Ok(rx.recv_timeout(WATCH_TIMEOUT).is_ok())
+    })();
+    std::fs::remove_dir_all(&dir).ok();
+
+    match result {
+        Ok(true) => Check::ok(NAME, "changes are noticed"),
+        Ok(false) => Check::fail(
+            NAME,
+            format!("no event within {WATCH_TIMEOUT:?} of writing a file"),
+            fix,
+        ),
+        Err(e) => Check::fail(NAME, format!("can't watch {dir:?}: {e}"), fix),
+    }
+}
+
+/// Whether the output path's directory exists or can be created, and is writable.
+fn output_path(output_path: &Path) -> Check {
+    const NAME: &str = "output path";
+    let dir = paths::parent_dir(output_path);
+
+    let Some(existing) = paths::nearest_existing(dir) else {
+        return Check::fail(
+            NAME,
+            format!("no parent of {output_path:?} exists"),
+            "Check the output path",
+        );
+    };
+
+    match paths::probe_writable(existing) {
+        Ok(()) => Check::ok(NAME, format!("{existing:?} is writable")),
+        Err(e) => Check::fail(
+            NAME,
+            format!("can't write to {existing:?}: {e}"),
+            format!("Fix the permissions of {existing:?}, or choose another output path"),
+        ),
+    }
+}
+
+/// Whether the shader crate's spirv-std matches the spirv-builder this binary was built with.
+fn spirv_std(path_to_crate: &Path) -> Check {
+    const NAME: &str = "spirv-std";
+    let builder = toml_value(MANIFEST, "spirv-builder").unwrap_or("unknown");
+    let fix = format!("Depend on spirv-std {builder} in the shader crate");
+
+    let metadata = match metadata::metadata(path_to_crate) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            return Check::fail(
+                NAME,
+                format!("can't read the shader crate's metadata: {e}"),
+                "Check the path points at a shader crate with a valid Cargo.toml",
+            )
+        }
+    };
+
+    let Some(spirv_std) = metadata.packages.iter().find(|package| package.name == "spirv-std") else {
+        return Check::warn(NAME, "the shader crate doesn't depend on spirv-std", fix)
+    };
+
+    // Pre-1.0, minor versions are breaking
+    fn compatible(version: &str) -> Vec<&str> {
+        version.split('.').take(2).collect()
+    }
+    if compatible(&spirv_std.version) == compatible(builder) {
+        Check::ok(
+            NAME,
+            format!("{} matches spirv-builder {builder}", spirv_std.version),
+        )
+    } else {
+        Check::fail(
+            NAME,
+            format!(
+                "{} doesn't match spirv-builder {builder}",
+                spirv_std.version
+            ),
+            fix,
+        )
+    }
+}
+
+/// Run every check that applies, given an optional shader crate and output path.
+pub fn run(path_to_crate: Option<&Path>, output: Option<&Path>) -> Vec<Check> {
+    let mut checks = vec![toolchain()];
+    if let Some(path_to_crate) = path_to_crate {
+        checks.push(crate_toolchain(path_to_crate));
+        checks.push(spirv_std(path_to_crate));
+    }
+    checks.push(spirv_tools());
+    checks.push(watcher());
+    if let Some(output) = output {
+        checks.push(output_path(output));
+    }
+    checks
+}
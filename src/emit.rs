@@ -0,0 +1,305 @@
+//! Extra kinds of output written next to the output path, selected with `--emit`.
+//!
+//! Each kind is an [`Emitter`], which turns a finished build into files to write.
+
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use rspirv::binary::Disassemble;
+use rust_gpu_builder_shared::{RustGpuBuilderModules, RustGpuBuilderOutput};
+
+use crate::{
+    naga_ir, output::Artifact, pack, patch, reflect, rename, stages, webgpu, webgpu::WgslModules,
+    OutputFormat, ShaderBuilder,
+};
+
+/// What an emitter works from.
+pub struct EmitInput<'a> {
+    pub args: &'a ShaderBuilder,
+    /// The build's output with a module for every entry point, even under --dedup-modules.
+    pub output: &'a RustGpuBuilderOutput,
+    pub artifact: &'a Artifact<'a>,
+    /// The artifact as written to the output path.
+    pub bytes: &'a [u8],
+    /// The output path, with its placeholders substituted.
+    pub output_path: &'a Path,
+    /// What the output path held before this build, if any emitter [needs it](Emitter::needs_previous).
+    pub previous: Option<&'a [u8]>,
+}
+
+/// A file produced by an emitter.
+pub struct Emitted {
+    pub path: PathBuf,
+    pub bytes: Vec<u8>,
+}
+
+/// Turns a build into another kind of output.
+pub trait Emitter: Send + Sync {
+    /// Name selecting the emitter with `--emit`, also used in logs.
+    fn name(&self) -> &str;
+
+    /// Whether to read the output path before it's overwritten, into [`EmitInput::previous`].
+    fn needs_previous(&self) -> bool {
+        false
+    }
+
+    fn emit(&self, input: &EmitInput) -> Result<Vec<Emitted>, String>;
+}
+
+/// Emit every output before any is written, so a failing emitter doesn't leave a partial set.
+pub fn run<'a>(
+    emitters: &[&'a dyn Emitter],
+    input: &EmitInput,
+) -> Result<Vec<(&'a str, Emitted)>, String> {
+    let mut outputs: Vec<(&str, Emitted)> = vec![];
+    for emitter in emitters {
+        for emitted in emitter
+            .emit(input)
+            .map_err(|e| format!("Failed to emit {}: {e}", emitter.name()))?
+        {
+            if emitted.path == input.output_path {
+                return Err(format!(
+                    "The {} output would overwrite the output path {:?}",
+                    emitter.name(),
+                    input.output_path
+                ));
+            }
+            if let Some((other, _)) = outputs.iter().find(|(_, other)| other.path == emitted.path) {
+                return Err(format!(
+                    "The {other} and {} outputs would both be written to {:?}",
+                    emitter.name(),
+                    emitted.path
+                ));
+            }
+            outputs.push((emitter.name(), emitted));
+        }
+    }
+    Ok(outputs)
+}
+
+/// The built-in emitters.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Emit {
+    /// The artifact serialized as JSON.
+    Json,
+    /// The artifact serialized as Messagepack.
+    Msgpack,
+    /// Each raw SPIR-V module.
+    Spv,
+    /// Disassembly of each module.
+    Spvasm,
+    /// WGSL translation of each module.
+    Wgsl,
+    /// naga's IR of each module, as RON.
+    NagaIr,
+    /// Rust constants naming each module's entry points and descriptor bindings.
+    RustBindings,
+    /// Indexed container of raw modules, see [`pack`].
+    Pack,
+    /// Binary patch from the previous output to the new one, see [`patch`].
+    Patch,
+}
+
+impl FromStr for Emit {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "msgpack" => Ok(Self::Msgpack),
+            "spv" => Ok(Self::Spv),
+            "spvasm" => Ok(Self::Spvasm),
+            "wgsl" => Ok(Self::Wgsl),
+            "naga-ir" => Ok(Self::NagaIr),
+            "rust-bindings" => Ok(Self::RustBindings),
+            "pack" => Ok(Self::Pack),
+            "patch" => Ok(Self::Patch),
+            _ => Err("Unrecognized emit kind"),
+        }
+    }
+}
+
+impl Emitter for Emit {
+    fn name(&self) -> &str {
+        match self {
+            Emit::Json => "json",
+            Emit::Msgpack => "msgpack",
+            Emit::Spv => "spv",
+            Emit::Spvasm => "spvasm",
+            Emit::Wgsl => "wgsl",
+            Emit::NagaIr => "naga-ir",
+            Emit::RustBindings => "rust-bindings",
+            Emit::Pack => "pack",
+            Emit::Patch => "patch",
+        }
+    }
+
+    fn needs_previous(&self) -> bool {
+        *self == Emit::Patch
+    }
+
+    fn emit(&self, input: &EmitInput) -> Result<Vec<Emitted>, String> {
+        let output_path = input.output_path;
+        let sibling = |extension: &str, bytes: Vec<u8>| {
+            vec![Emitted {
+                path: output_path.with_extension(extension),
+                bytes,
+            }]
+        };
+
+        Ok(match self {
+            Emit::Json => sibling("json", OutputFormat::Json.serialize(input.artifact)),
+            Emit::Msgpack => sibling(
+                "msgpack",
+                OutputFormat::Messagepack.serialize(input.artifact),
+            ),
+            Emit::Spv => per_module(input, "spv", |_, module| Ok(module.to_vec()))?,
+            Emit::Spvasm => per_module(input, "spvasm", |name, module| {
+                reflect::parse(module)
+                    .map(|module| module.disassemble().into_bytes())
+                    .map_err(|e| format!("{name}: {e}"))
+            })?,
+            Emit::Wgsl => wgsl(input)?,
+            Emit::NagaIr => per_module(input, "naga.ron", |name, module| {
+                naga_ir::to_ron(module)
+                    .map(String::into_bytes)
+                    .map_err(|e| format!("{name}: {e}"))
+            })?,
+            Emit::RustBindings => sibling("rs", rust_bindings(input)?.into_bytes()),
+            Emit::Pack => sibling(
+                "pack",
+                pack::encode(
+                    input.output,
+                    &input.args.crate_name(),
+                    input.artifact.stages.as_ref(),
+                ),
+            ),
+            Emit::Patch => match input.previous {
+                Some(previous) => sibling("patch", patch::diff(previous, input.bytes)),
+                None => vec![],
+            },
+        })
+    }
+}
+
+/// Path of one module's file: `<output>.<extension>` for a single module build,
+/// `<output stem>.<entry point>.<extension>` for each module of a multimodule build.
+fn module_path(output_path: &Path, entry_point: Option<&str>, extension: &str) -> PathBuf {
+    let Some(entry_point) = entry_point else {
+        return output_path.with_extension(extension)
+    };
+
+    let stem = output_path
+        .file_stem()
+        .expect("Output path must have a file name")
+        .to_string_lossy();
+    output_path.with_file_name(format!(
+        "{stem}.{}.{extension}",
+        entry_point.replace("::", "-")
+    ))
+}
+
+/// One file per module, each converted by `convert`.
+fn per_module(
+    input: &EmitInput,
+    extension: &str,
+    convert: impl Fn(&str, &[u8]) -> Result<Vec<u8>, String>,
+) -> Result<Vec<Emitted>, String> {
+    let modules = &input.output.modules;
+    let multi = matches!(modules, RustGpuBuilderModules::Multi(_));
+
+    reflect::named_modules(modules, &input.args.crate_name())
+        .into_iter()
+        .map(|(name, module)| {
+            Ok(Emitted {
+                path: module_path(input.output_path, multi.then_some(&*name), extension),
+                bytes: convert(&name, module)?,
+            })
+        })
+        .collect()
+}
+
+/// WGSL of each module, as already translated under `--preset webgpu` or translated now.
+fn wgsl(input: &EmitInput) -> Result<Vec<Emitted>, String> {
+    let translated;
+    let wgsl = match &input.artifact.wgsl {
+        Some(wgsl) => wgsl,
+        None => {
+            translated = webgpu::translate(&input.output.modules, &input.args.crate_name())
+                .map_err(|errors| errors.join("; "))?;
+            &translated
+        }
+    };
+
+    Ok(match wgsl {
+        WgslModules::Single(source) => vec![Emitted {
+            path: module_path(input.output_path, None, "wgsl"),
+            bytes: source.clone().into_bytes(),
+        }],
+        WgslModules::Multi(sources) => sources
+            .iter()
+            .map(|(name, source)| Emitted {
+                path: module_path(input.output_path, Some(name), "wgsl"),
+                bytes: source.clone().into_bytes(),
+            })
+            .collect(),
+    })
+}
+
+/// `name` as a Rust identifier: `-`, `::` and other punctuation become `_`.
+fn identifier(name: &str) -> String {
+    let identifier = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    match identifier.chars().next() {
+        Some(c) if !c.is_ascii_digit() => identifier,
+        _ => format!("_{identifier}"),
+    }
+}
+
+/// A Rust module of constants per shader module, naming its entry points and descriptor bindings,
+/// so host code refers to them by checked names rather than string literals.
+fn rust_bindings(input: &EmitInput) -> Result<String, String> {
+    let crate_name = input.args.crate_name();
+    let mut out = format!(
+        "// Generated by rust-gpu-builder from the `{crate_name}` shader crate, don't edit by hand.\n"
+    );
+
+    for (name, module) in reflect::named_modules(&input.output.modules, &crate_name) {
+        let module = reflect::parse(module).map_err(|e| format!("{name}: {e}"))?;
+
+        writeln!(out, "\npub mod {} {{", identifier(&name).to_lowercase()).unwrap();
+        for (model, entry_point) in reflect::entry_points(&module) {
+            let entry_point = rename::rename(&input.args.entry_point_rename, &entry_point);
+            writeln!(
+                out,
+                "    /// `{}` entry point.\n    pub const {}: &str = {entry_point:?};",
+                stages::stage_name(model),
+                identifier(&entry_point).to_uppercase()
+            )
+            .unwrap();
+        }
+
+        let bindings = reflect::bindings(&module)
+            .into_iter()
+            .map(|binding| {
+                let variable = reflect::name_of(&module, binding.variable)
+                    .unwrap_or_else(|| format!("_{}", binding.variable));
+                format!("({variable:?}, {}, {})", binding.set, binding.binding)
+            })
+            .collect::<Vec<_>>();
+        writeln!(
+            out,
+            "    /// `(name, set, binding)` of each descriptor binding.\n    \
+             pub const BINDINGS: &[(&str, u32, u32)] = &[{}];\n}}",
+            bindings.join(", ")
+        )
+        .unwrap();
+    }
+
+    Ok(out)
+}
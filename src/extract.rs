@@ -0,0 +1,91 @@
+//! Pulling a single entry point's module out of an existing artifact without rebuilding,
+//! e.g. to share one kernel or attach it to a driver bug report.
+
+use std::{error::Error, path::Path};
+
+use rspirv::{binary::Assemble, dr::Operand, spirv::Op};
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+use spirv_tools::{
+    opt::{self, Optimizer, Passes},
+    TargetEnv,
+};
+
+use crate::{output, reflect, validate};
+
+/// The module of `entry_point` in the artifact at `path`, as SPIR-V.
+///
+/// Multimodule artifacts already have one, and single modules are cut down to it:
+/// every other entry point is dropped, then spirv-opt removes what only they used.
+pub async fn extract(
+    path: &Path,
+    entry_point: &str,
+    dictionary: Option<&[u8]>,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let output = output::read_artifact_with_dictionary(path, dictionary).await?;
+    let module = match output.modules {
+        RustGpuBuilderModules::Multi(mut modules) => {
+            return modules
+                .remove(entry_point)
+                .ok_or_else(|| missing(path, entry_point, &output.entry_points))
+        }
+        RustGpuBuilderModules::Single(module) => module,
+    };
+
+    let mut module =
+        reflect::parse(&module).map_err(|e| format!("Failed to parse {path:?}: {e}"))?;
+    let functions = reflect::entry_point_functions(&module);
+    let Some(&function) = functions.get(entry_point) else {
+        return Err(missing(path, entry_point, &output.entry_points))
+    };
+    module
+        .entry_points
+        .retain(|inst| matches!(inst.operands.get(1), Some(Operand::IdRef(id)) if *id == function));
+    module.execution_modes.retain(|inst| {
+        !matches!(inst.class.opcode, Op::ExecutionMode | Op::ExecutionModeId)
+            || matches!(inst.operands.first(), Some(Operand::IdRef(id)) if *id == function)
+    });
+
+    // Optimize for the environment the artifact was built for, where it says
+    let env = output::read_config(path, dictionary)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|config| validate::target_env(&config.target).ok())
+        .unwrap_or(TargetEnv::Universal_1_5);
+    let mut optimizer = opt::create(Some(env));
+    for pass in [
+        Passes::EliminateDeadFunctions,
+        Passes::AggressiveDCE,
+        Passes::DeadVariableElimination,
+        Passes::EliminateDeadConstant,
+        Passes::RemoveUnusedInterfaceVariables,
+    ] {
+        optimizer.register_pass(pass);
+    }
+
+    let mut messages = vec![];
+    let binary = optimizer
+        .optimize(
+            module.assemble(),
+            &mut |message: spirv_tools::error::Message| messages.push(message.message),
+            None,
+        )
+        .map_err(|e| {
+            format!(
+                "spirv-opt failed to strip the other entry points: {e}{}",
+                messages
+                    .iter()
+                    .map(|message| format!("\n  {message}"))
+                    .collect::<String>()
+            )
+        })?;
+    Ok(binary.as_bytes().to_vec())
+}
+
+fn missing(path: &Path, entry_point: &str, entry_points: &[String]) -> Box<dyn Error> {
+    format!(
+        "{path:?} has no entry point {entry_point:?}, it has {}",
+        entry_points.join(", ")
+    )
+    .into()
+}
@@ -0,0 +1,109 @@
+use std::{
+    error::Error,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    metadata::{self, Package},
+    paths,
+};
+
+/// Whether `watch_path` will pick up changes to files inside `package`.
+fn covers(watch_path: &Path, package: &Package) -> bool {
+    let Ok(dir) = paths::canonicalize(package.dir()) else {
+        return false
+    };
+
+    paths::starts_with(&dir, watch_path) || paths::starts_with(watch_path, &dir)
+}
+
+/// Render the local crate graph of the shader crate at `path_to_crate`,
+/// and the watch paths covering each crate, as a DOT digraph.
+///
+/// Local crates not covered by any watch path are highlighted,
+/// since changes to them won't trigger a rebuild.
+pub fn dot(path_to_crate: &Path, watch_paths: &[PathBuf]) -> Result<String, Box<dyn Error>> {
+    let metadata = metadata::metadata(path_to_crate)?;
+    let root = metadata
+        .package_in(path_to_crate)
+        .ok_or_else(|| format!("No package found at {path_to_crate:?}"))?;
+
+    // Walk path dependencies out from the shader crate
+    let mut packages = vec![root];
+    let mut i = 0;
+    while i < packages.len() {
+        for dependency in &packages[i].dependencies {
+            let Some(dependency) = dependency.path.as_ref().and_then(|path| metadata.package_in(path)) else {
+                continue
+            };
+
+            if !packages
+                .iter()
+                .any(|package| package.manifest_path == dependency.manifest_path)
+            {
+                packages.push(dependency);
+            }
+        }
+        i += 1;
+    }
+
+    let watch_paths = watch_paths
+        .iter()
+        .map(|path| paths::canonicalize(path).unwrap_or_else(|_| path.clone()))
+        .collect::<Vec<_>>();
+
+    let mut out = String::new();
+    writeln!(out, "digraph rust_gpu_builder {{")?;
+    writeln!(out, "    rankdir=LR;")?;
+
+    for package in &packages {
+        let watched = watch_paths.iter().any(|path| covers(path, package));
+        let style = match (package.manifest_path == root.manifest_path, watched) {
+            (true, _) => "shape=box, style=bold",
+            (false, true) => "shape=box",
+            (false, false) => "shape=box, color=red, style=dashed",
+        };
+        let label = format!(
+            "{} {}\n{}",
+            package.name,
+            package.version,
+            package.dir().display()
+        );
+        writeln!(
+            out,
+            "    {:?} [label={label:?}, {style}];",
+            package.manifest_path
+        )?;
+    }
+
+    for package in &packages {
+        for dependency in &package.dependencies {
+            let Some(dependency) = dependency.path.as_ref().and_then(|path| metadata.package_in(path)) else {
+                continue
+            };
+            writeln!(
+                out,
+                "    {:?} -> {:?};",
+                dependency.manifest_path, package.manifest_path
+            )?;
+        }
+    }
+
+    for watch_path in &watch_paths {
+        writeln!(out, "    {watch_path:?} [shape=folder];")?;
+        for package in packages
+            .iter()
+            .filter(|package| covers(watch_path, package))
+        {
+            writeln!(
+                out,
+                "    {watch_path:?} -> {:?} [style=dotted, label=\"watches\"];",
+                package.manifest_path
+            )?;
+        }
+    }
+
+    writeln!(out, "}}")?;
+    Ok(out)
+}
@@ -0,0 +1,88 @@
+//! Checks that a file watcher still delivers events, by touching a sentinel file in the
+//! watched directory and waiting for the change to come through.
+//!
+//! inotify can stop reporting without any error, e.g. after its queue overflows,
+//! which would otherwise leave a long watch session silently dead.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use notify::Event;
+use serde::Serialize;
+
+/// Name of the file touched in each watched directory.
+pub const SENTINEL: &str = ".rust-gpu-builder-heartbeat";
+
+/// How long a heartbeat's event may take to arrive before the watcher counts as stalled.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What a heartbeat found.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Beat {
+    /// The watcher is alive, or it isn't time to check yet.
+    Alive,
+    /// The last heartbeat's event never arrived.
+    Stalled,
+}
+
+#[derive(Debug)]
+pub struct Heartbeat {
+    sentinel: PathBuf,
+    interval: Duration,
+    last: Instant,
+    /// When the heartbeat awaiting its event was sent.
+    sent: Option<Instant>,
+}
+
+impl Heartbeat {
+    pub fn new(dir: &Path, interval: Duration) -> Self {
+        Heartbeat {
+            sentinel: dir.join(SENTINEL),
+            interval,
+            last: Instant::now(),
+            sent: None,
+        }
+    }
+
+    /// Whether `event` only concerns the sentinel, so shouldn't trigger a rebuild.
+    pub fn is_heartbeat(&self, event: &Event) -> bool {
+        !event.paths.is_empty() && event.paths.iter().all(|path| *path == self.sentinel)
+    }
+
+    /// Note the sentinel's event arrived.
+    pub fn received(&mut self) {
+        if self.sent.take().is_some() {
+            std::fs::remove_file(&self.sentinel).ok();
+            self.last = Instant::now();
+        }
+    }
+
+    /// Send a heartbeat if one is due, or report the pending one never arrived.
+    pub fn tick(&mut self) -> std::io::Result<Beat> {
+        match self.sent {
+            Some(sent) if sent.elapsed() > TIMEOUT => {
+                self.sent = None;
+                self.last = Instant::now();
+                std::fs::remove_file(&self.sentinel).ok();
+                Ok(Beat::Stalled)
+            }
+            Some(_) => Ok(Beat::Alive),
+            None if self.last.elapsed() >= self.interval => {
+                std::fs::write(&self.sentinel, b"")?;
+                self.sent = Some(Instant::now());
+                Ok(Beat::Alive)
+            }
+            None => Ok(Beat::Alive),
+        }
+    }
+}
+
+/// Posted to webhooks when a watcher stalls, and once it's been replaced.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HealthEvent {
+    WatchStalled { watch_path: PathBuf },
+    WatchRecovered { watch_path: PathBuf },
+}
@@ -0,0 +1,123 @@
+//! The latest watch-mode builds, kept in memory for `GET /history` and printed on exit,
+//! for working out when and how a crate that built fine earlier stopped building.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{
+    console::Table,
+    webhook::{BuildEvent, BuildStatus},
+};
+
+/// A finished build, as listed by `GET /history`.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    /// When the build finished, in milliseconds since the Unix epoch.
+    pub finished_at_ms: u128,
+    #[serde(flatten)]
+    pub event: BuildEvent,
+}
+
+/// The last `capacity` builds, oldest first, shared between the server and the message bus.
+#[derive(Debug, Clone)]
+pub struct History {
+    capacity: usize,
+    entries: Arc<Mutex<VecDeque<HistoryEntry>>>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            capacity,
+            entries: Arc::default(),
+        }
+    }
+
+    /// Add a build, forgetting the oldest once full.
+    pub fn record(&self, event: BuildEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let finished_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(HistoryEntry {
+            finished_at_ms,
+            event,
+        });
+    }
+
+    /// Hash of the latest artifact built for `crate_name`, if it's still remembered.
+    pub fn last_artifact_hash(&self, crate_name: &str) -> Option<String> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|entry| entry.event.crate_name == crate_name)
+            .find_map(|entry| entry.event.artifact_hash.clone())
+    }
+
+    pub fn list(&self) -> Vec<HistoryEntry> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Print a table of the builds, if there were any.
+    pub fn print(&self) {
+        let entries = self.list();
+        if entries.is_empty() {
+            return;
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let mut table = Table::new(
+            "Build history",
+            vec![
+                "finished",
+                "crate",
+                "status",
+                "duration",
+                "artifact / error",
+            ],
+        );
+        for HistoryEntry {
+            finished_at_ms,
+            event,
+        } in entries
+        {
+            let ago = Duration::from_millis(now.saturating_sub(finished_at_ms) as u64);
+            let outcome = match event.status {
+                BuildStatus::Succeeded => event.artifact_hash.unwrap_or_default(),
+                // The first line is enough to tell failures apart, the rest is in GET /history
+                BuildStatus::Failed => event
+                    .error
+                    .as_deref()
+                    .and_then(|error| error.lines().next())
+                    .unwrap_or_default()
+                    .to_string(),
+            };
+            table.row(vec![
+                format!("{}s ago", ago.as_secs()),
+                event.crate_name,
+                format!("{:?}", event.status).to_lowercase(),
+                format!("{:.2?}", Duration::from_millis(event.duration_ms as u64)),
+                outcome,
+            ]);
+        }
+        table.print();
+    }
+}
@@ -0,0 +1,50 @@
+use std::{
+    fmt,
+    process::{Command, ExitStatus},
+};
+
+use tracing::info;
+
+/// Failure of a user-provided hook command.
+#[derive(Debug)]
+pub enum HookError {
+    /// The command could not be started.
+    Spawn(String, std::io::Error),
+    /// The command exited unsuccessfully.
+    Status(String, ExitStatus),
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookError::Spawn(command, e) => write!(f, "Failed to run `{command}`: {e}"),
+            HookError::Status(command, status) => write!(f, "`{command}` exited with {status}"),
+        }
+    }
+}
+
+impl std::error::Error for HookError {}
+
+/// Run a hook command through the platform shell, inheriting stdio.
+pub fn run(command: &str) -> Result<(), HookError> {
+    info!("Running `{command}`...");
+
+    match shell(command).status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(HookError::Status(command.to_string(), status)),
+        Err(e) => Err(HookError::Spawn(command.to_string(), e)),
+    }
+}
+
+/// `command`, to run through the platform shell.
+pub fn shell(command: &str) -> Command {
+    #[cfg(windows)]
+    let (shell, flag) = ("cmd", "/C");
+
+    #[cfg(not(windows))]
+    let (shell, flag) = ("sh", "-c");
+
+    let mut shell = Command::new(shell);
+    shell.args([flag, command]);
+    shell
+}
@@ -0,0 +1,147 @@
+//! Building a shader crate and then running the host's tests against the fresh artifact,
+//! so CI can build shaders and check them against the renderer in one step.
+
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Instant,
+};
+
+use futures_lite::future;
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    cancel::CancelToken,
+    console, hooks,
+    run_manifest::ManifestArtifact,
+    webhook::{self, BuildStatus},
+    ShaderBuilder,
+};
+
+/// What runs the host's tests.
+#[derive(Debug, Clone)]
+pub enum Runner {
+    /// A command run through the platform shell.
+    Command(String),
+    /// `cargo test` in a companion crate.
+    Crate(PathBuf),
+}
+
+/// Posted to webhooks once the host's tests have run, after the build's own event.
+#[derive(Debug, Clone, Serialize)]
+pub struct TestEvent {
+    /// Always `host_tests`, telling this apart from build events.
+    pub event: &'static str,
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    pub status: BuildStatus,
+    pub duration_ms: u128,
+    pub artifact_hash: String,
+    /// The test runner's exit code, if it exited normally.
+    pub exit_code: Option<i32>,
+}
+
+/// Build with `args`, then run the host's tests with `RUST_GPU_BUILDER_*` environment
+/// variables pointing at the artifact, failing if either fails.
+pub fn run(
+    args: &ShaderBuilder,
+    runner: &Runner,
+    cancel: &CancelToken,
+) -> Result<(), Box<dyn Error>> {
+    if args.output_path.is_none() {
+        return Err("test needs an output path to point the host's tests at".into());
+    }
+
+    args.print_banner(None);
+    let start = Instant::now();
+    let result = args.build_shader(cancel);
+    let (event, artifact) = future::block_on(crate::handle_build_result(
+        result,
+        start.elapsed(),
+        args.clone(),
+        None,
+    ))
+    .ok_or("Build cancelled")?;
+    let artifact = match (event.status, artifact) {
+        (BuildStatus::Succeeded, Some(artifact)) => artifact,
+        _ => return Err("Build failed, so the host's tests weren't run".into()),
+    };
+
+    let mut command = match runner {
+        Runner::Command(command) => hooks::shell(command),
+        Runner::Crate(path) => {
+            let mut command = Command::new(std::env::var_os("CARGO").unwrap_or("cargo".into()));
+            command
+                .arg("test")
+                .arg("--manifest-path")
+                .arg(path.join("Cargo.toml"));
+            command
+        }
+    };
+    env(&mut command, args, &artifact)?;
+
+    info!("Running host tests against {:?}...", artifact.path);
+    let start = Instant::now();
+    let status = command
+        .status()
+        .map_err(|e| format!("Failed to run the host's tests: {e}"))?;
+    let duration = start.elapsed();
+
+    let event = TestEvent {
+        event: "host_tests",
+        crate_name: args.crate_name(),
+        status: if status.success() {
+            BuildStatus::Succeeded
+        } else {
+            BuildStatus::Failed
+        },
+        duration_ms: duration.as_millis(),
+        artifact_hash: artifact.hash,
+        exit_code: status.code(),
+    };
+    for url in &args.webhook {
+        future::block_on(webhook::post(url.clone(), event.clone()));
+    }
+
+    if !status.success() {
+        console::status(
+            false,
+            "Failed",
+            &format!("{} host tests after {duration:.2?}", args.crate_name()),
+        );
+        return Err(format!("Host tests exited with {status}").into());
+    }
+    console::status(
+        true,
+        "Tested",
+        &format!("{} in {duration:.2?}", args.crate_name()),
+    );
+    Ok(())
+}
+
+/// Point the host's tests at the artifact, by absolute path as `cargo test` runs them
+/// from the companion crate's directory.
+fn env(
+    command: &mut Command,
+    args: &ShaderBuilder,
+    artifact: &ManifestArtifact,
+) -> std::io::Result<()> {
+    let absolute = |path: &Path| std::env::current_dir().map(|dir| dir.join(path));
+
+    command
+        .env("RUST_GPU_BUILDER_ARTIFACT", absolute(&artifact.path)?)
+        .env("RUST_GPU_BUILDER_ARTIFACT_HASH", &artifact.hash)
+        .env("RUST_GPU_BUILDER_CRATE", &artifact.crate_name)
+        .env("RUST_GPU_BUILDER_TARGET", &artifact.target)
+        .env("RUST_GPU_BUILDER_PROFILE", &artifact.profile)
+        .env(
+            "RUST_GPU_BUILDER_ENTRY_POINTS",
+            artifact.entry_points.join(","),
+        );
+    if let Some(spv_dir) = &args.spv_dir {
+        command.env("RUST_GPU_BUILDER_SPV_DIR", absolute(spv_dir)?);
+    }
+    Ok(())
+}
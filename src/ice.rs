@@ -0,0 +1,183 @@
+use std::fmt;
+
+/// Where rust-gpu bugs should be reported.
+const ISSUES_URL: &str = "https://github.com/EmbarkStudios/rust-gpu/issues";
+
+/// Condensed description of an internal compiler error found in cargo's output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IceSummary {
+    /// The panic or `bug!` message.
+    pub message: String,
+    /// Source location the compiler panicked at, if reported.
+    pub location: Option<String>,
+    /// Entry point being compiled when the compiler panicked, if it could be inferred.
+    pub entry_point: Option<String>,
+    /// The `rustc X running on Y` line rustc prints after an ICE.
+    pub rustc: Option<String>,
+}
+
+impl fmt::Display for IceSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Internal compiler error, this is a bug in rust-gpu rather than your shader:"
+        )?;
+        writeln!(f, "  message:     {}", self.message)?;
+        if let Some(location) = &self.location {
+            writeln!(f, "  at:          {location}")?;
+        }
+        if let Some(entry_point) = &self.entry_point {
+            writeln!(f, "  entry point: {entry_point}")?;
+        }
+        if let Some(rustc) = &self.rustc {
+            writeln!(f, "  toolchain:   {rustc}")?;
+        }
+        writeln!(
+            f,
+            "  builder:     rust-gpu-builder {}",
+            env!("CARGO_PKG_VERSION")
+        )?;
+        write!(
+            f,
+            "Please check {ISSUES_URL} and file an issue with the full output above if it isn't already known."
+        )
+    }
+}
+
+/// Remove ANSI escape sequences, so colored cargo output can be matched.
+pub fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip `ESC [ params final-byte`
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// The text between the first pair of backticks following `marker` in `line`.
+fn backticked_after<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    let rest = &line[line.find(marker)? + marker.len()..];
+    let rest = rest.strip_prefix('`')?;
+    Some(&rest[..rest.find('`')?])
+}
+
+/// Look for an internal compiler error in captured cargo stderr, and summarize it.
+pub fn detect(stderr: &str) -> Option<IceSummary> {
+    let stderr = strip_ansi(stderr);
+
+    if !stderr.contains("error: internal compiler error")
+        && !stderr.contains("the compiler unexpectedly panicked")
+    {
+        return None;
+    }
+
+    let mut message = None;
+    let mut location = None;
+    let mut entry_point = None;
+    let mut rustc = None;
+
+    let mut lines = stderr.lines().map(str::trim).peekable();
+    while let Some(line) = lines.next() {
+        if let Some(rest) = line.strip_prefix("error: internal compiler error: ") {
+            message.get_or_insert_with(|| rest.to_string());
+            if let Some(next) = lines.peek().and_then(|next| next.strip_prefix("--> ")) {
+                location.get_or_insert_with(|| next.to_string());
+            }
+        } else if let Some(rest) = line.split_once("panicked at ").map(|(_, rest)| rest) {
+            if let Some(rest) = rest.strip_prefix('\'') {
+                // `panicked at 'message', location`
+                if let Some((panic, at)) = rest.rsplit_once("', ") {
+                    message.get_or_insert_with(|| panic.to_string());
+                    location.get_or_insert_with(|| at.to_string());
+                }
+            } else {
+                // `panicked at location:` with the message on the following line
+                location.get_or_insert_with(|| rest.trim_end_matches(':').to_string());
+                if let Some(next) = lines.peek() {
+                    message.get_or_insert_with(|| next.to_string());
+                }
+            }
+        } else if let Some(rest) = line.strip_prefix("note: rustc ") {
+            if rest.contains(" running on ") {
+                rustc.get_or_insert_with(|| format!("rustc {rest}"));
+            }
+        }
+
+        if entry_point.is_none() {
+            entry_point = backticked_after(line, "entry point ")
+                .or_else(|| backticked_after(line, "used from within "))
+                .map(str::to_string);
+        }
+    }
+
+    Some(IceSummary {
+        message: message.unwrap_or_else(|| "unknown".to_string()),
+        location,
+        entry_point,
+        rustc,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_ordinary_errors() {
+        assert_eq!(
+            detect("error[E0308]: mismatched types\n  --> src/lib.rs:3:5"),
+            None
+        );
+    }
+
+    #[test]
+    fn summarizes_internal_compiler_errors() {
+        let stderr = "\
+error: internal compiler error: unexpected type in entry point `main_fs`
+ --> src/lib.rs:12:1
+note: rustc 1.70.0-nightly (a1b2c3d4 2023-04-15) running on x86_64-unknown-linux-gnu
+";
+        let ice = detect(stderr).unwrap();
+        assert_eq!(ice.message, "unexpected type in entry point `main_fs`");
+        assert_eq!(ice.location.as_deref(), Some("src/lib.rs:12:1"));
+        assert_eq!(ice.entry_point.as_deref(), Some("main_fs"));
+        assert_eq!(
+            ice.rustc.as_deref(),
+            Some("rustc 1.70.0-nightly (a1b2c3d4 2023-04-15) running on x86_64-unknown-linux-gnu")
+        );
+    }
+
+    #[test]
+    fn reads_both_panic_formats() {
+        let old = "thread 'rustc' panicked at 'index out of bounds', src/builder.rs:10:5\n\
+                   error: the compiler unexpectedly panicked. this is a bug.";
+        let ice = detect(old).unwrap();
+        assert_eq!(ice.message, "index out of bounds");
+        assert_eq!(ice.location.as_deref(), Some("src/builder.rs:10:5"));
+
+        let new = "thread 'rustc' panicked at src/builder.rs:10:5:\n\
+                   index out of bounds\n\
+                   error: the compiler unexpectedly panicked. this is a bug.";
+        let ice = detect(new).unwrap();
+        assert_eq!(ice.message, "index out of bounds");
+        assert_eq!(ice.location.as_deref(), Some("src/builder.rs:10:5"));
+    }
+
+    #[test]
+    fn matches_colored_output() {
+        let stderr = "\x1b[0m\x1b[1m\x1b[38;5;9merror\x1b[0m: internal compiler error: boom";
+        assert_eq!(strip_ansi(stderr), "error: internal compiler error: boom");
+        assert_eq!(detect(stderr).unwrap().message, "boom");
+    }
+}
@@ -0,0 +1,154 @@
+//! Linux's per-user limit on inotify watches, which recursive watching uses one of per directory,
+//! and a coarser way of watching that fits within tight limits.
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Share of the limit past which watching is reported as likely to run out.
+const WARN_RATIO: f64 = 0.8;
+
+/// `fs.inotify.max_user_watches`, on Linux.
+pub fn max_user_watches() -> Option<usize> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    std::fs::read_to_string("/proc/sys/fs/inotify/max_user_watches")
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// How watch paths that are directories are watched.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WatchStrategy {
+    /// Every directory below the watch path.
+    Recursive,
+    /// The watch path and the directories directly inside it, plus deeper directories holding
+    /// Rust sources or Cargo manifests, skipping `target` and hidden directories.
+    Coarse,
+}
+
+impl FromStr for WatchStrategy {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "recursive" => Ok(Self::Recursive),
+            "coarse" => Ok(Self::Coarse),
+            _ => Err("Expected recursive or coarse"),
+        }
+    }
+}
+
+/// Whether a directory holds build output or tool state rather than sources.
+fn is_ignored(name: &str) -> bool {
+    name == "target" || name.starts_with('.')
+}
+
+/// Whether changes to a file can affect a build, under the coarse strategy.
+fn is_source(name: &str) -> bool {
+    name.ends_with(".rs") || name == "Cargo.toml" || name == "Cargo.lock"
+}
+
+/// Directories to watch non-recursively for `root` under [`WatchStrategy::Coarse`].
+pub fn coarse_dirs(root: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    let mut pending = vec![(root.to_owned(), 0)];
+    while let Some((dir, depth)) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue
+        };
+
+        let mut has_sources = false;
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() && !is_ignored(&name) => {
+                    pending.push((entry.path(), depth + 1));
+                }
+                Ok(file_type) if file_type.is_file() && is_source(&name) => has_sources = true,
+                _ => (),
+            }
+        }
+
+        if depth <= 1 || has_sources {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// Whether `error` is notify running out of inotify watches.
+pub fn is_exhausted(error: &(dyn std::error::Error + 'static)) -> bool {
+    error.downcast_ref::<notify::Error>().map_or(false, |e| {
+        matches!(e.kind, notify::ErrorKind::MaxFilesWatch)
+    })
+}
+
+/// Explanation of running out of watches while watching `path`, and how to get around it.
+pub fn exhausted_message(path: &Path) -> String {
+    let limit = max_user_watches()
+        .map(|limit| format!(" of {limit}"))
+        .unwrap_or_default();
+    format!(
+        "Ran out of inotify watches watching {path:?}, the limit{limit} is shared with every other \
+         program watching files. Raise it with `sudo sysctl fs.inotify.max_user_watches=<watches>`, \
+         or pass `--watch-strategy coarse` to watch far fewer directories"
+    )
+}
+
+/// Number of directories at or below `root`, i.e. the watches watching it recursively takes.
+pub fn count_dirs(root: &Path) -> usize {
+    let mut count = 0;
+    let mut pending = vec![root.to_owned()];
+    while let Some(dir) = pending.pop() {
+        count += 1;
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue
+        };
+        for entry in entries.flatten() {
+            if entry
+                .file_type()
+                .map_or(false, |file_type| file_type.is_dir())
+            {
+                pending.push(entry.path());
+            }
+        }
+    }
+    count
+}
+
+/// A warning if watching `roots` with `strategy` would use most or all of the watch limit,
+/// which other programs watching files share.
+pub fn check_budget<'a>(
+    roots: impl IntoIterator<Item = &'a Path>,
+    strategy: WatchStrategy,
+) -> Option<String> {
+    let limit = max_user_watches()?;
+    let needed = roots
+        .into_iter()
+        .map(|root| match strategy {
+            WatchStrategy::Recursive => count_dirs(root),
+            WatchStrategy::Coarse => coarse_dirs(root).len(),
+        })
+        .sum::<usize>();
+    if (needed as f64) < limit as f64 * WARN_RATIO {
+        return None;
+    }
+
+    let coarse = match strategy {
+        WatchStrategy::Recursive => {
+            ", or pass `--watch-strategy coarse` to watch fewer directories"
+        }
+        WatchStrategy::Coarse => "",
+    };
+    Some(format!(
+        "Watching needs about {needed} inotify watches, close to or over the limit of {limit}, \
+         so changes may go unnoticed. Raise it with `sudo sysctl fs.inotify.max_user_watches={}`{coarse}",
+        (needed * 2).max(limit * 2)
+    ))
+}
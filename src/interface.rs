@@ -0,0 +1,477 @@
+//! The interface a build presents to host code, compared with the artifact it replaces so a
+//! binding or entry point changing under a hot-reloading host is called out by the builder,
+//! rather than found out from a validation error or a garbled frame.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use rspirv::{
+    dr::{Module, Operand},
+    spirv::{Decoration, Dim, ExecutionModel, Op, StorageClass, Word},
+};
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+use serde::Serialize;
+
+use crate::{
+    pipelines, reflect,
+    rename::{self, EntryPointRename},
+    stages,
+};
+
+/// What host code binds to, as reflected from a build's modules.
+#[derive(Debug, Default)]
+pub struct Interface {
+    /// Descriptor type and layout of each binding, keyed by set and binding.
+    bindings: BTreeMap<(u32, u32), (String, String)>,
+    /// Layout of the push constants each module declares, keyed by module.
+    push_constants: BTreeMap<String, String>,
+    /// Signature of each entry point, keyed by its name in the output.
+    entry_points: BTreeMap<String, String>,
+}
+
+/// Posted to webhooks when a build's interface differs from the artifact it replaced.
+#[derive(Debug, Clone, Serialize)]
+pub struct InterfaceEvent {
+    /// Always `interface_changed`, telling this apart from build events.
+    pub event: &'static str,
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    pub artifact_hash: String,
+    pub changes: Vec<InterfaceChange>,
+}
+
+impl fmt::Display for InterfaceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Interface of {} changed since its last build:",
+            self.crate_name
+        )?;
+        for change in &self.changes {
+            write!(f, "\n  {change}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A binding, push constant block or entry point that was added, removed or changed.
+#[derive(Debug, Clone, Serialize)]
+pub struct InterfaceChange {
+    /// `binding`, `push_constants` or `entry_point`.
+    pub kind: &'static str,
+    /// What changed, e.g. `binding 2 in set 0`.
+    pub item: String,
+    /// What it was, if it isn't new.
+    pub before: Option<String>,
+    /// What it is now, if it wasn't removed.
+    pub after: Option<String>,
+}
+
+impl fmt::Display for InterfaceChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => {
+                write!(f, "{} changed from {before} to {after}", self.item)
+            }
+            (None, Some(after)) => write!(f, "{} added as {after}", self.item),
+            (Some(before), None) => write!(f, "{} removed, was {before}", self.item),
+            (None, None) => write!(f, "{}", self.item),
+        }
+    }
+}
+
+impl Interface {
+    /// Reflect the interface of `modules`, naming entry points as they appear in the output
+    /// after `rules` are applied.
+    pub fn new(modules: &RustGpuBuilderModules, rules: &[EntryPointRename]) -> Self {
+        let mut interface = Interface::default();
+        for (name, module) in reflect::named_modules(modules, "") {
+            let Ok(module) = reflect::parse(module) else {
+                continue
+            };
+
+            // Modules of one crate share their descriptor sets, so the first to declare a
+            // binding speaks for it
+            for binding in reflect::bindings(&module) {
+                let Some((class, ty)) = pointee(&module, binding.variable) else {
+                    continue
+                };
+                interface
+                    .bindings
+                    .entry((binding.set, binding.binding))
+                    .or_insert_with(|| (descriptor_type(&module, class, ty), layout(&module, ty)));
+            }
+
+            let push_constants = module
+                .types_global_values
+                .iter()
+                .filter(|inst| {
+                    inst.class.opcode == Op::Variable
+                        && inst.operands.first()
+                            == Some(&Operand::StorageClass(StorageClass::PushConstant))
+                })
+                .filter_map(|inst| pointee(&module, inst.result_id?))
+                .map(|(_, ty)| layout(&module, ty))
+                .collect::<Vec<_>>();
+            if !push_constants.is_empty() {
+                interface
+                    .push_constants
+                    .insert(name, push_constants.join("; "));
+            }
+
+            let functions = reflect::entry_point_functions(&module);
+            for (model, name) in reflect::entry_points(&module) {
+                if let Some(&function) = functions.get(&name) {
+                    interface.entry_points.insert(
+                        rename::rename(rules, &name),
+                        signature(&module, model, function),
+                    );
+                }
+            }
+        }
+        interface
+    }
+
+    /// What changed from `previous` to this interface.
+    pub fn changes(&self, previous: &Interface) -> Vec<InterfaceChange> {
+        let mut changes = vec![];
+
+        for (&(set, binding), before, after) in compare(&previous.bindings, &self.bindings) {
+            // Name only the half of a binding that changed
+            let (before, after) = match (before, after) {
+                (Some((before, layout)), Some((after, same))) if layout == same => {
+                    (Some(before.clone()), Some(after.clone()))
+                }
+                (Some((ty, before)), Some((same, after))) if ty == same => {
+                    (Some(format!("`{before}`")), Some(format!("`{after}`")))
+                }
+                (before, after) => {
+                    let describe = |(ty, layout): &(String, String)| format!("{ty} `{layout}`");
+                    (before.map(describe), after.map(describe))
+                }
+            };
+            changes.push(InterfaceChange {
+                kind: "binding",
+                item: format!("binding {binding} in set {set}"),
+                before,
+                after,
+            });
+        }
+
+        for (module, before, after) in compare(&previous.push_constants, &self.push_constants) {
+            changes.push(InterfaceChange {
+                kind: "push_constants",
+                item: if module.is_empty() {
+                    "push constants".to_string()
+                } else {
+                    format!("push constants of {module}")
+                },
+                before: before.map(|layout| format!("`{layout}`")),
+                after: after.map(|layout| format!("`{layout}`")),
+            });
+        }
+
+        for (name, before, after) in compare(&previous.entry_points, &self.entry_points) {
+            changes.push(InterfaceChange {
+                kind: "entry_point",
+                item: format!("entry point {name}"),
+                before: before.map(|signature| format!("`{signature}`")),
+                after: after.map(|signature| format!("`{signature}`")),
+            });
+        }
+
+        changes
+    }
+}
+
+/// Keys whose values differ between `before` and `after`, with each side's value if it has one.
+fn compare<'a, K: Ord, V: PartialEq>(
+    before: &'a BTreeMap<K, V>,
+    after: &'a BTreeMap<K, V>,
+) -> Vec<(&'a K, Option<&'a V>, Option<&'a V>)> {
+    before
+        .keys()
+        .chain(after.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|key| (key, before.get(key), after.get(key)))
+        .filter(|(_, before, after)| before != after)
+        .collect()
+}
+
+/// Storage class and pointee type of the global `variable`.
+fn pointee(module: &Module, variable: Word) -> Option<(StorageClass, Word)> {
+    let pointer = reflect::global(module, variable)?.result_type?;
+    match reflect::global(module, pointer)?.operands.as_slice() {
+        [Operand::StorageClass(class), Operand::IdRef(ty)] => Some((*class, *ty)),
+        _ => None,
+    }
+}
+
+/// The Vulkan descriptor type of a binding of type `ty` in `class`.
+fn descriptor_type(module: &Module, class: StorageClass, ty: Word) -> String {
+    // Arrays of descriptors are the type of their elements
+    let mut ty = ty;
+    while let Some(inst) = reflect::global(module, ty)
+        .filter(|inst| matches!(inst.class.opcode, Op::TypeArray | Op::TypeRuntimeArray))
+    {
+        match inst.operands.first() {
+            Some(Operand::IdRef(element)) => ty = *element,
+            _ => break,
+        }
+    }
+
+    let inst = reflect::global(module, ty);
+    let descriptor = match class {
+        StorageClass::StorageBuffer => "StorageBuffer",
+        StorageClass::Uniform if reflect::has_decoration(module, ty, Decoration::BufferBlock) => {
+            "StorageBuffer"
+        }
+        StorageClass::Uniform => "UniformBuffer",
+        StorageClass::UniformConstant => {
+            match inst.map(|inst| (inst.class.opcode, inst.operands.as_slice())) {
+                Some((Op::TypeSampler, _)) => "Sampler",
+                Some((Op::TypeSampledImage, _)) => "CombinedImageSampler",
+                Some((Op::TypeAccelerationStructureKHR, _)) => "AccelerationStructure",
+                Some((Op::TypeImage, [_, Operand::Dim(dim), _, _, _, sampled, ..])) => {
+                    let storage = *sampled == Operand::LiteralInt32(2);
+                    match dim {
+                        Dim::DimSubpassData => "InputAttachment",
+                        Dim::DimBuffer if storage => "StorageTexelBuffer",
+                        Dim::DimBuffer => "UniformTexelBuffer",
+                        _ if storage => "StorageImage",
+                        _ => "SampledImage",
+                    }
+                }
+                _ => "UniformConstant",
+            }
+        }
+        class => return format!("{class:?}"),
+    };
+    descriptor.to_string()
+}
+
+/// The name of `ty`, with the members and offsets of any structs in it, as host code
+/// has to lay out its side.
+fn layout(module: &Module, ty: Word) -> String {
+    let Some(inst) = reflect::global(module, ty) else {
+        return reflect::type_name(module, ty)
+    };
+    match (inst.class.opcode, inst.operands.as_slice()) {
+        (Op::TypeStruct, members) => {
+            let members = members
+                .iter()
+                .enumerate()
+                .filter_map(|(index, member)| {
+                    let Operand::IdRef(member) = member else {
+                        return None
+                    };
+                    let layout = layout(module, *member);
+                    Some(match offset(module, ty, index as u32) {
+                        Some(offset) => format!("{offset}: {layout}"),
+                        None => layout,
+                    })
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            // Ids are renumbered from build to build, so unnamed structs go by their members
+            match reflect::name_of(module, ty) {
+                Some(name) => format!("{name} {{ {members} }}"),
+                None => format!("{{ {members} }}"),
+            }
+        }
+        (Op::TypeArray | Op::TypeRuntimeArray, [Operand::IdRef(element), ..]) => {
+            reflect::type_name(module, ty).replacen(
+                &reflect::type_name(module, *element),
+                &layout(module, *element),
+                1,
+            )
+        }
+        _ => reflect::type_name(module, ty),
+    }
+}
+
+/// The `Offset` decoration of member `index` of the struct `ty`.
+fn offset(module: &Module, ty: Word, index: u32) -> Option<u32> {
+    module
+        .annotations
+        .iter()
+        .filter(|inst| inst.class.opcode == Op::MemberDecorate)
+        .find_map(|inst| match inst.operands.as_slice() {
+            [target, member, decoration, Operand::LiteralInt32(offset)]
+                if *target == Operand::IdRef(ty)
+                    && *member == Operand::LiteralInt32(index)
+                    && *decoration == Operand::Decoration(Decoration::Offset) =>
+            {
+                Some(*offset)
+            }
+            _ => None,
+        })
+}
+
+/// An entry point's stage, and its located inputs and outputs or its workgroup size.
+fn signature(module: &Module, model: ExecutionModel, function: Word) -> String {
+    let stage = stages::stage_name(model);
+    match model {
+        ExecutionModel::GLCompute | ExecutionModel::Kernel => {
+            match reflect::local_size(module, function) {
+                Some([x, y, z]) => format!("{stage} {x}x{y}x{z}"),
+                None => stage.to_string(),
+            }
+        }
+        _ => {
+            let [inputs, outputs] = pipelines::signature(module, model, function);
+            format!("{stage}({}) -> ({})", inputs.join(", "), outputs.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rspirv::{binary::Assemble, dr::Builder, spirv};
+
+    use super::*;
+
+    /// A module with a storage buffer at binding 2 of set 0, push constants, a vertex entry point
+    /// and a compute entry point, or with `changed` a uniform buffer laid out differently, a
+    /// vertex output and no compute entry point.
+    fn module(changed: bool) -> RustGpuBuilderModules {
+        let mut b = Builder::new();
+        b.capability(spirv::Capability::Shader);
+        b.memory_model(spirv::AddressingModel::Logical, spirv::MemoryModel::GLSL450);
+        let void = b.type_void();
+        let f32_type = b.type_float(32);
+        let u32_type = b.type_int(32, 0);
+        let vec4 = b.type_vector(f32_type, 4);
+        let function_type = b.type_function(void, vec![]);
+
+        let buffer = b.type_struct(vec![f32_type, u32_type]);
+        b.decorate(buffer, Decoration::Block, vec![]);
+        for (member, offset) in [(0, 0), (1, if changed { 8 } else { 4 })] {
+            b.member_decorate(
+                buffer,
+                member,
+                Decoration::Offset,
+                vec![Operand::LiteralInt32(offset)],
+            );
+        }
+        let class = if changed {
+            StorageClass::Uniform
+        } else {
+            StorageClass::StorageBuffer
+        };
+        let pointer = b.type_pointer(None, class, buffer);
+        let binding = b.variable(pointer, None, class, None);
+        b.decorate(
+            binding,
+            Decoration::DescriptorSet,
+            vec![Operand::LiteralInt32(0)],
+        );
+        b.decorate(binding, Decoration::Binding, vec![Operand::LiteralInt32(2)]);
+
+        let constants = b.type_struct(vec![vec4]);
+        b.name(constants, "Constants");
+        b.member_decorate(
+            constants,
+            0,
+            Decoration::Offset,
+            vec![Operand::LiteralInt32(0)],
+        );
+        let pointer = b.type_pointer(None, StorageClass::PushConstant, constants);
+        b.variable(pointer, None, StorageClass::PushConstant, None);
+
+        let mut outputs = vec![];
+        if changed {
+            let pointer = b.type_pointer(None, StorageClass::Output, vec4);
+            let output = b.variable(pointer, None, StorageClass::Output, None);
+            b.decorate(output, Decoration::Location, vec![Operand::LiteralInt32(0)]);
+            outputs.push(output);
+        }
+
+        let entry_point = |b: &mut Builder, model, name, variables| {
+            let function = b
+                .begin_function(void, None, spirv::FunctionControl::NONE, function_type)
+                .unwrap();
+            b.begin_block(None).unwrap();
+            b.ret().unwrap();
+            b.end_function().unwrap();
+            b.entry_point(model, function, name, variables);
+            function
+        };
+        entry_point(&mut b, ExecutionModel::Vertex, "main_vs", outputs);
+        if !changed {
+            let function = entry_point(&mut b, ExecutionModel::GLCompute, "main_cs", vec![]);
+            b.execution_mode(function, spirv::ExecutionMode::LocalSize, vec![64, 1, 1]);
+        }
+
+        let words = b.module().assemble();
+        RustGpuBuilderModules::Single(words.iter().flat_map(|word| word.to_le_bytes()).collect())
+    }
+
+    #[test]
+    fn reflects_what_hosts_bind_to() {
+        let interface = Interface::new(&module(false), &[]);
+        assert_eq!(
+            interface.bindings,
+            [(
+                (0, 2),
+                (
+                    "StorageBuffer".to_string(),
+                    "{ 0: f32, 4: u32 }".to_string()
+                )
+            )]
+            .into()
+        );
+        assert_eq!(
+            interface.push_constants,
+            [("".to_string(), "Constants { 0: vec4<f32> }".to_string())].into()
+        );
+        assert_eq!(
+            interface.entry_points,
+            [
+                ("main_cs".to_string(), "compute 64x1x1".to_string()),
+                ("main_vs".to_string(), "vertex() -> ()".to_string()),
+            ]
+            .into()
+        );
+    }
+
+    #[test]
+    fn reports_nothing_for_an_unchanged_interface() {
+        let interface = Interface::new(&module(false), &[]);
+        assert!(interface
+            .changes(&Interface::new(&module(false), &[]))
+            .is_empty());
+    }
+
+    #[test]
+    fn reports_changes() {
+        let before = Interface::new(&module(false), &[]);
+        let after = Interface::new(&module(true), &[]);
+        let changes = after
+            .changes(&before)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            [
+                "binding 2 in set 0 changed from StorageBuffer `{ 0: f32, 4: u32 }` to UniformBuffer `{ 0: f32, 8: u32 }`",
+                "entry point main_cs removed, was `compute 64x1x1`",
+                "entry point main_vs changed from `vertex() -> ()` to `vertex() -> (location 0: vec4<f32>)`",
+            ]
+        );
+    }
+
+    #[test]
+    fn names_entry_points_as_renamed() {
+        let rules = ["^main_=".parse().unwrap()];
+        let interface = Interface::new(&module(false), &rules);
+        assert_eq!(
+            interface.entry_points.keys().collect::<Vec<_>>(),
+            ["cs", "vs"]
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+
+use rspirv::{dr::Module, spirv::ExecutionModel};
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+use serde::Serialize;
+
+use crate::{
+    reflect::{self, Binding},
+    rename::{self, EntryPointRename},
+};
+
+/// A descriptor binding a kernel reads or writes.
+#[derive(Debug, Clone, Serialize)]
+pub struct KernelBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+impl KernelBinding {
+    pub fn new(module: &Module, binding: Binding) -> Self {
+        let ty = reflect::global(module, binding.variable)
+            .and_then(|inst| inst.result_type)
+            .map(|ty| reflect::type_name(module, ty))
+            .unwrap_or_default();
+        KernelBinding {
+            set: binding.set,
+            binding: binding.binding,
+            name: reflect::name_of(module, binding.variable),
+            ty,
+        }
+    }
+}
+
+/// What a host needs to know to dispatch a compute entry point or OpenCL kernel.
+#[derive(Debug, Clone, Serialize)]
+pub struct KernelSignature {
+    /// Types of the kernel function's parameters, in order.
+    pub params: Vec<String>,
+    /// Bindings statically used anywhere in the kernel's module.
+    pub bindings: Vec<KernelBinding>,
+    /// Fixed workgroup size, if the kernel declares one.
+    pub workgroup_size: Option<[u32; 3]>,
+}
+
+/// Signatures of every `Kernel` and `GLCompute` entry point declared by `modules`,
+/// keyed by their names as they appear in the output after `rules` are applied.
+pub fn signatures(
+    modules: &RustGpuBuilderModules,
+    rules: &[EntryPointRename],
+) -> BTreeMap<String, KernelSignature> {
+    let mut signatures = BTreeMap::new();
+
+    for (_, module) in reflect::named_modules(modules, "") {
+        let Ok(module) = reflect::parse(module) else {
+            continue
+        };
+
+        let functions = reflect::entry_point_functions(&module);
+        let bindings = reflect::bindings(&module)
+            .into_iter()
+            .filter(|binding| reflect::is_statically_used(&module, binding.variable))
+            .map(|binding| KernelBinding::new(&module, binding))
+            .collect::<Vec<_>>();
+
+        for (model, name) in reflect::entry_points(&module) {
+            if !matches!(model, ExecutionModel::Kernel | ExecutionModel::GLCompute) {
+                continue;
+            }
+            let Some(function) = functions.get(&name) else {
+                continue
+            };
+
+            let params = module
+                .functions
+                .iter()
+                .find(|f| f.def_id() == Some(*function))
+                .map(|f| {
+                    f.parameters
+                        .iter()
+                        .filter_map(|param| param.result_type)
+                        .map(|ty| reflect::type_name(&module, ty))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            signatures.insert(
+                rename::rename(rules, &name),
+                KernelSignature {
+                    params,
+                    bindings: bindings.clone(),
+                    workgroup_size: reflect::local_size(&module, *function),
+                },
+            );
+        }
+    }
+
+    signatures
+}
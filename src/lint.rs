@@ -0,0 +1,143 @@
+use std::{collections::BTreeSet, fmt, path::PathBuf};
+
+use clap::Args;
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+use tracing::warn;
+
+use crate::{
+    reflect,
+    rename::{self, EntryPointRename},
+};
+
+/// Tidiness checks run on every module after a successful compile.
+#[derive(Debug, Clone, Default, Args)]
+#[command(next_help_heading = "Lints")]
+pub struct Lints {
+    /// File listing the expected entry point names, one per line.
+    ///
+    /// Names are compared after --entry-point-rename rules are applied. Entry points present in the SPIR-V but missing from this file are reported.
+    /// Blank lines and lines starting with `#` are ignored.
+    #[arg(long)]
+    pub expected_entry_points: Option<PathBuf>,
+    /// Report descriptor bindings that are declared but never statically used.
+    ///
+    /// Unused bindings are normally stripped unless --preserve-bindings is set.
+    #[arg(long, default_value = "false")]
+    pub unused_bindings: bool,
+    /// Fail the build on lint findings instead of warning.
+    #[arg(long, default_value = "false")]
+    pub deny_lints: bool,
+}
+
+/// Lint findings which failed the build under --deny-lints, or modules that couldn't be linted.
+#[derive(Debug, Clone)]
+pub struct LintReport(pub Vec<String>);
+
+impl fmt::Display for LintReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} lint finding(s):", self.0.len())?;
+        for finding in &self.0 {
+            write!(f, "\n  {finding}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Lints {
+    /// Whether any lint is enabled.
+    fn enabled(&self) -> bool {
+        self.expected_entry_points.is_some() || self.unused_bindings
+    }
+
+    /// Read the expected entry points manifest.
+    fn expected(&self) -> std::io::Result<Option<BTreeSet<String>>> {
+        let Some(path) = &self.expected_entry_points else {
+            return Ok(None)
+        };
+
+        let manifest = std::fs::read_to_string(path)?;
+        Ok(Some(
+            manifest
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string)
+                .collect(),
+        ))
+    }
+
+    /// Lint each module, warning about findings or failing under --deny-lints.
+    ///
+    /// Entry points are named as they appear in the output, after `rules` are applied.
+    /// A module that can't be parsed fails the build either way, rather than going unlinted.
+    pub fn check(
+        &self,
+        modules: &RustGpuBuilderModules,
+        single_name: &str,
+        rules: &[EntryPointRename],
+    ) -> Result<(), LintReport> {
+        if !self.enabled() {
+            return Ok(());
+        }
+
+        let expected = match self.expected() {
+            Ok(expected) => expected,
+            Err(e) => {
+                return Err(LintReport(vec![format!(
+                    "Failed to read expected entry points from {:?}: {e:}",
+                    self.expected_entry_points.as_ref().unwrap()
+                )]))
+            }
+        };
+
+        let mut findings = vec![];
+        let mut unlinted = vec![];
+        for (name, bytes) in reflect::named_modules(modules, single_name) {
+            let module = match reflect::parse(bytes) {
+                Ok(module) => module,
+                Err(e) => {
+                    unlinted.push(format!("{name}: failed to parse for lints: {e:}"));
+                    continue;
+                }
+            };
+
+            if let Some(expected) = &expected {
+                for entry_point in reflect::entry_point_names(&module) {
+                    let entry_point = rename::rename(rules, &entry_point);
+                    if !expected.contains(&entry_point) {
+                        findings.push(format!(
+                            "{name}: entry point {entry_point} is not listed as expected"
+                        ));
+                    }
+                }
+            }
+
+            if self.unused_bindings {
+                for binding in reflect::bindings(&module) {
+                    if !reflect::is_statically_used(&module, binding.variable) {
+                        let variable = reflect::name_of(&module, binding.variable)
+                            .unwrap_or_else(|| format!("%{}", binding.variable));
+                        findings.push(format!(
+                            "{name}: binding {} in set {} ({variable}) is never used",
+                            binding.binding, binding.set
+                        ));
+                    }
+                }
+            }
+        }
+
+        if self.deny_lints {
+            unlinted.extend(findings);
+        } else {
+            for finding in findings {
+                warn!("{finding}");
+            }
+        }
+
+        if unlinted.is_empty() {
+            Ok(())
+        } else {
+            Err(LintReport(unlinted))
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Advisory file locks, so builder instances sharing an output directory or run manifest
+//! take turns writing instead of the last writer silently winning.
+//!
+//! Locks are held through an open lock file, which is left in place afterwards:
+//! removing it while another instance waits on it would let two writers in at once.
+
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+};
+
+use tracing::info;
+
+/// Name of the lock file builds take in their output directory while writing to it.
+const OUTPUT_LOCK: &str = ".rust-gpu-builder.lock";
+
+/// A held lock, released when dropped.
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    /// Take the lock on `path` for writing, waiting for any other holder to release it.
+    pub fn exclusive(path: &Path) -> io::Result<Self> {
+        let file = open(path)?;
+        sys::lock(&file, true, true)?;
+        Ok(FileLock { _file: file })
+    }
+
+    /// Take the lock on `path` alongside other shared holders, waiting for any exclusive one.
+    pub fn shared(path: &Path) -> io::Result<Self> {
+        let file = open(path)?;
+        sys::lock(&file, false, true)?;
+        Ok(FileLock { _file: file })
+    }
+
+    /// Take the lock on `path` for writing, or `None` if someone else holds it.
+    pub fn try_exclusive(path: &Path) -> io::Result<Option<Self>> {
+        let file = open(path)?;
+        match sys::lock(&file, true, false) {
+            Ok(()) => Ok(Some(FileLock { _file: file })),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Take the lock on `dir` for writing build output, waiting for any other build writing to it.
+pub async fn output_dir(dir: &Path) -> io::Result<FileLock> {
+    let path = dir.join(OUTPUT_LOCK);
+    if let Some(lock) = FileLock::try_exclusive(&path)? {
+        return Ok(lock);
+    }
+
+    info!("Waiting for another build writing to {dir:?}...");
+    blocking::unblock(move || FileLock::exclusive(&path)).await
+}
+
+/// Hidden sibling of `path` locked to coordinate writes to it, with `suffix` telling
+/// apart locks with different purposes.
+pub fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{file_name}.{suffix}"))
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(path)
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::{fs::File, io, os::unix::io::AsRawFd};
+
+    pub fn lock(file: &File, exclusive: bool, wait: bool) -> io::Result<()> {
+        let mut operation = if exclusive {
+            libc::LOCK_EX
+        } else {
+            libc::LOCK_SH
+        };
+        if !wait {
+            operation |= libc::LOCK_NB;
+        }
+
+        loop {
+            if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+                return Ok(());
+            }
+            let e = io::Error::last_os_error();
+            if e.kind() != io::ErrorKind::Interrupted {
+                return Err(e);
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::{fs::File, io, os::windows::io::AsRawHandle};
+
+    use windows_sys::Win32::{
+        Foundation::ERROR_LOCK_VIOLATION,
+        Storage::FileSystem::{LockFileEx, LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY},
+        System::IO::OVERLAPPED,
+    };
+
+    pub fn lock(file: &File, exclusive: bool, wait: bool) -> io::Result<()> {
+        let mut flags = 0;
+        if exclusive {
+            flags |= LOCKFILE_EXCLUSIVE_LOCK;
+        }
+        if !wait {
+            flags |= LOCKFILE_FAIL_IMMEDIATELY;
+        }
+
+        // Lock the whole file, however long it gets
+        let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+        let locked = unsafe {
+            LockFileEx(
+                file.as_raw_handle() as _,
+                flags,
+                0,
+                u32::MAX,
+                u32::MAX,
+                &mut overlapped,
+            )
+        };
+        if locked != 0 {
+            return Ok(());
+        }
+
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() == Some(ERROR_LOCK_VIOLATION as i32) {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        Err(e)
+    }
+}
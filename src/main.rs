@@ -1,26 +1,126 @@
+mod assets;
+mod bench;
+mod budget;
+mod build_config;
+mod bundle;
+mod cache;
+mod cancel;
+mod cargo;
+mod checksum;
+mod compress;
+mod console;
+mod deny;
+mod diagnostics;
+mod diff;
+mod doctor;
+mod emit;
+mod extract;
+mod graph;
+mod heartbeat;
+mod history;
+mod hooks;
+mod host_test;
+mod ice;
+mod inotify;
+mod interface;
+mod kernels;
+mod lint;
+mod lock;
+mod matrix;
+mod metadata;
+mod naga_ir;
+mod output;
+mod pack;
+mod patch;
+mod paths;
+mod pipeline_cache;
+mod pipelines;
+mod plugin;
+mod priority;
+mod projects;
+mod provenance;
+mod queue;
+mod reflect;
+mod reflection;
+mod rename;
+mod restart;
+mod run_manifest;
+mod serve;
+mod signing;
+mod source;
+mod stages;
+mod state;
+mod symbols;
+mod target;
+mod timing;
+mod validate;
+mod webgpu;
+mod webhook;
+mod worker;
+
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     error::Error,
+    ffi::OsString,
+    fmt,
+    net::{SocketAddr, TcpListener},
     path::{Path, PathBuf},
     str::FromStr,
+    time::{Duration, Instant, SystemTime},
 };
 
 use rust_gpu_builder_shared::{RustGpuBuilderModules, RustGpuBuilderOutput};
 
-use clap::{error::ErrorKind, Parser};
+use clap::{error::ErrorKind, Args, Parser, Subcommand};
+use serde::Serialize;
 
 use async_channel::{unbounded, Receiver, Sender};
-use async_executor::Executor;
-use easy_parallel::Parallel;
+use async_executor::{Executor, Task};
+use async_io::{Async, Timer};
 use futures_lite::future;
 
-use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::{event::ModifyKind, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 
 use spirv_builder::{
     Capability, CompileResult, MetadataPrintout, SpirvBuilder, SpirvBuilderError, SpirvMetadata,
 };
 
-use tracing::{error, info};
+use spirv_tools::val::ValidatorOptions;
+
+use tracing::{error, info, warn};
+
+use assets::AssetGlob;
+use budget::{BudgetReport, Budgets};
+use build_config::BuildConfig;
+use bundle::{Bundle, BundleInput, Collisions};
+use cancel::CancelToken;
+use cargo::CargoOptions;
+use compress::Compression;
+use console::ColorChoice;
+use deny::{DenyList, DenyReport};
+use emit::{Emit, EmitInput, Emitter};
+use heartbeat::{Beat, HealthEvent, Heartbeat};
+use history::History;
+use hooks::HookError;
+use ice::IceSummary;
+use inotify::WatchStrategy;
+use interface::{Interface, InterfaceEvent};
+use lint::{LintReport, Lints};
+use output::{Artifact, TemplateVars};
+use pipelines::{Pipeline, PipelineReport};
+use plugin::Plugin;
+use projects::{Project, Projects};
+use queue::{BuildQueue, Next, Priority, Pushed};
+use rename::EntryPointRename;
+use restart::SelfWatch;
+use run_manifest::{ManifestArtifact, RunManifest};
+use serve::{JobId, Jobs, Server};
+use source::GitSource;
+use state::State;
+use timing::{Phase, Timings};
+use validate::{Validation, ValidationReport};
+use webhook::{BuildEvent, BuildStatus};
+use worker::Compiled;
 
 #[derive(Debug, Copy, Clone)]
 pub enum OutputFormat {
@@ -28,6 +128,20 @@ pub enum OutputFormat {
     Messagepack,
 }
 
+impl OutputFormat {
+    /// Serialize an artifact in this format.
+    fn serialize(self, output: &impl Serialize) -> Vec<u8> {
+        match self {
+            OutputFormat::Json => serde_json::to_string_pretty(output)
+                .expect("Failed to serialize output")
+                .into_bytes(),
+            OutputFormat::Messagepack => {
+                rmp_serde::to_vec_named(output).expect("Failed to serialize output")
+            }
+        }
+    }
+}
+
 impl FromStr for OutputFormat {
     type Err = &'static str;
 
@@ -40,35 +154,669 @@ impl FromStr for OutputFormat {
     }
 }
 
+/// Bundle of build settings for a particular kind of consumer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Preset {
+    /// wgpu on the web: SPIR-V it can ingest, translated to WGSL and checked against WebGPU's limits.
+    WebGpu,
+}
+
+impl FromStr for Preset {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "webgpu" => Ok(Self::WebGpu),
+            _ => Err("Unrecognized preset"),
+        }
+    }
+}
+
 /// Clap application struct.
 #[derive(Debug, Clone, Parser)]
-#[command(author, version, about, long_about = None)]
-struct ShaderBuilder {
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    arg_required_else_help = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    builder: Option<ShaderBuilder>,
+    /// Whether to color output: `auto` colors it when writing to a terminal and `NO_COLOR` isn't set.
+    #[arg(long, global = true, default_value = "auto")]
+    color: ColorChoice,
+}
+
+/// Utility subcommands, run instead of a build.
+#[derive(Debug, Clone, Subcommand)]
+enum Command {
+    /// Re-point a versioned output path at a previous build.
+    Rollback {
+        /// Output path previously written with --versioned-output.
+        output_path: PathBuf,
+        /// How many builds to step back from the current one.
+        #[arg(long, default_value = "1")]
+        steps: usize,
+    },
+    /// Print the shader crate's local dependency graph, and the watch paths covering it, as DOT.
+    Graph {
+        /// Shader crate to inspect.
+        path_to_crate: PathBuf,
+        /// Watch paths to check coverage of, as passed to a build.
+        #[arg(short, long)]
+        watch_paths: Vec<PathBuf>,
+    },
+    /// Compare two artifacts' entry points, module sizes and capabilities.
+    Diff {
+        artifact_a: PathBuf,
+        artifact_b: PathBuf,
+        /// Also print an instruction-level diff of each changed module.
+        #[arg(long, default_value = "false")]
+        instructions: bool,
+        /// Exit with status 1 if the artifacts differ.
+        #[arg(long, default_value = "false")]
+        exit_code: bool,
+        /// zstd dictionary the artifacts were compressed with, as given to --compress-dictionary.
+        #[arg(long)]
+        dictionary: Option<PathBuf>,
+    },
+    /// Remove stale intermediate and versioned build files.
+    #[command(arg_required_else_help = true)]
+    Clean {
+        /// Remove spirv-builder intermediates older than the TTL.
+        #[arg(long, default_value = "false")]
+        cache: bool,
+        /// Remove versioned builds of this output path older than the TTL,
+        /// except the one it currently points at.
+        ///
+        /// Can be specified multiple times.
+        #[arg(long)]
+        artifacts: Vec<PathBuf>,
+        /// Files last modified longer ago than this are removed, e.g. `12h` or `7d`.
+        #[arg(long, value_parser = cache::parse_duration, default_value = "7d")]
+        ttl: Duration,
+        /// Report what would be removed without removing anything.
+        #[arg(long, default_value = "false")]
+        dry_run: bool,
+    },
+    /// Combine the artifacts of several shader crates into one file, keyed by crate.
+    Bundle {
+        /// File to write the bundle to.
+        output_path: PathBuf,
+        /// Artifacts to include, as `<crate>=<path>`, or a path to key by its file stem.
+        #[arg(required = true, value_parser = BundleInput::from_str)]
+        artifacts: Vec<BundleInput>,
+        /// The format to write the bundle in.
+        #[arg(long, default_value = "Messagepack")]
+        output_format: OutputFormat,
+        /// What to do about entry points named the same in more than one crate:
+        /// `error`, or `namespace` to key every entry point in the manifest by its crate.
+        #[arg(long, default_value = "error")]
+        collisions: Collisions,
+        /// zstd dictionary the artifacts were compressed with, as given to --compress-dictionary.
+        #[arg(long)]
+        dictionary: Option<PathBuf>,
+    },
+    /// Train a zstd dictionary for --compress-dictionary on a corpus of past artifacts.
+    TrainDict {
+        /// File to write the dictionary to.
+        output_path: PathBuf,
+        /// Artifacts built without --compress, e.g. those kept by --keep-artifacts.
+        #[arg(required = true)]
+        artifacts: Vec<PathBuf>,
+        /// Largest dictionary to train, in bytes.
+        #[arg(long, default_value = "112640")]
+        max_size: usize,
+    },
+    /// Build every combination of targets, profiles and feature sets listed in a JSON config.
+    Matrix {
+        /// Matrix config file.
+        config: PathBuf,
+        /// Number of jobs to build at once.
+        #[arg(short, long, default_value = "1")]
+        jobs: usize,
+        /// File to write the manifest of jobs and their artifacts to.
+        #[arg(long, default_value = "matrix-manifest.json")]
+        manifest: PathBuf,
+        /// Also write a run manifest of every artifact the matrix wrote, as `--run-manifest`
+        /// does for a single build.
+        #[arg(long)]
+        run_manifest: Option<PathBuf>,
+    },
+    /// Download the shader crate's dependencies, so it can be built with --offline.
+    Fetch {
+        /// Shader crate to fetch dependencies for.
+        path_to_crate: PathBuf,
+        /// rust-gpu compile target the crate will be built for.
+        #[arg(short, long, default_value = "spirv-unknown-vulkan1.2")]
+        target: String,
+        /// Fail instead of updating the crate's Cargo.lock if it's out of date.
+        #[arg(long, default_value = "false")]
+        locked: bool,
+    },
+    /// Build a shader crate repeatedly and report how long each phase took.
+    Bench {
+        /// Shader crate to build.
+        path_to_crate: PathBuf,
+        /// Number of timed builds.
+        #[arg(long, default_value = "5")]
+        iterations: usize,
+        /// Start every build from an empty spirv-builder target directory, recompiling `core`
+        /// and spirv-std, instead of only recompiling the shader crate.
+        #[arg(long, default_value = "false")]
+        clean: bool,
+        /// Also write the timings to this directory in criterion's format.
+        #[arg(long)]
+        criterion: Option<PathBuf>,
+        /// Build arguments, as given after the shader crate's path when building normally.
+        #[arg(last = true)]
+        args: Vec<OsString>,
+    },
+    /// Build a shader crate, then run the host's tests against the fresh artifact, failing if
+    /// either fails.
+    ///
+    /// The tests find the artifact through `RUST_GPU_BUILDER_ARTIFACT`, alongside `_ARTIFACT_HASH`,
+    /// `_CRATE`, `_TARGET`, `_PROFILE`, `_ENTRY_POINTS` and, with --spv-dir, `_SPV_DIR`.
+    Test {
+        /// Shader crate to build.
+        path_to_crate: PathBuf,
+        /// Shell command running the host's tests, e.g. `cargo test -p renderer`.
+        #[arg(
+            long,
+            required_unless_present = "host_crate",
+            conflicts_with = "host_crate"
+        )]
+        command: Option<String>,
+        /// Companion crate to run `cargo test` in instead.
+        #[arg(long)]
+        host_crate: Option<PathBuf>,
+        /// Build arguments, as given after the shader crate's path when building normally,
+        /// which must include an output path.
+        #[arg(last = true)]
+        args: Vec<OsString>,
+    },
+    /// Generate an ed25519 key pair for --signing-key.
+    Keygen {
+        /// File to write the secret key to. The public key is written beside it with a `.pub` extension.
+        path: PathBuf,
+    },
+    /// Check an artifact against its `<artifact>.sig` signature and embedded checksums.
+    Verify {
+        artifact: PathBuf,
+        /// Public key written by `keygen`.
+        #[arg(long)]
+        public_key: PathBuf,
+        /// zstd dictionary the artifact was compressed with, as given to --compress-dictionary.
+        #[arg(long)]
+        dictionary: Option<PathBuf>,
+    },
+    /// Print an artifact's entry points and modules.
+    Info {
+        artifact: PathBuf,
+        /// Also print the options the artifact was built with.
+        #[arg(long, default_value = "false")]
+        show_config: bool,
+        /// zstd dictionary the artifact was compressed with, as given to --compress-dictionary.
+        #[arg(long)]
+        dictionary: Option<PathBuf>,
+    },
+    /// Print the entry points, stages, kernel signatures, capabilities and bindings a build
+    /// reflects out of its modules as JSON, for an artifact or a raw `.spv` module.
+    Reflect {
+        artifact: PathBuf,
+        /// zstd dictionary the artifact was compressed with, as given to --compress-dictionary.
+        #[arg(long)]
+        dictionary: Option<PathBuf>,
+    },
+    /// Write a single entry point's module out of an artifact as SPIR-V, without rebuilding.
+    Extract {
+        artifact: PathBuf,
+        /// Entry point to extract, as listed by `info`.
+        #[arg(long)]
+        entry_point: String,
+        /// File to write the module to.
+        #[arg(short, long)]
+        output_path: PathBuf,
+        /// zstd dictionary the artifact was compressed with, as given to --compress-dictionary.
+        #[arg(long)]
+        dictionary: Option<PathBuf>,
+    },
+    /// Check the toolchain, SPIRV-Tools, file watching and, if given, a shader crate
+    /// and output path are set up to build, suggesting fixes for anything that isn't.
+    Doctor {
+        /// Shader crate to check the toolchain and spirv-std version of.
+        path_to_crate: Option<PathBuf>,
+        /// Output path to check is writable.
+        #[arg(short, long)]
+        output_path: Option<PathBuf>,
+    },
+}
+
+impl Command {
+    fn run(self) -> Result<(), Box<dyn Error>> {
+        match self {
+            Command::Rollback { output_path, steps } => {
+                let versioned_path = future::block_on(output::rollback(&output_path, steps))?;
+                info!("Rolled {output_path:?} back to {versioned_path:?}");
+            }
+            Command::Graph {
+                path_to_crate,
+                watch_paths,
+            } => print!("{}", graph::dot(&path_to_crate, &watch_paths)?),
+            Command::Diff {
+                artifact_a,
+                artifact_b,
+                instructions,
+                exit_code,
+                dictionary,
+            } => {
+                let dictionary = read_dictionary(dictionary)?;
+                let dictionary = dictionary.as_deref();
+                let a = future::block_on(output::read_artifact_with_dictionary(
+                    &artifact_a,
+                    dictionary,
+                ))?;
+                let b = future::block_on(output::read_artifact_with_dictionary(
+                    &artifact_b,
+                    dictionary,
+                ))?;
+                let diff = diff::diff(&a, &b, instructions);
+                print!("{}", diff.report);
+                if exit_code && diff.changed {
+                    std::process::exit(1);
+                }
+            }
+            Command::Clean {
+                cache,
+                artifacts,
+                ttl,
+                dry_run,
+            } => {
+                let cutoff = SystemTime::now() - ttl;
+                let mut cleaned = cache::Cleaned::default();
+
+                if cache {
+                    let target_dir = cache::spirv_builder_target_dir()
+                        .ok_or("Failed to locate the spirv-builder target directory")?;
+                    if target_dir.exists() {
+                        info!("Cleaning {target_dir:?}...");
+                        let inner = cache::remove_stale(&target_dir, cutoff, dry_run)?;
+                        cleaned.files += inner.files;
+                        cleaned.bytes += inner.bytes;
+                    }
+                }
+
+                for output_path in artifacts {
+                    let inner = future::block_on(cache::remove_stale_versions(
+                        &output_path,
+                        cutoff,
+                        dry_run,
+                    ))?;
+                    cleaned.files += inner.files;
+                    cleaned.bytes += inner.bytes;
+                }
+
+                info!(
+                    "{} {} file(s), {} bytes",
+                    if dry_run { "Would remove" } else { "Removed" },
+                    cleaned.files,
+                    cleaned.bytes
+                );
+            }
+            Command::Bundle {
+                output_path,
+                artifacts,
+                output_format,
+                collisions,
+                dictionary,
+            } => {
+                let dictionary = read_dictionary(dictionary)?;
+                let outputs =
+                    future::block_on(bundle::read_inputs(&artifacts, dictionary.as_deref()))?;
+                let bytes = output_format.serialize(&Bundle::new(&outputs, collisions)?);
+                future::block_on(output::write_atomic(&output_path, &bytes))?;
+                info!("Bundled {} artifact(s) into {output_path:?}", outputs.len());
+            }
+            Command::TrainDict {
+                output_path,
+                artifacts,
+                max_size,
+            } => {
+                let dictionary = compress::train(&artifacts, max_size)?;
+                future::block_on(output::write_atomic(&output_path, &dictionary))?;
+                info!(
+                    "Trained a {} byte dictionary with ID {} on {} artifact(s), written to {output_path:?}",
+                    dictionary.len(),
+                    compress::dictionary_id(&dictionary).unwrap_or_default(),
+                    artifacts.len()
+                );
+            }
+            Command::Matrix {
+                config,
+                jobs,
+                manifest,
+                run_manifest,
+            } => {
+                let run_manifest = run_manifest.map(RunManifest::new);
+                let entries =
+                    matrix::run(&config, jobs, run_manifest.as_ref(), &shutdown_on_signal())?;
+                let bytes = serde_json::to_vec_pretty(&entries)?;
+                future::block_on(output::write_atomic(&manifest, &bytes))?;
+
+                let failed = entries.iter().filter(|entry| entry.error.is_some()).count();
+                info!("Wrote manifest to {manifest:?}");
+                if failed > 0 {
+                    return Err(
+                        format!("{failed} of {} matrix job(s) failed", entries.len()).into(),
+                    );
+                }
+            }
+            Command::Fetch {
+                path_to_crate,
+                target,
+                locked,
+            } => {
+                cargo::fetch(&path_to_crate, &target, locked)?;
+                info!("Fetched dependencies of {path_to_crate:?}");
+            }
+            Command::Bench {
+                path_to_crate,
+                iterations,
+                clean,
+                criterion,
+                args,
+            } => {
+                let crate_name =
+                    ShaderBuilder::from_args(vec![path_to_crate.clone().into()])?.crate_name();
+                let args = std::iter::once(path_to_crate.into()).chain(args).collect();
+                let samples = bench::run(args, iterations, clean, &shutdown_on_signal())?;
+                print!("{}", samples.report());
+
+                if let Some(dir) = criterion {
+                    bench::write_criterion(&dir, &crate_name, &samples)?;
+                    info!("Wrote criterion results to {dir:?}");
+                }
+            }
+            Command::Test {
+                path_to_crate,
+                command,
+                host_crate,
+                args,
+            } => {
+                let runner = match (command, host_crate) {
+                    (Some(command), _) => host_test::Runner::Command(command),
+                    (None, Some(path)) => host_test::Runner::Crate(path),
+                    (None, None) => unreachable!("clap requires --command or --host-crate"),
+                };
+                let args = std::iter::once(path_to_crate.into()).chain(args).collect();
+                let builder = ShaderBuilder::from_args(args)?;
+                host_test::run(&builder, &runner, &shutdown_on_signal())?;
+            }
+            Command::Keygen { path } => {
+                let public_path = signing::generate(&path)?;
+                info!("Wrote secret key to {path:?} and public key to {public_path:?}");
+            }
+            Command::Verify {
+                artifact,
+                public_key,
+                dictionary,
+            } => {
+                let dictionary = read_dictionary(dictionary)?;
+                let key = signing::load_verifying_key(&public_key)?;
+                let bytes = std::fs::read(&artifact)?;
+                let signature_path = signing::signature_path(&artifact);
+                let signature = std::fs::read(&signature_path)
+                    .map_err(|e| format!("Failed to read {signature_path:?}: {e}"))?;
+                signing::verify(&key, &bytes, &signature)
+                    .map_err(|e| format!("{artifact:?} failed verification: {e}"))?;
+                future::block_on(output::read_artifact_with_dictionary(
+                    &artifact,
+                    dictionary.as_deref(),
+                ))?;
+                info!("{artifact:?} is signed by {public_key:?} and intact");
+            }
+            Command::Info {
+                artifact,
+                show_config,
+                dictionary,
+            } => {
+                let dictionary = read_dictionary(dictionary)?;
+                let dictionary = dictionary.as_deref();
+                let output =
+                    future::block_on(output::read_artifact_with_dictionary(&artifact, dictionary))?;
+
+                let mut entry_points = console::Table::new("Entry points", vec!["name"]);
+                for name in &output.entry_points {
+                    entry_points.row(vec![name.clone()]);
+                }
+                entry_points.print();
+
+                let mut modules = console::Table::new("Modules", vec!["name", "size"]);
+                for (name, module) in reflect::named_modules(&output.modules, "<module>") {
+                    modules.row(vec![
+                        name,
+                        format!("{:.1} KiB", module.len() as f64 / 1024.0),
+                    ]);
+                }
+                modules.print();
+
+                if show_config {
+                    match future::block_on(output::read_config(&artifact, dictionary))? {
+                        Some(config) => print!("\n{config}"),
+                        None => warn!("{artifact:?} doesn't record the options it was built with"),
+                    }
+                }
+            }
+            Command::Reflect {
+                artifact,
+                dictionary,
+            } => {
+                let dictionary = read_dictionary(dictionary)?;
+                let reflection =
+                    future::block_on(reflection::reflect(&artifact, dictionary.as_deref()))?;
+                println!("{}", serde_json::to_string_pretty(&reflection)?);
+            }
+            Command::Extract {
+                artifact,
+                entry_point,
+                output_path,
+                dictionary,
+            } => {
+                let dictionary = read_dictionary(dictionary)?;
+                let module = future::block_on(extract::extract(
+                    &artifact,
+                    &entry_point,
+                    dictionary.as_deref(),
+                ))?;
+                future::block_on(output::write_atomic(&output_path, &module))?;
+                info!(
+                    "Wrote {entry_point} ({:.1} KiB) to {output_path:?}",
+                    module.len() as f64 / 1024.0
+                );
+            }
+            Command::Doctor {
+                path_to_crate,
+                output_path,
+            } => {
+                let checks = doctor::run(path_to_crate.as_deref(), output_path.as_deref());
+                for check in &checks {
+                    println!("{check}");
+                }
+                let failed = checks
+                    .iter()
+                    .filter(|check| check.status == doctor::Status::Fail)
+                    .count();
+                if failed > 0 {
+                    return Err(format!("{failed} check(s) failed").into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The `--dictionary` of a subcommand reading artifacts, if given.
+fn read_dictionary(path: Option<PathBuf>) -> Result<Option<Vec<u8>>, String> {
+    path.as_deref().map(compress::read_dictionary).transpose()
+}
+
+/// Shader build options.
+#[derive(Debug, Clone, Args)]
+pub struct ShaderBuilder {
     /// Shader crate to compile.
+    ///
+    /// With --crate-git or --crate-tar, relative to the root of the fetched sources,
+    /// e.g. `.` for a crate at the root.
+    /// With --package, the root of the workspace to find the package in instead.
+    // clap leaves the group of an optionally flattened struct empty when it has
+    // flattened fields of its own, so `Cli::builder` is only `Some` through this
+    #[arg(group = "ShaderBuilder")]
     path_to_crate: PathBuf,
+    /// Clone the shader crate's sources from a git repository, given as `<url>#<rev>`,
+    /// into a temporary directory before building.
+    ///
+    /// The revision may be a commit, tag or branch, and defaults to the default branch.
+    #[arg(long, value_parser = GitSource::from_str, conflicts_with_all = ["crate_tar", "watch_paths"])]
+    crate_git: Option<GitSource>,
+    /// Unpack the shader crate's sources from a tarball into a temporary directory before building.
+    ///
+    /// An archive holding a single top-level directory is treated as rooted inside it.
+    #[arg(long, conflicts_with = "watch_paths")]
+    crate_tar: Option<PathBuf>,
+    /// Build the workspace package with this name, taking the shader crate's path as the
+    /// workspace root, so scripts keep working when crates move around inside it.
+    #[arg(short, long)]
+    package: Option<String>,
     /// If set, combined SPIR-V and entrypoint metadata will be written to this file on succesful compile.
+    ///
+    /// May contain `{crate}`, `{target}`, `{profile}` and `{hash}` placeholders,
+    /// which are substituted with the shader crate name, compile target, cargo profile
+    /// and artifact content hash respectively.
+    #[arg(value_parser = ShaderBuilder::output_path)]
     output_path: Option<PathBuf>,
+    /// Fail if the output path's directory doesn't exist, instead of creating it.
+    ///
+    /// Either way, the directory is checked to be writable before building.
+    #[arg(long, requires = "output_path", default_value = "false")]
+    no_create_dirs: bool,
     /// The format to write output in.
     #[arg(long, default_value = "Messagepack")]
     output_format: OutputFormat,
+    /// Compress the output, which consumers then have to decompress before deserializing it.
+    #[arg(long, requires = "output_path")]
+    compress: Option<Compression>,
+    /// zstd dictionary written by `train-dict`, to compress with.
+    #[arg(long, requires = "compress")]
+    compress_dictionary: Option<PathBuf>,
+    /// Write each successful build to a hash-suffixed sibling of the output path,
+    /// and atomically re-point the output path at the newest one.
+    ///
+    /// Previous builds are kept on disk, so a hot-reload client can roll back to them.
+    #[arg(long, default_value = "false")]
+    versioned_output: bool,
+    /// Number of versioned builds to keep on disk, oldest first to be removed.
+    #[arg(
+        long,
+        requires = "versioned_output",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    keep_artifacts: Option<usize>,
+    /// Also copy the raw `.spv` modules into this directory after every successful build,
+    /// instead of only leaving them in spirv-builder's target directory.
+    ///
+    /// Modules are named `<crate>.spv`, or `<entry-point>.spv` with --multimodule.
+    #[arg(long)]
+    spv_dir: Option<PathBuf>,
+    /// Also write other kinds of output next to the output path, each with its own extension.
+    ///
+    /// A comma-separated list, which can be given more than once:
+    /// `json` and `msgpack` are the artifact in that format, `spv`, `spvasm` and `wgsl`
+    /// one file per module (`<output>.<entry-point>.<ext>` with --multimodule),
+    /// and `rust-bindings` an `.rs` file of constants naming entry points and descriptor bindings.
+    ///
+    /// `naga-ir` is naga's parsed IR of each module as RON (`.naga.ron`), for analysis tools.
+    ///
+    /// `pack` is a compact indexed archive of the raw modules, meant to be memory-mapped
+    /// at runtime instead of deserialized.
+    ///
+    /// `patch` is a binary diff from the output being replaced to the new one,
+    /// for hot-reload clients that are slow to transfer whole artifacts to.
+    #[arg(long, requires = "output_path", value_delimiter = ',', value_parser = Emit::from_str)]
+    emit: Vec<Emit>,
+    /// Pipe the artifact through an external command as an extra output, given as
+    /// `<extension>=<command>`, e.g. `ktx=./tools/pack-ktx`.
+    ///
+    /// The command runs through the shell with the artifact as JSON on stdin, and
+    /// `RUST_GPU_BUILDER_CRATE` and `RUST_GPU_BUILDER_OUTPUT` set. What it prints to stdout
+    /// is written to `<output>.<extension>`, and the build fails if it exits unsuccessfully.
+    /// Can be specified multiple times, plugins run in the order given.
+    #[arg(long, requires = "output_path", value_parser = Plugin::from_str)]
+    emit_plugin: Vec<Plugin>,
+    /// Build for a particular kind of consumer.
+    ///
+    /// `webgpu` targets SPIR-V that wgpu can ingest, rejects capabilities WGSL can't express,
+    /// and fails the build unless naga can translate every module to WGSL within WebGPU's
+    /// default limits. The translations are recorded in an extra `wgsl` field of the output.
+    #[arg(long, conflicts_with = "target", value_parser = Preset::from_str)]
+    preset: Option<Preset>,
+    /// After every successful build, create each compute pipeline on every Vulkan device present,
+    /// and write the resulting pipeline cache next to the output path as
+    /// `<output>.<vendor-id>-<device-id>.vkpipelinecache`.
+    #[arg(long, requires = "output_path", default_value = "false")]
+    prime_pipeline_cache: bool,
     /// rust-gpu compile target.
+    ///
+    /// OpenCL targets (`spirv-unknown-opencl*`) also enable the `Kernel` capability,
+    /// and record the signature of each compute entry point in an extra `kernels` field of the output.
     #[arg(short, long, default_value = "spirv-unknown-vulkan1.2")]
     target: String,
     /// Treat warnings as errors during compilation.
     #[arg(long, default_value = "false")]
     deny_warnings: bool,
     /// Compile shaders in release mode.
+    ///
+    /// Either profile can be tuned further with --profile-opt-level, --debug-assertions and --lto.
     #[arg(long, default_value = "false")]
     release: bool,
     /// Enables the provided SPIR-V capability.
     #[arg(long, value_parser=Self::spirv_capability)]
     capability: Vec<Capability>,
+    /// Don't enable the capabilities the target allows by default, such as `Int8` on Vulkan 1.2,
+    /// only those given with --capability.
+    #[arg(long, default_value = "false")]
+    no_default_capabilities: bool,
     /// Compile one .spv file per entry point.
     #[arg(long, default_value = "false")]
     multimodule: bool,
+    /// Store byte-identical modules of a multimodule build once, with a `module_aliases` map
+    /// from each left-out entry point to the one whose module it shares.
+    ///
+    /// The modules are written under `deduplicated_modules` rather than `modules`, so readers
+    /// that don't know about aliases fail to load the artifact instead of missing entry points.
+    #[arg(long, requires = "multimodule", default_value = "false")]
+    dedup_modules: bool,
     /// Set the level of metadata included in the SPIR-V binary.
     #[arg(long, value_parser=Self::spirv_metadata, default_value = "none")]
     spirv_metadata: SpirvMetadata,
+    /// Move names, embedded sources and line information out of the output's modules
+    /// and into a `<output>.symbols` sidecar in the same format, like split debuginfo.
+    ///
+    /// Use with `--spirv-metadata full`. The sidecar also keeps each unstripped module
+    /// for debuggers such as RenderDoc.
+    #[arg(long, requires = "output_path", default_value = "false")]
+    split_debug_info: bool,
+    /// Print `cargo:` build script directives for the shader crate: `none`, `dependency-only`
+    /// for `rerun-if-changed` lines, or `full` to also export each module path as an env var.
+    ///
+    /// `full` is not supported with --multimodule.
+    #[arg(long, value_parser=Self::metadata_printout, default_value = "none")]
+    print_metadata: MetadataPrintout,
     /// Allow store from one struct type to a different type with compatible layout and members.
     #[arg(long, default_value = "false")]
     relax_struct_store: bool,
@@ -102,9 +850,239 @@ struct ShaderBuilder {
     /// Can be specified multiple times to watch more than one directory.
     #[arg(short, long)]
     watch_paths: Option<Vec<String>>,
+    /// Also watch the targets of symlinks inside watched directories, and notice when
+    /// a watch path that is itself a symlink is pointed somewhere else.
+    #[arg(long, default_value = "false")]
+    follow_symlinks: bool,
+    /// How to watch watch paths that are directories: `recursive` watches every directory below them,
+    /// `coarse` only the top two levels and deeper directories holding Rust sources or Cargo manifests.
+    ///
+    /// Each watched directory takes one of Linux's limited inotify watches, so `coarse` fits huge
+    /// trees within tight limits. It skips `target` and hidden directories, and new directories
+    /// deeper down aren't watched until the watch path is watched again.
+    #[arg(long, default_value = "recursive", value_parser = WatchStrategy::from_str)]
+    watch_strategy: WatchStrategy,
+    /// Every this many seconds, check each watcher still delivers events by touching a
+    /// `.rust-gpu-builder-heartbeat` file in its directory, and recreate any that stalled.
+    ///
+    /// Stalls and recoveries are posted to webhooks as `watch_stalled` and `watch_recovered` events.
+    #[arg(long, requires = "watch_paths")]
+    heartbeat: Option<u64>,
+    /// In watch mode, run the startup build in the background once the watchers are running,
+    /// instead of blocking before they start.
+    ///
+    /// This is the crate's normal build, output and all, so it compiles the dependency graph
+    /// along with the crate and the first rebuild after a change only recompiles the crate.
+    #[arg(long, alias = "prebuild-deps", default_value = "false")]
+    background_startup_build: bool,
+    /// Minimum time in milliseconds between the start of consecutive rebuilds in watch mode.
+    ///
+    /// Changes arriving sooner are coalesced into a single queued rebuild.
+    #[arg(long, default_value = "0")]
+    min_rebuild_interval: u64,
+    /// Number of builds to remember in watch mode, for `GET /history` and the table printed on exit.
+    #[arg(long, default_value = "50")]
+    history: usize,
+    /// In watch mode, listen on this port or address for HTTP build requests, e.g. `8450`.
+    ///
+    /// A bare port listens on loopback only, as the API has no authentication: give a full
+    /// address like `0.0.0.0:8450` to accept requests from other machines.
+    ///
+    /// `POST /build` queues a build and returns its job ID, optionally with a JSON body like
+    /// `{"crate": "my-shader", "options": {"release": true}}` to override build options.
+    /// `GET /jobs/<id>` reports the job's status, and `POST /projects` registers more crates to build.
+    /// Options that run commands, fetch sources, read files or post anywhere can't be set over HTTP.
+    #[arg(long, requires = "watch_paths", value_parser = Self::serve_addr)]
+    serve: Option<SocketAddr>,
+    /// Remember artifact hashes, build stats and whether the crate has changed since its last
+    /// build in this JSON file, and skip the startup build if nothing has changed.
+    ///
+    /// Sources are compared by the size and modification time of the files under the crate
+    /// and the watch paths, along with the build arguments.
+    #[arg(long)]
+    state_file: Option<PathBuf>,
+    /// Write a JSON manifest of every artifact the run writes to this file, with its crate,
+    /// target, profile, path, hash, size and entry points.
+    ///
+    /// In watch mode it's rewritten after every build, listing the latest artifact at each path.
+    #[arg(long, requires = "output_path")]
+    run_manifest: Option<PathBuf>,
+    /// In watch mode, restart with the same arguments when the builder's own executable is
+    /// replaced, e.g. by `cargo install`. An in-flight build is cancelled and runs again after.
+    ///
+    /// Pair with `--state-file` so the restart skips crates that haven't changed. Projects
+    /// registered over HTTP aren't kept.
+    #[arg(long, default_value = "false", requires = "watch_paths")]
+    auto_restart: bool,
+    /// POST a JSON build event to this URL after every build.
+    ///
+    /// Can be specified multiple times to notify more than one endpoint.
+    #[arg(long)]
+    webhook: Vec<String>,
+    /// Rename entry points with a `<regex>=<replacement>` rule before writing output,
+    /// e.g. `^.*::=` to strip module path prefixes.
+    ///
+    /// Applies to the recorded entry point names and per-entry module names.
+    /// Can be specified multiple times, rules apply in the order given.
+    #[arg(long, value_parser = EntryPointRename::from_str)]
+    entry_point_rename: Vec<EntryPointRename>,
+    /// Also record each entry point under its stage (`vertex`, `fragment`, `compute`, ...)
+    /// in an extra `stages` field of the output, so consumers don't need to parse SPIR-V.
+    #[arg(long, default_value = "false")]
+    group_entry_points: bool,
+    /// Declare a render pipeline as `<name>:<stage>=<entry point>,...`,
+    /// e.g. `pbr:vertex=pbr_vs,fragment=pbr_fs`, to be checked and recorded in the output.
+    ///
+    /// The build fails if a stage's entry point is missing or of another stage, or reads a
+    /// location the stage before it doesn't write, or as another type or rate, or reads integers
+    /// into a fragment shader without `Flat`. Stages are `task`, `mesh`, `vertex`,
+    /// `tessellation_control`, `tessellation_evaluation`, `geometry` and `fragment`.
+    /// Can be specified multiple times.
+    #[arg(long, value_parser = Pipeline::from_str)]
+    pipeline: Vec<Pipeline>,
+    /// Don't record the shader crate's git commit, branch and dirty state in the output.
+    #[arg(long, default_value = "false")]
+    no_provenance: bool,
+    /// Sign every artifact with the ed25519 key in this file, writing the signature
+    /// to a `<output>.sig` sidecar.
+    ///
+    /// Generate a key pair with the `keygen` subcommand.
+    #[arg(long, requires = "output_path")]
+    signing_key: Option<PathBuf>,
+    /// Copy files matching this glob into the output directory after every build,
+    /// and whenever they change in watch mode.
+    ///
+    /// Paths are preserved relative to the glob's leading non-wildcard directories.
+    /// Can be specified multiple times.
+    #[arg(long, requires = "output_path", value_parser = AssetGlob::from_str)]
+    asset: Vec<AssetGlob>,
+    /// Shell command to run before each compile, e.g. to regenerate code.
+    ///
+    /// If it fails, the build is aborted.
+    /// Can be specified multiple times, commands run in the order given.
+    ///
+    /// In watch mode, generated files inside a watched path should only be rewritten
+    /// when their contents change, otherwise every build will queue another.
+    #[arg(long)]
+    pre_build: Vec<String>,
+    /// Log which changed path, watch path and crate caused each rebuild in watch mode,
+    /// and why changes were ignored.
+    #[arg(long, default_value = "false")]
+    explain: bool,
+    /// Print only the first this many diagnostics with each error code per build, then
+    /// how many more were left out, so a change breaking hundreds of lines the same way
+    /// doesn't bury the rest.
+    ///
+    /// Diagnostics without a code are told apart by their message.
+    #[arg(long)]
+    max_diagnostics: Option<usize>,
+    #[command(flatten)]
+    budgets: Budgets,
+    #[command(flatten)]
+    lints: Lints,
+    #[command(flatten)]
+    deny_list: DenyList,
+    #[command(flatten)]
+    validation: Validation,
+    #[command(flatten)]
+    cargo: CargoOptions,
+    /// Arguments to re-run this binary with to compile in a build worker.
+    #[arg(skip)]
+    worker_args: Vec<OsString>,
+    /// spirv-builder target directory to compile into instead of the shared one,
+    /// so concurrent matrix jobs don't wait on each other's cargo lock.
+    #[arg(skip)]
+    target_dir: Option<PathBuf>,
 }
 
 impl ShaderBuilder {
+    /// Parse build arguments as they would be given on the command line.
+    fn from_args(args: Vec<OsString>) -> Result<Self, Box<dyn Error>> {
+        let cli = Cli::try_parse_from(std::iter::once(OsString::from("")).chain(args.clone()))?;
+        let mut builder = cli.builder.ok_or("Missing shader build arguments")?;
+        builder.worker_args = args;
+        builder.apply_preset()?;
+        target::check_capabilities(&builder.target, &builder.capability)?;
+        builder.resolve_source()?;
+        Ok(builder)
+    }
+
+    /// Fetch the sources given by --crate-git or --crate-tar, if any,
+    /// and point `path_to_crate` into them.
+    ///
+    /// Then, with --package, point it at that package of the workspace it names.
+    fn resolve_source(&mut self) -> Result<(), String> {
+        let root = match (&self.crate_git, &self.crate_tar) {
+            (Some(git), _) => Some(source::checkout_git(git)?),
+            (None, Some(tar)) => Some(source::unpack_tar(tar)?),
+            (None, None) => None,
+        };
+        if let Some(root) = root {
+            self.path_to_crate = root.join(&self.path_to_crate);
+            if !self.path_to_crate.join("Cargo.toml").is_file() {
+                return Err(format!(
+                    "No shader crate at {:?} in the fetched sources",
+                    self.path_to_crate
+                        .strip_prefix(&root)
+                        .unwrap_or(&self.path_to_crate)
+                ));
+            }
+        }
+
+        if let Some(package) = &self.package {
+            let metadata = metadata::metadata(&self.path_to_crate).map_err(|e| e.to_string())?;
+            self.path_to_crate = metadata
+                .local_package(package)
+                .ok_or_else(|| {
+                    let names = metadata
+                        .local_packages()
+                        .map(|package| package.name.as_str())
+                        .collect::<Vec<_>>();
+                    format!(
+                        "No package named {package:?} in the workspace at {:?}, it has {}",
+                        self.path_to_crate,
+                        names.join(", ")
+                    )
+                })?
+                .dir()
+                .to_owned();
+        }
+        Ok(())
+    }
+
+    /// Adjust settings implied by --preset.
+    fn apply_preset(&mut self) -> Result<(), String> {
+        match self.preset {
+            Some(Preset::WebGpu) => {
+                self.target = webgpu::TARGET.to_string();
+                // WGSL has no 64-bit or 8-bit types to enable
+                self.no_default_capabilities = true;
+                webgpu::check_capabilities(&self.capability)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Clap value parser for the output path, rejecting the `true` or `false` that
+    /// `--debug-assertions false` leaves behind instead of writing to a file named after it.
+    fn output_path(s: &str) -> Result<PathBuf, String> {
+        match s {
+            "true" | "false" => Err(format!(
+                "{s:?} isn't an output path, pass flag values as e.g. `--debug-assertions={s}`"
+            )),
+            _ => Ok(PathBuf::from(s)),
+        }
+    }
+
+    /// Clap value parser for --serve, listening on loopback when only given a port.
+    fn serve_addr(s: &str) -> Result<SocketAddr, String> {
+        if let Ok(port) = s.parse::<u16>() {
+            return Ok(SocketAddr::from(([127, 0, 0, 1], port)));
+        }
+        s.parse()
+            .map_err(|_| format!("Expected a port or an address like 127.0.0.1:8450, got {s:?}"))
+    }
+
     /// Clap value parser for `SpirvMetadata`.
     fn spirv_metadata(s: &str) -> Result<SpirvMetadata, clap::Error> {
         match s {
@@ -115,6 +1093,16 @@ impl ShaderBuilder {
         }
     }
 
+    /// Clap value parser for `MetadataPrintout`.
+    fn metadata_printout(s: &str) -> Result<MetadataPrintout, clap::Error> {
+        match s {
+            "none" => Ok(MetadataPrintout::None),
+            "dependency-only" => Ok(MetadataPrintout::DependencyOnly),
+            "full" => Ok(MetadataPrintout::Full),
+            _ => Err(clap::Error::new(ErrorKind::InvalidValue)),
+        }
+    }
+
     /// Clap value parser for `Capability`.
     fn spirv_capability(s: &str) -> Result<Capability, clap::Error> {
         match Capability::from_str(s) {
@@ -123,12 +1111,145 @@ impl ShaderBuilder {
         }
     }
 
-    /// Builds a shader with the provided set of options.
-    pub fn build_shader(&self) -> Result<CompileResult, SpirvBuilderError> {
+    /// Name of the shader crate, as reported in build events.
+    fn crate_name(&self) -> String {
+        let path =
+            paths::canonicalize(&self.path_to_crate).unwrap_or_else(|_| self.path_to_crate.clone());
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string())
+    }
+
+    /// Name of the cargo profile the shader crate is compiled with.
+    fn profile(&self) -> &'static str {
+        if self.release {
+            "release"
+        } else {
+            "dev"
+        }
+    }
+
+    /// Path an artifact with the given hash is written to.
+    fn artifact_path(&self, hash: &str) -> Option<PathBuf> {
+        Some(output::expand_template(
+            self.output_path.as_ref()?,
+            &TemplateVars {
+                crate_name: &self.crate_name(),
+                target: &self.target,
+                profile: self.profile(),
+                hash,
+            },
+        ))
+    }
+
+    /// Print the banner introducing a build, and the job that requested it if any.
+    fn print_banner(&self, job: Option<JobId>) {
+        let mut details = vec![self.target.clone(), self.profile().to_string()];
+        if let Some(jobs) = self.cargo.cargo_jobs {
+            details.push(format!("{jobs} cargo job(s)"));
+        }
+        if let Some(job) = job {
+            details.push(format!("job {job}"));
+        }
+        console::banner(&self.crate_name(), &details.join(" · "));
+    }
+
+    /// Paths whose contents a build depends on, as far as `--state-file` is concerned.
+    fn sources(&self) -> Vec<PathBuf> {
+        let mut sources = vec![self.path_to_crate.clone()];
+        for path in self.watch_paths.iter().flatten() {
+            sources.push(PathBuf::from(path));
+        }
+        sources
+    }
+
+    /// Directory that companion assets are copied into when they change,
+    /// if it doesn't depend on the artifact hash.
+    fn asset_dir(&self) -> Option<PathBuf> {
+        let output_dir = self.output_path.as_ref()?.parent()?;
+        let output_dir = output::expand_template(
+            output_dir,
+            &TemplateVars {
+                crate_name: &self.crate_name(),
+                target: &self.target,
+                profile: self.profile(),
+                hash: "{hash}",
+            },
+        );
+
+        (!output_dir.to_string_lossy().contains("{hash}")).then_some(output_dir)
+    }
+
+    /// Create the output directory unless --no-create-dirs, and check it's writable,
+    /// so a mistyped output path fails before a long compile rather than after.
+    ///
+    /// Directories depending on the artifact hash are created once it's known,
+    /// so only the closest existing ancestor is checked.
+    fn prepare_output_dir(&self) -> Result<(), String> {
+        let Some(output_path) = &self.output_path else {
+            return Ok(())
+        };
+
+        let dir = match self.asset_dir() {
+            Some(dir) if dir.as_os_str().is_empty() => PathBuf::from("."),
+            Some(dir) => {
+                if !dir.is_dir() {
+                    if self.no_create_dirs {
+                        return Err(format!(
+                            "Output directory {dir:?} doesn't exist. Create it, or drop --no-create-dirs to have it created"
+                        ));
+                    }
+                    std::fs::create_dir_all(&dir)
+                        .map_err(|e| format!("Failed to create output directory {dir:?}: {e}"))?;
+                }
+                dir
+            }
+            None if self.no_create_dirs => {
+                return Err(format!(
+                    "--no-create-dirs can't be used with {output_path:?}, whose directory depends on the artifact {{hash}}"
+                ))
+            }
+            None => paths::nearest_existing(paths::parent_dir(output_path))
+                .unwrap_or(Path::new("."))
+                .to_path_buf(),
+        };
+
+        paths::probe_writable(&dir)
+            .map_err(|e| format!("Output directory {dir:?} isn't writable: {e}"))
+    }
+
+    /// Builds a shader with the provided set of options, until `cancel` is cancelled.
+    pub fn build_shader(&self, cancel: &CancelToken) -> Result<Compiled, BuildError> {
+        self.prepare_output_dir().map_err(BuildError::Output)?;
+
+        for command in &self.pre_build {
+            hooks::run(command).map_err(BuildError::PreBuild)?;
+        }
+
+        self.cargo
+            .check_lockfile(&self.path_to_crate)
+            .map_err(BuildError::Lockfile)?;
+
+        worker::build(
+            self.worker_args.clone(),
+            &self.path_to_crate,
+            self.target_dir.as_deref(),
+            self.cargo.low_priority,
+            self.max_diagnostics,
+            cancel,
+        )
+    }
+
+    /// Compiles the shader crate in this process, as a build worker.
+    fn compile(&self) -> Result<CompileResult, SpirvBuilderError> {
         // As per `spirv-builder`, apply env vars set in build.rs
         // to work around potentially suboptimal cargo behaviour
-        std::env::set_var("OUT_DIR", env!("OUT_DIR"));
+        match worker::target_dir() {
+            Some(target_dir) => std::env::set_var("OUT_DIR", cache::out_dir_for(&target_dir)),
+            None => std::env::set_var("OUT_DIR", env!("OUT_DIR")),
+        }
         std::env::set_var("PROFILE", env!("PROFILE"));
+        self.cargo.apply_env(self.profile());
 
         let mut builder = SpirvBuilder::new(&self.path_to_crate, &self.target)
             .deny_warnings(self.deny_warnings)
@@ -142,19 +1263,200 @@ impl ShaderBuilder {
             .scalar_block_layout(self.scalar_block_layout)
             .skip_block_layout(self.skip_block_layout)
             .preserve_bindings(self.preserve_bindings)
-            .print_metadata(MetadataPrintout::None);
+            .print_metadata(self.print_metadata);
 
-        for capability in &self.capability {
-            builder = builder.capability(*capability);
+        for capability in self.capabilities() {
+            builder = builder.capability(capability);
         }
 
         builder.build()
     }
+
+    /// Capabilities given with --capability, followed by any others the target implies.
+    fn capabilities(&self) -> Vec<Capability> {
+        let mut capabilities = self.capability.clone();
+        capabilities.extend(self.default_capabilities());
+        capabilities
+    }
+
+    /// Capabilities the target implies that weren't given with --capability.
+    fn default_capabilities(&self) -> Vec<Capability> {
+        if self.no_default_capabilities {
+            return vec![];
+        }
+        target::default_capabilities(&self.target)
+            .iter()
+            .filter(|capability| !self.capability.contains(capability))
+            .copied()
+            .collect()
+    }
+
+    /// The options this build resolves to, as recorded in its artifact.
+    fn build_config(&self) -> BuildConfig {
+        let passes = [
+            ("relax-struct-store", self.relax_struct_store),
+            ("relax-logical-pointer", self.relax_logical_pointer),
+            ("relax-block-layout", self.relax_block_layout),
+            (
+                "uniform-buffer-standard-layout",
+                self.uniform_buffer_standard_layout,
+            ),
+            ("scalar-block-layout", self.scalar_block_layout),
+            ("skip-block-layout", self.skip_block_layout),
+            ("preserve-bindings", self.preserve_bindings),
+        ];
+
+        BuildConfig {
+            builder_version: env!("CARGO_PKG_VERSION").to_string(),
+            target: self.target.clone(),
+            profile: self.profile().to_string(),
+            capabilities: self
+                .capabilities()
+                .iter()
+                .map(|capability| format!("{capability:?}"))
+                .collect(),
+            multimodule: self.multimodule,
+            spirv_metadata: match self.spirv_metadata {
+                SpirvMetadata::None => "none",
+                SpirvMetadata::NameVariables => "name-variables",
+                SpirvMetadata::Full => "full",
+            }
+            .to_string(),
+            deny_warnings: self.deny_warnings,
+            passes: passes
+                .into_iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            opt_level: self.cargo.profile_opt_level.clone(),
+            debug_assertions: self.cargo.debug_assertions,
+            lto: self.cargo.lto.clone(),
+            env: self.cargo.env.iter().map(|(key, _)| key.clone()).collect(),
+            preset: self.preset.map(|preset| match preset {
+                Preset::WebGpu => "webgpu".to_string(),
+            }),
+            entry_point_renames: self
+                .entry_point_rename
+                .iter()
+                .map(|rule| rule.to_string())
+                .collect(),
+            split_debug_info: self.split_debug_info,
+            dedup_modules: self.dedup_modules,
+            compression: self.compress.map(|_| "zstd".to_string()),
+            compression_dictionary: None,
+        }
+    }
+
+    /// spirv-val options matching the layout rules the crate was compiled with.
+    fn validator_options(&self) -> ValidatorOptions {
+        ValidatorOptions {
+            relax_struct_store: self.relax_struct_store,
+            relax_logical_pointer: self.relax_logical_pointer,
+            relax_block_layout: self.relax_block_layout.then_some(true),
+            uniform_buffer_standard_layout: self.uniform_buffer_standard_layout,
+            scalar_block_layout: self.scalar_block_layout,
+            skip_block_layout: self.skip_block_layout,
+            ..Default::default()
+        }
+    }
+}
+
+/// Reasons a shader build can fail.
+#[derive(Debug)]
+pub enum BuildError {
+    /// A pre-build command failed, so compilation was skipped.
+    PreBuild(HookError),
+    /// `spirv-builder` failed to compile the shader crate.
+    Spirv(String),
+    /// The compiler itself crashed while compiling the shader crate.
+    Ice(IceSummary),
+    /// The build worker process could not be run, or crashed.
+    Worker(String),
+    /// The build was cancelled before it finished.
+    Cancelled,
+    /// Renaming entry points made their names ambiguous.
+    Rename(String),
+    /// The compiled modules exceeded a configured budget.
+    Budget(BudgetReport),
+    /// The compiled modules had lint findings under --deny-lints.
+    Lint(LintReport),
+    /// The compiled modules used an instruction or feature given to --deny-op or --deny-feature.
+    Denied(DenyReport),
+    /// The compiled modules don't fit a --pipeline.
+    Pipeline(PipelineReport),
+    /// The compiled modules failed --validate.
+    Validation(ValidationReport),
+    /// The shader crate's Cargo.lock can't be used as is.
+    Lockfile(String),
+    /// The --signing-key couldn't be loaded.
+    Signing(String),
+    /// The output directory is missing or not writable, or writing to it failed.
+    Output(String),
+    /// An --emit output couldn't be produced.
+    Emit(String),
+    /// The output couldn't be compressed.
+    Compression(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::PreBuild(e) => write!(f, "Pre-build command failed: {e}"),
+            BuildError::Spirv(e) => write!(f, "{e}"),
+            BuildError::Ice(summary) => write!(f, "{summary}"),
+            BuildError::Worker(e) => write!(f, "{e}"),
+            BuildError::Cancelled => write!(f, "Build cancelled"),
+            BuildError::Rename(e) => write!(f, "{e}"),
+            BuildError::Budget(report) => write!(f, "{report}"),
+            BuildError::Lint(report) => write!(f, "{report}"),
+            BuildError::Denied(report) => write!(f, "{report}"),
+            BuildError::Pipeline(report) => write!(f, "{report}"),
+            BuildError::Validation(report) => write!(f, "{report}"),
+            BuildError::Lockfile(e) => write!(f, "{e}"),
+            BuildError::Signing(e) => write!(f, "Failed to sign the output: {e}"),
+            BuildError::Output(e) => write!(f, "{e}"),
+            BuildError::Emit(e) => write!(f, "{e}"),
+            BuildError::Compression(e) => write!(f, "Failed to compress the output: {e}"),
+        }
+    }
 }
 
+impl Error for BuildError {}
+
+/// The filesystem change that caused a rebuild.
+#[derive(Debug, Clone)]
+struct Trigger {
+    /// Path reported by the watcher.
+    changed: PathBuf,
+    /// Watch path it fell under.
+    watch_path: PathBuf,
+}
+
+/// How soon to first check whether a removed watch path has reappeared.
+const REWATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Longest wait between checks for a removed watch path, which the interval backs off to.
+const REWATCH_MAX_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often to check that watch paths still exist, since unmounting or removing a parent directory
+/// can leave a watch dead without delivering any event.
+const LIVENESS_INTERVAL: Duration = Duration::from_secs(2);
+
 enum Msg {
-    Change,
-    Build(Result<CompileResult, SpirvBuilderError>),
+    /// A path a project watches changed, or `None` for its startup build.
+    Change(String, Option<Trigger>),
+    Asset(String, AssetGlob, PathBuf),
+    /// A project's watcher stalled or recovered.
+    Health(String, HealthEvent),
+    /// A build requested over HTTP, with its option overrides applied.
+    Request(JobId, Box<ShaderBuilder>),
+    /// A project was registered over HTTP, so should be watched and built.
+    Register(String),
+    /// A project was unregistered over HTTP, so should no longer be watched.
+    Unregister(String),
+    Ready,
+    /// A build finished, with the fingerprint of the sources it started from if state is kept.
+    Build(Result<Compiled, BuildError>, Duration, Option<String>),
 }
 
 /// Instantiate an async watcher and return it alongside a channel to receive events on.
@@ -166,7 +1468,8 @@ fn async_watcher() -> notify::Result<(RecommendedWatcher, Receiver<notify::Resul
     let watcher = RecommendedWatcher::new(
         move |res| {
             future::block_on(async {
-                tx.send(res).await.unwrap();
+                // The receiver is gone once a stalled watcher has been replaced
+                tx.send(res).await.ok();
             })
         },
         Default::default(),
@@ -175,37 +1478,367 @@ fn async_watcher() -> notify::Result<(RecommendedWatcher, Receiver<notify::Resul
     Ok((watcher, rx))
 }
 
+/// How watch paths are watched.
+#[derive(Debug, Copy, Clone)]
+struct WatchOptions {
+    follow_symlinks: bool,
+    strategy: WatchStrategy,
+}
+
+/// What a watch path currently resolves to, and the watches registered for it.
+struct Watched {
+    /// The canonical file or directory.
+    path: PathBuf,
+    is_dir: bool,
+    /// Directories covered on its behalf, watched recursively with the recursive strategy.
+    roots: Vec<PathBuf>,
+    /// With --follow-symlinks, files outside the watch path linked from inside it.
+    files: Vec<PathBuf>,
+    /// Directories watched non-recursively on its behalf, with the coarse strategy.
+    dirs: Vec<PathBuf>,
+    /// With --follow-symlinks, the watch path itself if it is a symlink.
+    /// Its parent directory is watched so retargeting it can be noticed.
+    link: Option<PathBuf>,
+}
+
+impl Watched {
+    /// Resolve `requested` and register watches for it.
+    fn watch(
+        watcher: &mut RecommendedWatcher,
+        requested: &Path,
+        options: WatchOptions,
+    ) -> Result<Watched, Box<dyn Error>> {
+        let path = paths::canonicalize(requested)?;
+        let is_dir = path.is_dir();
+
+        // Watching a single file is done through its directory, since editors
+        // often save by replacing the file, which would drop a watch on it
+        let mut roots = vec![if is_dir {
+            path.clone()
+        } else {
+            path.parent().unwrap().to_owned()
+        }];
+        let mut files = vec![];
+        if options.follow_symlinks && is_dir {
+            let (dirs, linked): (Vec<_>, Vec<_>) = paths::symlink_targets(&path)
+                .into_iter()
+                .partition(|target| target.is_dir());
+            roots.extend(dirs);
+            files = linked;
+        }
+
+        let mut dirs = vec![];
+        for root in &roots {
+            match options.strategy {
+                WatchStrategy::Recursive => watcher.watch(root, RecursiveMode::Recursive)?,
+                WatchStrategy::Coarse if is_dir => {
+                    for dir in inotify::coarse_dirs(root) {
+                        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+                        dirs.push(dir);
+                    }
+                }
+                WatchStrategy::Coarse => {
+                    watcher.watch(root, RecursiveMode::NonRecursive)?;
+                    dirs.push(root.clone());
+                }
+            }
+        }
+
+        for file in &files {
+            watcher.watch(file, RecursiveMode::NonRecursive)?;
+        }
+
+        let mut link = None;
+        if options.follow_symlinks && requested.is_symlink() {
+            let requested = paths::absolute(requested)?;
+            let parent = paths::canonicalize(requested.parent().unwrap())?;
+            watcher.watch(&parent, RecursiveMode::NonRecursive)?;
+            link = Some(parent.join(requested.file_name().unwrap()));
+        }
+
+        Ok(Watched {
+            path,
+            is_dir,
+            roots,
+            files,
+            dirs,
+            link,
+        })
+    }
+
+    fn unwatch(&self, watcher: &mut RecommendedWatcher) {
+        for root in self.roots.iter().chain(&self.files).chain(&self.dirs) {
+            watcher.unwatch(root).ok();
+        }
+        if let Some(parent) = self.link.as_deref().and_then(Path::parent) {
+            watcher.unwatch(parent).ok();
+        }
+    }
+
+    /// Whether the watches no longer cover `requested` after `event`, because a watched
+    /// directory or linked file was removed or replaced, or the symlink was pointed elsewhere.
+    fn is_stale(&self, requested: &Path, event: &Event) -> bool {
+        event.paths.iter().any(|path| {
+            self.roots
+                .iter()
+                .chain(&self.files)
+                .any(|root| paths::same(path, root))
+        }) || self.is_missing()
+            || (self.link.is_some()
+                && paths::canonicalize(requested).map_or(true, |path| path != self.path))
+    }
+
+    /// Whether the watch path went away without an event saying so.
+    fn is_gone(&self, requested: &Path) -> bool {
+        self.is_missing() || (!self.is_dir && !self.path.exists()) || !requested.exists()
+    }
+
+    /// Whether a watched directory or linked file no longer exists, or is no longer a
+    /// directory or file respectively.
+    fn is_missing(&self) -> bool {
+        self.roots.iter().any(|root| !root.is_dir())
+            || self.files.iter().any(|file| !file.is_file())
+    }
+
+    /// Wait for `requested` to be watchable again, backing off between attempts.
+    ///
+    /// Running out of inotify watches is reported once, in case the limit is raised meanwhile.
+    async fn rewatch(
+        watcher: &mut RecommendedWatcher,
+        requested: &Path,
+        options: WatchOptions,
+    ) -> Watched {
+        let mut interval = REWATCH_INTERVAL;
+        let mut reported = false;
+        loop {
+            Timer::after(interval).await;
+            let exhausted = match Watched::watch(watcher, requested, options) {
+                Ok(watched) => return watched,
+                Err(e) => inotify::is_exhausted(e.as_ref()),
+            };
+            if exhausted && !reported {
+                error!(
+                    watch_path = ?requested,
+                    reason = "exhausted",
+                    "{}, retrying meanwhile...",
+                    inotify::exhausted_message(requested)
+                );
+                reported = true;
+            }
+            interval = (interval * 2).min(REWATCH_MAX_INTERVAL);
+        }
+    }
+
+    /// The path in `event` that should trigger a rebuild, if any.
+    fn changed<'a>(&self, event: &'a Event) -> Option<&'a PathBuf> {
+        event.paths.iter().find(|candidate| {
+            if self.is_dir {
+                self.roots
+                    .iter()
+                    .any(|root| paths::starts_with(candidate, root))
+                    || self.files.iter().any(|file| paths::same(candidate, file))
+            } else {
+                paths::same(candidate, &self.path)
+            }
+        })
+    }
+}
+
 /// Watch a file or directory, sending relevant events through the provided channel.
+///
+/// If the watch path is missing, removed or replaced, it is watched again once it reappears.
+///
+/// With a `heartbeat` interval, a watcher that stops delivering events is replaced.
 async fn async_watch<P: AsRef<Path>>(
+    project: String,
     path: P,
     change_tx: Sender<Msg>,
+    explain: bool,
+    options: WatchOptions,
+    heartbeat_interval: Option<Duration>,
 ) -> Result<(), Box<dyn Error>> {
-    let path = path.as_ref();
-    let path = std::fs::canonicalize(path)
-        .unwrap_or_else(|e| panic!("Failed to canonicalize path {path:?}: {e:}"));
+    let requested = path.as_ref();
 
-    let (mut watcher, rx) = async_watcher()?;
+    let (mut watcher, mut rx) = async_watcher()?;
 
-    // Add a path to be watched. All files and directories at that path and
-    // below will be monitored for changes.
-    let watch_path = if path.is_dir() {
-        path.clone()
-    } else {
-        path.parent().unwrap().to_owned()
+    let initial = Watched::watch(&mut watcher, requested, options)
+        .map_err(|e| (inotify::is_exhausted(e.as_ref()), e.to_string()));
+    let mut watched = match initial {
+        Ok(watched) => watched,
+        Err((exhausted, e)) => {
+            // Running out of watches is reported while retrying
+            if !exhausted {
+                warn!(
+                    watch_path = ?requested,
+                    reason = "unavailable",
+                    "Can't watch {requested:?} ({e:}), waiting for it to appear..."
+                );
+            }
+            let watched = Watched::rewatch(&mut watcher, requested, options).await;
+            info!(watch_path = ?requested, "Watching {:?}", watched.path);
+            watched
+        }
     };
-    watcher.watch(watch_path.as_ref(), RecursiveMode::Recursive)?;
+    let mut heartbeat =
+        heartbeat_interval.map(|interval| Heartbeat::new(&watched.roots[0], interval));
+
+    loop {
+        let res = future::or(async { Some(rx.recv().await) }, async {
+            Timer::after(LIVENESS_INTERVAL).await;
+            None
+        })
+        .await;
+
+        if let (Some(Ok(Ok(event))), Some(heartbeat)) = (&res, &mut heartbeat) {
+            if heartbeat.is_heartbeat(event) {
+                heartbeat.received();
+                continue;
+            }
+        }
+
+        let beat = match heartbeat.as_mut().map(Heartbeat::tick) {
+            Some(Ok(beat)) => beat,
+            Some(Err(e)) => {
+                warn!(
+                    "Can't write a heartbeat file in {:?} ({e:}), no longer checking its watcher",
+                    watched.roots[0]
+                );
+                heartbeat = None;
+                Beat::Alive
+            }
+            None => Beat::Alive,
+        };
+
+        if beat == Beat::Stalled {
+            warn!(
+                watch_path = ?requested,
+                reason = "stalled",
+                "The watcher of {requested:?} stopped delivering events, recreating it..."
+            );
+            let watch_path = requested.to_owned();
+            change_tx
+                .send(Msg::Health(
+                    project.clone(),
+                    HealthEvent::WatchStalled {
+                        watch_path: watch_path.clone(),
+                    },
+                ))
+                .await
+                .unwrap();
+
+            // Replace the watcher before its receiver, so it never sends into a closed channel
+            let (new_watcher, new_rx) = async_watcher()?;
+            watcher = new_watcher;
+            rx = new_rx;
+            let rewatched = Watched::watch(&mut watcher, requested, options).ok();
+            watched = match rewatched {
+                Some(watched) => watched,
+                None => Watched::rewatch(&mut watcher, requested, options).await,
+            };
+            heartbeat =
+                heartbeat_interval.map(|interval| Heartbeat::new(&watched.roots[0], interval));
+            info!(watch_path = ?requested, "Watching {:?} again", watched.path);
+            change_tx
+                .send(Msg::Health(
+                    project.clone(),
+                    HealthEvent::WatchRecovered { watch_path },
+                ))
+                .await
+                .unwrap();
+
+            // Anything that changed while the watcher was stalled went unnoticed
+            let trigger = Trigger {
+                changed: watched.path.clone(),
+                watch_path: watched.path.clone(),
+            };
+            change_tx
+                .send(Msg::Change(project.clone(), Some(trigger)))
+                .await
+                .unwrap();
+            continue;
+        }
+
+        let lost = match &res {
+            Some(Ok(Ok(event))) => {
+                let renamed = matches!(
+                    event.kind,
+                    EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+                );
+                (renamed && watched.is_stale(requested, event)).then_some("removed")
+            }
+            Some(Ok(Err(_))) => None,
+            Some(Err(_)) => break,
+            None => watched.is_gone(requested).then_some("gone"),
+        };
+
+        if let Some(reason) = lost {
+            warn!(
+                watch_path = ?requested,
+                reason,
+                "{requested:?} was removed, replaced or unmounted, waiting for it to reappear..."
+            );
+            watched.unwatch(&mut watcher);
+            watched = Watched::rewatch(&mut watcher, requested, options).await;
+            heartbeat =
+                heartbeat_interval.map(|interval| Heartbeat::new(&watched.roots[0], interval));
+            info!(watch_path = ?requested, "Watching {:?} again", watched.path);
+
+            let trigger = Trigger {
+                changed: watched.path.clone(),
+                watch_path: watched.path.clone(),
+            };
+            change_tx
+                .send(Msg::Change(project.clone(), Some(trigger)))
+                .await
+                .unwrap();
+            continue;
+        }
+
+        match res {
+            Some(Ok(Ok(event))) => match watched.changed(&event) {
+                Some(changed) => {
+                    let trigger = Trigger {
+                        changed: changed.clone(),
+                        watch_path: watched.path.clone(),
+                    };
+                    change_tx
+                        .send(Msg::Change(project.clone(), Some(trigger)))
+                        .await
+                        .unwrap();
+                }
+                None if explain => info!(
+                    "Ignoring change to {:?}, it isn't the watched path {:?}",
+                    event.paths, watched.path
+                ),
+                None => (),
+            },
+            Some(Ok(Err(e))) => error!("Watch error: {:?}", e),
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+/// Watch the base directory of an asset glob, sending matching file changes through the provided channel.
+async fn async_watch_assets(
+    project: String,
+    glob: AssetGlob,
+    change_tx: Sender<Msg>,
+) -> Result<(), Box<dyn Error>> {
+    let (mut watcher, rx) = async_watcher()?;
+    watcher.watch(glob.base(), RecursiveMode::Recursive)?;
 
     while let Ok(res) = rx.recv().await {
         match res {
             Ok(event) => {
-                if path.is_dir()
-                    || event
-                        .paths
-                        .iter()
-                        .find(|candidate| **candidate == path)
-                        .is_some()
-                {
-                    change_tx.send(Msg::Change).await.unwrap();
+                for path in event.paths {
+                    if glob.matches(&path) && path.is_file() {
+                        change_tx
+                            .send(Msg::Asset(project.clone(), glob.clone(), path))
+                            .await
+                            .unwrap();
+                    }
                 }
             }
             Err(e) => error!("Watch error: {:?}", e),
@@ -215,37 +1848,134 @@ async fn async_watch<P: AsRef<Path>>(
     Ok(())
 }
 
-async fn handle_compile_result(
-    result: CompileResult,
-    output_path: Option<PathBuf>,
-    output_format: OutputFormat,
+/// Report a finished build, write its output and notify any webhooks.
+///
+/// Returns the build event and the artifact written, or `None` if the build was cancelled.
+async fn handle_build_result(
+    result: Result<Compiled, BuildError>,
+    duration: Duration,
+    args: ShaderBuilder,
+    previous_artifact: Option<PathBuf>,
+) -> Option<(BuildEvent, Option<ManifestArtifact>)> {
+    let mut phases = None;
+    let result = match result {
+        Ok(Compiled {
+            result,
+            mut timings,
+        }) => {
+            let result =
+                handle_compile_result(result, &mut timings, args.clone(), previous_artifact).await;
+            info!("Build took {duration:.2?}: {timings}");
+            phases = Some(timings);
+            result
+        }
+        Err(e) => Err(e),
+    };
+
+    let (artifact, error) = match result {
+        Ok(artifact) => (artifact, None),
+        Err(BuildError::Cancelled) => {
+            info!("Build cancelled");
+            return None;
+        }
+        Err(e) => {
+            error!("Build failed!\n{e:}");
+            (None, Some(e.to_string()))
+        }
+    };
+
+    if error.is_none() {
+        console::status(
+            true,
+            "Finished",
+            &format!("{} in {duration:.2?}", args.crate_name()),
+        );
+    } else {
+        console::status(
+            false,
+            "Failed",
+            &format!("{} after {duration:.2?}", args.crate_name()),
+        );
+    }
+
+    let event = BuildEvent {
+        crate_name: args.crate_name(),
+        status: if error.is_none() {
+            BuildStatus::Succeeded
+        } else {
+            BuildStatus::Failed
+        },
+        duration_ms: duration.as_millis(),
+        phases,
+        artifact_hash: artifact.as_ref().map(|artifact| artifact.hash.clone()),
+        error,
+    };
+
+    for url in args.webhook {
+        webhook::post(url, event.clone()).await;
+    }
+
+    Some((event, artifact))
+}
+
+/// Print tables of the compiled entry points and modules.
+fn print_summary(
+    entry_points: &[String],
+    modules: &RustGpuBuilderModules,
+    module_paths: &[(String, PathBuf)],
+    args: &ShaderBuilder,
 ) {
-    info!("Entry Points:");
-    for entry in &result.entry_points {
-        println!("{entry:}");
+    let stages = stages::group(modules, &args.entry_point_rename)
+        .into_iter()
+        .flat_map(|(stage, names)| names.into_iter().map(move |name| (name, stage)))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut table = console::Table::new("Entry points", vec!["name", "stage"]);
+    for entry_point in entry_points {
+        let stage = stages.get(entry_point).copied().unwrap_or("unknown");
+        table.row(vec![entry_point.clone(), stage.to_string()]);
     }
+    table.print();
 
-    let entry_points = result.entry_points;
+    let sizes = reflect::named_modules(modules, &args.crate_name())
+        .into_iter()
+        .map(|(name, module)| (name, module.len()))
+        .collect::<BTreeMap<_, _>>();
+    let mut table = console::Table::new("Modules", vec!["name", "size", "path"]);
+    for (name, path) in module_paths {
+        let size = sizes.get(name.as_str()).map_or_else(String::new, |len| {
+            format!("{:.1} KiB", *len as f64 / 1024.0)
+        });
+        table.row(vec![name.clone(), size, path.display().to_string()]);
+    }
+    table.print();
+}
 
-    println!();
+/// Print, check and write the output of a successful compile,
+/// returning the written artifact if there was an output path.
+///
+/// Time spent on each phase is added to `timings`.
+async fn handle_compile_result(
+    result: CompileResult,
+    timings: &mut Timings,
+    args: ShaderBuilder,
+    previous_artifact: Option<PathBuf>,
+) -> Result<Option<ManifestArtifact>, BuildError> {
+    let mut lap = Instant::now();
+    let result = rename::apply(&args.entry_point_rename, result).map_err(BuildError::Rename)?;
 
-    info!("Modules:");
-    match &result.module {
-        spirv_builder::ModuleResult::SingleModule(single) => {
-            println!("{single:?}");
-        }
+    let entry_points = result.entry_points;
 
-        spirv_builder::ModuleResult::MultiModule(multi) => {
-            for (k, module) in multi {
-                println!("{k:}: {module:?}");
-            }
+    let module_paths = match &result.module {
+        spirv_builder::ModuleResult::SingleModule(single) => {
+            vec![(args.crate_name(), single.clone())]
         }
+        spirv_builder::ModuleResult::MultiModule(multi) => multi
+            .iter()
+            .map(|(name, path)| (name.clone(), path.clone()))
+            .collect(),
     };
 
-    let Some(output_path) = output_path else {
-                                    return
-                                };
-
     let modules = match result.module {
         spirv_builder::ModuleResult::SingleModule(single) => {
             let module = async_fs::read(single)
@@ -266,125 +1996,957 @@ async fn handle_compile_result(
         }
     };
 
+    print_summary(&entry_points, &modules, &module_paths, &args);
+
+    args.budgets
+        .check(&modules, &args.crate_name())
+        .map_err(BuildError::Budget)?;
+    args.lints
+        .check(&modules, &args.crate_name(), &args.entry_point_rename)
+        .map_err(BuildError::Lint)?;
+    args.deny_list
+        .check(&modules, &args.crate_name())
+        .map_err(BuildError::Denied)?;
+    pipelines::check(&args.pipeline, &modules, &args.entry_point_rename)
+        .map_err(BuildError::Pipeline)?;
+    args.validation
+        .check(
+            &modules,
+            &args.crate_name(),
+            &args.target,
+            &args.validator_options(),
+        )
+        .map_err(BuildError::Validation)?;
+    timings.lap(Phase::Postprocess, &mut lap);
+
+    let wgsl = match args.preset {
+        Some(Preset::WebGpu) => Some(
+            webgpu::translate(&modules, &args.crate_name())
+                .map_err(|errors| BuildError::Validation(ValidationReport(errors)))?,
+        ),
+        None => None,
+    };
+
+    let kernels = target::is_opencl(&args.target)
+        .then(|| kernels::signatures(&modules, &args.entry_point_rename));
+    timings.lap(Phase::Reflection, &mut lap);
+
+    let (modules, symbols) = if args.split_debug_info {
+        let (stripped, symbols) = symbols::split(&modules, &args.crate_name());
+        (stripped, Some(symbols))
+    } else {
+        (modules, None)
+    };
+    timings.lap(Phase::Postprocess, &mut lap);
+
+    if let Some(spv_dir) = &args.spv_dir {
+        let written = output::write_spv_dir(spv_dir, &modules, &args.crate_name())
+            .await
+            .map_err(|e| BuildError::Output(format!("Failed to write SPIR-V modules: {e}")))?;
+        info!("Copied {} module(s) to {spv_dir:?}", written.len());
+    }
+    timings.lap(Phase::Serialization, &mut lap);
+
+    let Some(output_template) = args.output_path.clone() else {
+        return Ok(None)
+    };
+
     let out = RustGpuBuilderOutput {
         entry_points,
         modules,
     };
 
+    let stages = args
+        .group_entry_points
+        .then(|| stages::group(&out.modules, &args.entry_point_rename));
+    let pipelines = (!args.pipeline.is_empty()).then(|| pipelines::record(&args.pipeline));
+
+    let provenance = if args.no_provenance {
+        None
+    } else {
+        let path_to_crate = args.path_to_crate.clone();
+        blocking::unblock(move || provenance::detect(&path_to_crate)).await
+    };
+    timings.lap(Phase::Reflection, &mut lap);
+
+    let (stored, module_aliases) = match args
+        .dedup_modules
+        .then(|| output::dedup_modules(&out.modules))
+        .flatten()
+    {
+        Some((modules, aliases)) => {
+            let shared = aliases.values().collect::<BTreeSet<_>>().len();
+            info!(
+                "{} entry points share {shared} module(s), storing each once",
+                aliases.len() + shared
+            );
+            let stored = RustGpuBuilderOutput {
+                entry_points: out.entry_points.clone(),
+                modules,
+            };
+            (Some(stored), Some(aliases))
+        }
+        None => (None, None),
+    };
+    let stored = stored.as_ref().unwrap_or(&out);
+
+    let dictionary = args
+        .compress_dictionary
+        .as_deref()
+        .map(compress::read_dictionary)
+        .transpose()
+        .map_err(BuildError::Compression)?;
+    let mut config = args.build_config();
+    config.compression_dictionary = dictionary.as_deref().and_then(compress::dictionary_id);
+
+    let deduplicated = module_aliases.is_some();
+    let artifact = Artifact {
+        entry_points: &stored.entry_points,
+        modules: (!deduplicated).then_some(&stored.modules),
+        deduplicated_modules: deduplicated.then_some(&stored.modules),
+        stages,
+        pipelines,
+        wgsl,
+        kernels,
+        provenance,
+        config: Some(config),
+        module_aliases,
+        checksums: checksum::ModuleChecksums::new(&stored.modules),
+    };
+    let bytes = args.output_format.serialize(&artifact);
+    let bytes = match args.compress {
+        Some(Compression::Zstd) => compress::compress(&bytes, dictionary.as_deref())
+            .map_err(|e| BuildError::Compression(e.to_string()))?,
+        None => bytes,
+    };
+
+    // Before writing anything, so a bad key doesn't leave an unsigned artifact behind
+    let signature = args
+        .signing_key
+        .as_deref()
+        .map(|path| {
+            signing::load_signing_key(path)
+                .map(|key| signing::sign(&key, &bytes))
+                .map_err(|e| BuildError::Signing(e.to_string()))
+        })
+        .transpose()?;
+
+    let artifact_hash = output::artifact_hash(&bytes);
+
+    let output_path = output::expand_template(
+        &output_template,
+        &TemplateVars {
+            crate_name: &args.crate_name(),
+            target: &args.target,
+            profile: args.profile(),
+            hash: &artifact_hash,
+        },
+    );
+
     let output_dir = output_path.parent().expect("Output path must be a valid directory");
     async_fs::create_dir_all(&output_dir).await.ok();
 
-    match output_format {
-        OutputFormat::Json => {
-            let out = serde_json::to_string_pretty(&out).expect("Failed to serialize output");
+    let emitters = args
+        .emit
+        .iter()
+        .map(|emit| emit as &dyn Emitter)
+        .chain(args.emit_plugin.iter().map(|plugin| plugin as &dyn Emitter))
+        .collect::<Vec<_>>();
 
-            async_fs::write(&output_path, out)
-                .await
-                .expect("Failed to write output");
+    // Other builds writing to the same directory wait until this one's output is all there
+    let _lock = lock::output_dir(output_dir)
+        .await
+        .map_err(|e| warn!("Failed to lock {output_dir:?}, writing output without it: {e}"))
+        .ok();
+
+    // Compared with the artifact it replaces, which hot-reloading hosts still have loaded.
+    // With a {hash} in the output path, that's only found where the last build was recorded.
+    let previous_path = previous_artifact.unwrap_or_else(|| output_path.clone());
+    let interface_changes = if previous_path.exists() {
+        match output::read_artifact_with_dictionary(&previous_path, dictionary.as_deref()).await {
+            Ok(previous) => Interface::new(&out.modules, &args.entry_point_rename)
+                .changes(&Interface::new(&previous.modules, &args.entry_point_rename)),
+            Err(e) => {
+                warn!("Failed to read the previous artifact {previous_path:?}, so can't tell whether the interface changed: {e}");
+                vec![]
+            }
         }
-        OutputFormat::Messagepack => {
-            let out = rmp_serde::to_vec_named(&out).expect("Failed to serialize output");
-            async_fs::write(&output_path, out)
+    } else {
+        vec![]
+    };
+
+    let previous = if emitters.iter().any(|emitter| emitter.needs_previous()) {
+        async_fs::read(&previous_path).await.ok()
+    } else {
+        None
+    };
+    let emitted = emit::run(
+        &emitters,
+        &EmitInput {
+            args: &args,
+            output: &out,
+            artifact: &artifact,
+            bytes: &bytes,
+            output_path: &output_path,
+            previous: previous.as_deref(),
+        },
+    )
+    .map_err(BuildError::Emit)?;
+
+    if args.versioned_output {
+        let versioned_path = output::write_versioned(&output_path, &bytes)
+            .await
+            .map_err(|e| BuildError::Output(format!("Failed to write versioned output: {e}")))?;
+        info!("Wrote output to {versioned_path:?}, linked from {output_path:?}");
+
+        if let Some(keep) = args.keep_artifacts {
+            let removed = output::prune_versions(&output_path, keep)
                 .await
-                .expect("Failed to write output");
+                .map_err(|e| {
+                    BuildError::Output(format!("Failed to prune versioned output: {e}"))
+                })?;
+            for path in removed {
+                info!("Removed old output {path:?}");
+            }
+        }
+    } else {
+        output::write_atomic(&output_path, &bytes)
+            .await
+            .map_err(|e| BuildError::Output(format!("Failed to write output: {e}")))?;
+        info!("Wrote output to {output_path:?}");
+    }
+
+    if let Some(signature) = signature {
+        let signature_path = signing::signature_path(&output_path);
+        output::write_atomic(&signature_path, &signature)
+            .await
+            .map_err(|e| BuildError::Output(format!("Failed to write signature: {e}")))?;
+        info!("Wrote signature to {signature_path:?}");
+    }
+
+    if let Some(symbols) = symbols {
+        let symbols_path = output_path.with_extension("symbols");
+        output::write_atomic(&symbols_path, &args.output_format.serialize(&symbols))
+            .await
+            .map_err(|e| BuildError::Output(format!("Failed to write debug symbols: {e}")))?;
+        info!("Wrote debug symbols to {symbols_path:?}");
+    }
+
+    for (name, emitted) in emitted {
+        output::write_atomic(&emitted.path, &emitted.bytes)
+            .await
+            .map_err(|e| BuildError::Output(format!("Failed to write {name} output: {e}")))?;
+        info!("Wrote {name} output to {:?}", emitted.path);
+    }
+
+    if !interface_changes.is_empty() {
+        let event = InterfaceEvent {
+            event: "interface_changed",
+            crate_name: args.crate_name(),
+            artifact_hash: artifact_hash.clone(),
+            changes: interface_changes,
+        };
+        warn!("{event}");
+        for url in &args.webhook {
+            webhook::post(url.clone(), event.clone()).await;
+        }
+    }
+
+    timings.lap(Phase::Serialization, &mut lap);
+
+    if args.prime_pipeline_cache {
+        let modules = out.modules.clone();
+        let cache_output_path = output_path.clone();
+        match blocking::unblock(move || {
+            pipeline_cache::prime(&modules, &cache_output_path).map_err(|e| e.to_string())
+        })
+        .await
+        {
+            Ok(written) => {
+                for path in written {
+                    info!("Wrote pipeline cache to {path:?}");
+                }
+            }
+            Err(e) => warn!("Failed to prime pipeline caches: {e}"),
         }
     }
-    println!();
-    info!("Wrote output to {output_path:?}");
+
+    assets::copy_all(&args.asset, output_dir).await;
+
+    Ok(Some(ManifestArtifact {
+        crate_name: args.crate_name(),
+        target: args.target.clone(),
+        profile: args.profile().to_string(),
+        path: output_path,
+        hash: artifact_hash,
+        size: bytes.len() as u64,
+        entry_points: out.entry_points,
+    }))
 }
 
-fn main() {
-    tracing_subscriber::fmt().init();
+/// A finished build, with the arguments it was built with.
+struct Finished {
+    result: Result<Compiled, BuildError>,
+    duration: Duration,
+    args: ShaderBuilder,
+    /// The job that requested the build, if any.
+    job: Option<JobId>,
+    fingerprint: Option<String>,
+}
+
+/// Where finished builds are recorded, besides with their jobs.
+#[derive(Debug, Clone)]
+struct Records {
+    state: Option<State>,
+    manifest: Option<RunManifest>,
+    history: History,
+}
 
-    let mut args = ShaderBuilder::parse();
+impl Records {
+    /// Where the last build of `args` wrote its artifact, from the build history or the state file.
+    fn previous_artifact(&self, args: &ShaderBuilder) -> Option<PathBuf> {
+        self.history
+            .last_artifact_hash(&args.crate_name())
+            .and_then(|hash| args.artifact_path(&hash))
+            .or_else(|| self.state.as_ref()?.get(&args.path_to_crate)?.artifact)
+    }
+}
 
-    println!();
-    info!("Shader Builder");
-    println!();
+impl Finished {
+    /// Report the build, and record its outcome with its job, in the state file,
+    /// the run manifest and the build history.
+    async fn handle(self, jobs: &Jobs, records: &Records) {
+        let previous_artifact = records.previous_artifact(&self.args);
+        let (event, artifact) = match handle_build_result(
+            self.result,
+            self.duration,
+            self.args.clone(),
+            previous_artifact,
+        )
+        .await
+        {
+            Some((event, artifact)) => (Some(event), artifact),
+            None => (None, None),
+        };
 
-    info!("Building shader...");
-    println!();
-    match args.build_shader() {
-        Ok(result) => {
-            future::block_on(handle_compile_result(
-                result,
-                args.output_path.clone(),
-                args.output_format,
-            ));
+        if let Some(job) = self.job {
+            jobs.finish(job, event.as_ref());
         }
-        Err(e) => {
-            error!("Build failed!\n{e:}");
+
+        if let (Some(state), Some(fingerprint), Some(event)) =
+            (&records.state, self.fingerprint, &event)
+        {
+            let artifact = event
+                .artifact_hash
+                .as_ref()
+                .and_then(|hash| self.args.artifact_path(hash));
+            state.record(&self.args.path_to_crate, fingerprint, event, artifact);
+        }
+
+        if let (Some(manifest), Some(artifact)) = (&records.manifest, artifact) {
+            manifest.record(artifact);
+        }
+
+        if let Some(event) = event {
+            records.history.record(event);
         }
     }
-    println!();
+}
 
-    let Some(watch_paths) = args.watch_paths.take() else {
-        return
-    };
+/// Spawn a project's file and asset watchers, which stop when their tasks are dropped.
+fn spawn_watchers(
+    ex: &Executor<'_>,
+    project: &str,
+    args: &ShaderBuilder,
+    change_tx: &Sender<Msg>,
+) -> Vec<Task<()>> {
+    let watch_paths = args.watch_paths.clone().unwrap_or_default();
+    if let Some(warning) =
+        inotify::check_budget(watch_paths.iter().map(Path::new), args.watch_strategy)
+    {
+        warn!("{warning}");
+    }
 
-    let ex = Executor::new();
-    let (change_tx, change_rx) = unbounded::<Msg>();
-    let (build_tx, build_rx) = unbounded::<Msg>();
+    let mut watchers = vec![];
+    for path in watch_paths {
+        info!("Watching {path:} for changes...");
+        watchers.push(ex.spawn({
+            let project = project.to_string();
+            let change_tx = change_tx.clone();
+            let explain = args.explain;
+            let options = WatchOptions {
+                follow_symlinks: args.follow_symlinks,
+                strategy: args.watch_strategy,
+            };
+            let heartbeat = args.heartbeat.map(Duration::from_secs);
+            async move {
+                async_watch(project, path, change_tx, explain, options, heartbeat)
+                    .await
+                    .expect("Async watcher error");
+            }
+        }));
+    }
 
-    Parallel::new()
-        // Spawn file watchers
-        .each(watch_paths, |path| {
-            info!("Watching {path:} for changes...");
-            future::block_on(async {
-                async_watch(path, change_tx)
+    for glob in args.asset.clone() {
+        info!("Watching {:?} for asset changes...", glob.base());
+        watchers.push(ex.spawn({
+            let project = project.to_string();
+            let change_tx = change_tx.clone();
+            async move {
+                async_watch_assets(project, glob, change_tx)
                     .await
                     .expect("Async watcher error");
-            });
+            }
+        }));
+    }
+
+    watchers
+}
+
+/// Run the watch-mode message bus until `shutdown` is cancelled,
+/// then wait for in-flight work to wind down.
+async fn bus(
+    ex: &Executor<'_>,
+    projects: Projects,
+    change_rx: Receiver<Msg>,
+    jobs: Jobs,
+    records: Records,
+    min_rebuild_interval: Duration,
+    shutdown: CancelToken,
+) {
+    let (build_tx, build_rx) = unbounded::<Msg>();
+
+    // Build results are handled one at a time, in order, by a single task
+    let (result_tx, result_rx) = unbounded::<Finished>();
+    let results = ex.spawn({
+        let jobs = jobs.clone();
+        let records = records.clone();
+        async move {
+            while let Ok(finished) = result_rx.recv().await {
+                finished.handle(&jobs, &records).await;
+            }
+        }
+    });
+
+    // Projects build one at a time into the shared target directory,
+    // and the queue never builds the same crate twice at once
+    let mut queue = BuildQueue::<(ShaderBuilder, Option<JobId>)>::new(min_rebuild_interval, 1);
+    // Watchers of projects registered over HTTP, which send through the bus's own channel
+    let mut watchers = BTreeMap::<String, Vec<Task<()>>>::new();
+    let mut building = None;
+    let mut current = None::<(ShaderBuilder, Option<JobId>)>;
+    let mut timer = None;
+    loop {
+        let msg = future::race(future::race(change_rx.recv(), build_rx.recv()), async {
+            shutdown.cancelled().await;
+            Err(async_channel::RecvError)
         })
-        // Spawn message Bus
-        .add(|| {
-            let mut building = false;
-            loop {
-                match future::block_on(futures_lite::future::race(
-                    change_rx.recv(),
-                    build_rx.recv(),
-                )) {
-                    // On file change, spawn a build task
-                    Ok(Msg::Change) => {
-                        if !building {
-                            building = true;
-                            println!();
-                            info!("Building shader...");
-                            println!();
-                            ex.spawn({
-                                let build_tx = build_tx.clone();
-                                let args = args.clone();
-                                async move {
-                                    build_tx
-                                        .send(Msg::Build(args.build_shader()))
-                                        .await
-                                        .unwrap();
-                                }
-                            })
-                            .detach();
-                        }
-                    }
-                    // On build complete, spawn a handle_compile_result task
-                    Ok(Msg::Build(result)) => {
-                        if let Ok(result) = result {
-                            let output_path = args.output_path.clone();
-                            let output_format = args.output_format;
-                            ex.spawn(handle_compile_result(result, output_path, output_format))
-                                .detach();
+        .await;
+
+        if shutdown.is_cancelled() {
+            break;
+        }
+
+        match msg {
+            // On file change, queue a rebuild
+            Ok(Msg::Change(project, trigger)) => {
+                // Changes can still arrive from a project that was just unregistered
+                let Some(Project { args, .. }) = projects.get(&project) else {
+                    continue
+                };
+                if let Some(state) = &records.state {
+                    state.mark_dirty(&args.path_to_crate);
+                }
+                let pushed = queue.push(
+                    args.path_to_crate.clone(),
+                    Priority::Watch,
+                    (args.clone(), None),
+                );
+                let queued = matches!(pushed, Pushed::Queued);
+                if let (true, Some(trigger)) = (args.explain, trigger) {
+                    info!(
+                        "{:?} changed under watch path {:?}, mapped to crate {:?}{}",
+                        trigger.changed,
+                        trigger.watch_path,
+                        args.path_to_crate,
+                        if queued {
+                            ""
                         } else {
-                            error!("Build failed!");
+                            " (rebuild already queued)"
                         }
-                        println!();
-                        building = false;
+                    );
+                }
+            }
+            // On HTTP request, queue a rebuild with its options, replacing any older request
+            Ok(Msg::Request(job, request)) => {
+                let pushed = queue.push(
+                    request.path_to_crate.clone(),
+                    Priority::Requested,
+                    (*request, Some(job)),
+                );
+                if let Pushed::Coalesced((_, Some(superseded))) = pushed {
+                    info!("Job {superseded} superseded by job {job}");
+                    jobs.supersede(superseded);
+                }
+            }
+            // On registration, watch the project and build it
+            Ok(Msg::Register(project)) => {
+                if let Some(Project { args, .. }) = projects.get(&project) {
+                    watchers.insert(
+                        project.clone(),
+                        spawn_watchers(ex, &project, &args, &build_tx),
+                    );
+                    build_tx.send(Msg::Change(project, None)).await.unwrap();
+                }
+            }
+            // On unregistration, stop watching the project
+            Ok(Msg::Unregister(project)) => {
+                watchers.remove(&project);
+            }
+            // On asset change, copy it over
+            Ok(Msg::Asset(project, glob, path)) => {
+                let Some(Project { args, .. }) = projects.get(&project) else {
+                    continue
+                };
+                match args.asset_dir() {
+                    Some(asset_dir) => assets::copy(&glob, &path, &asset_dir).await,
+                    None => warn!(
+                        "Output directory depends on {{hash}}, {path:?} will be copied on next build"
+                    ),
+                }
+            }
+            // On watcher health changes, notify webhooks
+            Ok(Msg::Health(project, event)) => {
+                let Some(Project { args, .. }) = projects.get(&project) else {
+                    continue
+                };
+                for url in args.webhook {
+                    ex.spawn(webhook::post(url, event.clone())).detach();
+                }
+            }
+            // On rate limit expiry, re-check the queue
+            Ok(Msg::Ready) => timer = None,
+            // On build complete, hand the result off
+            Ok(Msg::Build(result, duration, fingerprint)) => {
+                building = None;
+                let (args, job) = current.take().unwrap();
+                queue.finish(&args.path_to_crate);
+                result_tx
+                    .send(Finished {
+                        result,
+                        duration,
+                        args,
+                        job,
+                        fingerprint,
+                    })
+                    .await
+                    .unwrap();
+            }
+            Err(e) => {
+                panic!("{e:}")
+            }
+        }
+
+        match queue.next() {
+            // Spawn a build task
+            Next::Build(_, (build_args, job)) => {
+                if let Some(job) = job {
+                    jobs.start(job);
+                }
+                build_args.print_banner(job);
+                current = Some((build_args.clone(), job));
+                building = Some(ex.spawn({
+                    let build_tx = build_tx.clone();
+                    let args = build_args;
+                    let state = records.state.clone();
+                    let cancel = shutdown.child();
+                    async move {
+                        let start = Instant::now();
+                        // Compiling blocks for as long as cargo runs, so keep it off the executor
+                        let (result, fingerprint) = blocking::unblock(move || {
+                            // Fingerprint first, so changes made mid-build aren't recorded as built
+                            let fingerprint =
+                                state.map(|state| state.fingerprint(&args.worker_args));
+                            (args.build_shader(&cancel), fingerprint)
+                        })
+                        .await;
+                        build_tx
+                            .send(Msg::Build(result, start.elapsed(), fingerprint))
+                            .await
+                            .ok();
                     }
-                    Err(e) => {
-                        panic!("{e:}")
+                }));
+            }
+            // Spawn a timer task to wake the bus once the rebuild interval has elapsed
+            Next::Wait(duration) if timer.is_none() => {
+                timer = Some(ex.spawn({
+                    let build_tx = build_tx.clone();
+                    async move {
+                        Timer::after(duration).await;
+                        build_tx.send(Msg::Ready).await.ok();
                     }
-                }
+                }));
             }
-        })
-        // Run executor on main thread
-        .finish(|| loop {
-            future::block_on(ex.tick())
+            Next::Wait(_) | Next::Idle => (),
+        }
+    }
+
+    // The build's worker has been killed, so let it report back before exiting
+    drop(watchers);
+    if let Some(building) = building {
+        building.await;
+        // Registered projects' watchers may have sent changes ahead of the result
+        let built = std::iter::from_fn(|| build_rx.try_recv().ok()).find_map(|msg| match msg {
+            Msg::Build(result, duration, fingerprint) => Some((result, duration, fingerprint)),
+            _ => None,
         });
+        if let (Some((result, duration, fingerprint)), Some((args, job))) = (built, current.take())
+        {
+            result_tx
+                .send(Finished {
+                    result,
+                    duration,
+                    args,
+                    job,
+                    fingerprint,
+                })
+                .await
+                .unwrap();
+        }
+    }
+
+    drop(result_tx);
+    results.await;
+}
+
+/// A token cancelled by the first Ctrl-C, after which a second one exits immediately.
+fn shutdown_on_signal() -> CancelToken {
+    // Build workers lead their own process groups, so terminal signals
+    // have to be forwarded to them by cancelling their builds
+    let shutdown = CancelToken::new();
+    ctrlc::set_handler({
+        let shutdown = shutdown.clone();
+        move || {
+            if shutdown.is_cancelled() {
+                std::process::exit(130);
+            }
+            info!("Shutting down...");
+            shutdown.cancel();
+        }
+    })
+    .expect("Failed to set signal handler");
+    shutdown
+}
+
+fn main() {
+    let mut cli = Cli::parse();
+
+    console::init(cli.color);
+    tracing_subscriber::fmt()
+        .with_ansi(console::color_enabled())
+        .init();
+
+    let sources = source::Session;
+
+    if let Some(builder) = &mut cli.builder {
+        builder.worker_args = std::env::args_os().skip(1).collect();
+        if let Err(e) = builder
+            .apply_preset()
+            .and_then(|_| target::check_capabilities(&builder.target, &builder.capability))
+            .and_then(|_| builder.resolve_source())
+        {
+            error!("{e:}");
+            drop(sources);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(result_path) = worker::result_path() {
+        let args = cli.builder.expect("Missing shader build arguments");
+        worker::run(&args, &result_path);
+    }
+
+    if let Some(command) = cli.command {
+        if let Err(e) = command.run() {
+            error!("{e:}");
+            drop(sources);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let args = cli.builder.expect("Missing shader build arguments");
+
+    let defaults = args.default_capabilities();
+    if !defaults.is_empty() {
+        info!(
+            "Enabled {} by default for {}, pass --no-default-capabilities to only enable those given",
+            defaults
+                .iter()
+                .map(|capability| format!("{capability:?}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            args.target
+        );
+    }
+
+    let shutdown = shutdown_on_signal();
+
+    let background = args.background_startup_build && args.watch_paths.is_some();
+
+    let jobs = Jobs::default();
+    let state = args
+        .state_file
+        .clone()
+        .map(|path| State::load(path, args.sources()));
+    let fingerprint = state
+        .as_ref()
+        .map(|state| state.fingerprint(&args.worker_args));
+    let unchanged = state
+        .as_ref()
+        .zip(fingerprint.as_ref())
+        .and_then(|(state, fingerprint)| state.unchanged(&args.path_to_crate, fingerprint));
+    let records = Records {
+        state,
+        manifest: args.run_manifest.clone().map(RunManifest::new),
+        history: History::new(args.history),
+    };
+
+    if let Some(unchanged) = &unchanged {
+        args.print_banner(None);
+        info!(
+            "Sources unchanged since the last build, skipping it (artifact hash {}, {} build(s), {} failure(s))",
+            unchanged.artifact_hash.as_deref().unwrap_or("unknown"),
+            unchanged.builds,
+            unchanged.failures
+        );
+
+        // The skipped build's artifact is still part of this run's output
+        if let (Some(manifest), Some(path), Some(hash)) = (
+            &records.manifest,
+            unchanged.artifact.clone(),
+            unchanged.artifact_hash.clone(),
+        ) {
+            match future::block_on(ManifestArtifact::existing(&args, path.clone(), hash)) {
+                Ok(artifact) => manifest.record(artifact),
+                Err(e) => warn!("Failed to list {path:?} in the run manifest: {e}"),
+            }
+        }
+    } else if !background {
+        args.print_banner(None);
+        let start = Instant::now();
+        let result = args.build_shader(&shutdown);
+        future::block_on(
+            Finished {
+                result,
+                duration: start.elapsed(),
+                args: args.clone(),
+                job: None,
+                fingerprint,
+            }
+            .handle(&jobs, &records),
+        );
+    }
+
+    if args.watch_paths.is_none() || shutdown.is_cancelled() {
+        return;
+    }
+
+    let ex = Executor::new();
+    let (change_tx, change_rx) = unbounded::<Msg>();
+
+    let name = args.crate_name();
+    let projects = Projects::default();
+    projects
+        .register(
+            name.clone(),
+            Project {
+                args: args.clone(),
+                pinned: true,
+            },
+        )
+        .unwrap();
+
+    if background && unchanged.is_none() {
+        // Build through the message bus, so the watchers are already live
+        // while the cold compile is running
+        info!("Running the startup build in the background...");
+        future::block_on(change_tx.send(Msg::Change(name.clone(), None))).unwrap();
+    }
+
+    let mut watchers = spawn_watchers(&ex, &name, &args, &change_tx);
+
+    if let Some(addr) = args.serve {
+        let listener = match Async::<TcpListener>::bind(addr) {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to listen on {addr}: {e}");
+                std::process::exit(1);
+            }
+        };
+        info!("Listening for build requests on http://{addr}");
+        if !addr.ip().is_loopback() {
+            warn!("{addr} accepts build requests from other machines, without authentication");
+        }
+        watchers.push(
+            ex.spawn(
+                Server {
+                    projects: projects.clone(),
+                    jobs: jobs.clone(),
+                    history: records.history.clone(),
+                    change_tx,
+                }
+                .run(listener),
+            ),
+        );
+    }
+
+    let restart = args.auto_restart.then(SelfWatch::new).and_then(|watch| {
+        watch
+            .map_err(|e| warn!("Failed to find the builder executable for --auto-restart: {e}"))
+            .ok()
+    });
+    if let Some(restart) = &restart {
+        info!("Restarting when {:?} changes", restart.exe());
+        watchers.push(ex.spawn(restart.clone().run(shutdown.clone())));
+    }
+
+    let history = records.history.clone();
+    let min_rebuild_interval = Duration::from_millis(args.min_rebuild_interval);
+    future::block_on(ex.run(bus(
+        &ex,
+        projects,
+        change_rx,
+        jobs,
+        records,
+        min_rebuild_interval,
+        shutdown,
+    )));
+
+    history.print();
+
+    if let Some(restart) = restart.filter(SelfWatch::requested) {
+        drop(watchers);
+        restart.exec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    /// A fresh temporary directory holding a watch directory `w` that links to a file
+    /// `outside/lut.bin` and a directory `outside/textures`, as `w/lut.bin` and `w/textures`.
+    fn linked_tree(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "rust-gpu-builder-test-{name}-{}",
+            std::process::id()
+        ));
+        std::fs::remove_dir_all(&root).ok();
+        std::fs::create_dir_all(root.join("w")).unwrap();
+        std::fs::create_dir_all(root.join("outside/textures")).unwrap();
+        std::fs::write(root.join("outside/lut.bin"), b"lut").unwrap();
+        std::os::unix::fs::symlink("../outside/lut.bin", root.join("w/lut.bin")).unwrap();
+        std::os::unix::fs::symlink("../outside/textures", root.join("w/textures")).unwrap();
+        paths::canonicalize(root).unwrap()
+    }
+
+    #[cfg(unix)]
+    fn watch(requested: &Path) -> (RecommendedWatcher, Watched) {
+        let (mut watcher, _) = async_watcher().unwrap();
+        let options = WatchOptions {
+            follow_symlinks: true,
+            strategy: WatchStrategy::Recursive,
+        };
+        let watched = Watched::watch(&mut watcher, requested, options).unwrap();
+        (watcher, watched)
+    }
+
+    #[cfg(unix)]
+    fn modified(path: PathBuf) -> Event {
+        Event::new(EventKind::Modify(ModifyKind::Any)).add_path(path)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_linked_files_without_going_stale() {
+        let root = linked_tree("linked-files");
+        let requested = root.join("w");
+        let (_watcher, watched) = watch(&requested);
+
+        let lut = root.join("outside/lut.bin");
+        assert_eq!(
+            watched.roots,
+            [requested.clone(), root.join("outside/textures")]
+        );
+        assert_eq!(watched.files, [lut.clone()]);
+
+        assert_eq!(watched.changed(&modified(lut.clone())), Some(&lut));
+        assert!(!watched.is_stale(&requested, &modified(requested.join("lib.rs"))));
+        assert!(!watched.is_gone(&requested));
+
+        // Replaced by an editor saving through a rename
+        let event = Event::new(EventKind::Remove(notify::event::RemoveKind::File)).add_path(lut);
+        assert!(watched.is_stale(&requested, &event));
+
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn gone_once_a_linked_file_is_removed_or_replaced() {
+        let root = linked_tree("gone");
+        let requested = root.join("w");
+        let lut = root.join("outside/lut.bin");
+        let (_watcher, watched) = watch(&requested);
+        assert!(!watched.is_gone(&requested));
+
+        std::fs::remove_file(&lut).unwrap();
+        assert!(watched.is_gone(&requested));
+
+        std::fs::create_dir(&lut).unwrap();
+        assert!(watched.is_gone(&requested));
+
+        std::fs::remove_dir(&lut).unwrap();
+        std::fs::write(&lut, b"lut").unwrap();
+        assert!(!watched.is_gone(&requested));
+
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("rust-gpu-builder").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn keeps_at_least_one_artifact() {
+        let args = [
+            "shaders",
+            "out.bin",
+            "--versioned-output",
+            "--keep-artifacts",
+        ];
+        let error = parse(&[&args[..], &["0"]].concat()).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ValueValidation);
+        let cli = parse(&[&args[..], &["1"]].concat()).unwrap();
+        assert_eq!(cli.builder.unwrap().keep_artifacts, Some(1));
+    }
+
+    #[test]
+    fn takes_debug_assertions_only_with_an_equals_sign() {
+        let debug_assertions =
+            |args: &[&str]| parse(args).map(|cli| cli.builder.unwrap().cargo.debug_assertions);
+        assert_eq!(
+            debug_assertions(&["shaders", "--debug-assertions"]).unwrap(),
+            Some(true)
+        );
+        assert_eq!(
+            debug_assertions(&["shaders", "--debug-assertions=false"]).unwrap(),
+            Some(false)
+        );
+        assert_eq!(debug_assertions(&["shaders"]).unwrap(), None);
+        assert!(debug_assertions(&["shaders", "--debug-assertions", "false"]).is_err());
+        assert!(debug_assertions(&["shaders", "out.bin", "--debug-assertions", "false"]).is_err());
+    }
 }
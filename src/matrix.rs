@@ -0,0 +1,280 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use futures_lite::future;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    cache,
+    cancel::CancelToken,
+    cargo,
+    run_manifest::{ManifestArtifact, RunManifest},
+    BuildError, ShaderBuilder,
+};
+
+/// A matrix config file, expanded into one build per combination of its axes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MatrixConfig {
+    /// Shader crate to build, relative to the config file.
+    #[serde(rename = "crate")]
+    pub path_to_crate: PathBuf,
+    /// Output path template, relative to the config file.
+    ///
+    /// Should use `{target}`, `{profile}` and `{features}` to keep jobs from overwriting each other.
+    pub output: PathBuf,
+    #[serde(default = "MatrixConfig::default_targets")]
+    pub targets: Vec<String>,
+    /// `dev` and/or `release`.
+    #[serde(default = "MatrixConfig::default_profiles")]
+    pub profiles: Vec<String>,
+    /// Sets of rust-gpu target features, i.e. SPIR-V capabilities, to enable per job.
+    #[serde(default = "MatrixConfig::default_features")]
+    pub features: Vec<Vec<String>>,
+    /// Additional build arguments passed to every job.
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl MatrixConfig {
+    fn default_targets() -> Vec<String> {
+        vec!["spirv-unknown-vulkan1.2".to_string()]
+    }
+
+    fn default_profiles() -> Vec<String> {
+        vec!["dev".to_string()]
+    }
+
+    fn default_features() -> Vec<Vec<String>> {
+        vec![vec![]]
+    }
+
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let config = std::fs::read(path)?;
+        let config: MatrixConfig = serde_json::from_slice(&config)
+            .map_err(|e| format!("Invalid matrix config {path:?}: {e}"))?;
+
+        if let Some(profile) = config
+            .profiles
+            .iter()
+            .find(|profile| !matches!(profile.as_str(), "dev" | "release"))
+        {
+            return Err(format!("Unknown profile {profile:?}, expected dev or release").into());
+        }
+        // Every job would overwrite the others' manifest
+        if config
+            .args
+            .iter()
+            .any(|arg| arg == "--run-manifest" || arg.starts_with("--run-manifest="))
+        {
+            return Err(
+                "Pass --run-manifest to the matrix command, not in the config's args".into(),
+            );
+        }
+
+        Ok(config)
+    }
+
+    /// Whether the additional build arguments choose the number of cargo jobs.
+    fn sets_cargo_jobs(&self) -> bool {
+        self.args
+            .iter()
+            .any(|arg| arg == "--cargo-jobs" || arg.starts_with("--cargo-jobs="))
+    }
+
+    /// Every combination of target, profile and feature set.
+    pub fn jobs(&self) -> Vec<Job> {
+        let mut jobs = vec![];
+        for target in &self.targets {
+            for profile in &self.profiles {
+                for features in &self.features {
+                    jobs.push(Job {
+                        target: target.clone(),
+                        profile: profile.clone(),
+                        features: features.clone(),
+                    });
+                }
+            }
+        }
+        jobs
+    }
+}
+
+/// One coordinate of the matrix.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Job {
+    pub target: String,
+    pub profile: String,
+    pub features: Vec<String>,
+}
+
+impl Job {
+    /// Substituted for `{features}` in the output path, `default` when no features are enabled.
+    fn features_key(&self) -> String {
+        if self.features.is_empty() {
+            "default".to_string()
+        } else {
+            self.features.join("+")
+        }
+    }
+
+    /// Build arguments for this job, as they would be passed on the command line,
+    /// compiling with `cargo_jobs` unless the config's arguments say otherwise.
+    fn args(&self, config: &MatrixConfig, config_dir: &Path, cargo_jobs: usize) -> Vec<OsString> {
+        let output = config
+            .output
+            .to_string_lossy()
+            .replace("{features}", &self.features_key());
+
+        let mut args: Vec<OsString> = vec![
+            config_dir.join(&config.path_to_crate).into(),
+            config_dir.join(output).into(),
+            "--target".into(),
+            self.target.clone().into(),
+        ];
+        if self.profile == "release" {
+            args.push("--release".into());
+        }
+        for feature in &self.features {
+            args.push("--capability".into());
+            args.push(feature.into());
+        }
+        if !config.sets_cargo_jobs() {
+            args.push("--cargo-jobs".into());
+            args.push(cargo_jobs.to_string().into());
+        }
+        args.extend(config.args.iter().map(OsString::from));
+        args
+    }
+}
+
+/// Outcome of a single job, as recorded in the manifest.
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestEntry {
+    #[serde(flatten)]
+    pub job: Job,
+    /// Path of the written artifact, if the job succeeded.
+    pub artifact: Option<PathBuf>,
+    pub artifact_hash: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Build and write the output of a single job, compiling in `target_dir`.
+fn run_job(
+    args: Vec<OsString>,
+    target_dir: Option<PathBuf>,
+    cancel: &CancelToken,
+) -> Result<ManifestArtifact, BuildError> {
+    let mut builder =
+        ShaderBuilder::from_args(args).map_err(|e| BuildError::Worker(e.to_string()))?;
+    builder.target_dir = target_dir;
+
+    let mut compiled = builder.build_shader(cancel)?;
+    let artifact = future::block_on(crate::handle_compile_result(
+        compiled.result,
+        &mut compiled.timings,
+        builder,
+        None,
+    ))?
+    .expect("Matrix jobs always have an output path");
+    Ok(artifact)
+}
+
+/// Run every job of the matrix in `config_path`, `jobs` at a time,
+/// returning a manifest entry for each in matrix order.
+///
+/// Artifacts are recorded in `run_manifest` as their jobs finish.
+///
+/// Each concurrent slot compiles into its own target directory, as jobs sharing one would
+/// take turns on cargo's lock and rebuild what the previous job built with other features.
+pub fn run(
+    config_path: &Path,
+    jobs: usize,
+    run_manifest: Option<&RunManifest>,
+    cancel: &CancelToken,
+) -> Result<Vec<ManifestEntry>, Box<dyn Error>> {
+    let config = MatrixConfig::read(config_path)?;
+    let config_dir = config_path.parent().unwrap_or(Path::new(""));
+
+    let matrix = config.jobs();
+    // Split cargo's jobs between the concurrent builds, so they don't oversubscribe the machine
+    let jobs = jobs.max(1);
+    let cargo_jobs = (cargo::default_jobs() / jobs).max(1);
+    if config.sets_cargo_jobs() {
+        info!("Building {} matrix job(s), {jobs} at a time", matrix.len());
+    } else {
+        info!(
+            "Building {} matrix job(s), {jobs} at a time with {cargo_jobs} cargo job(s) each",
+            matrix.len()
+        );
+    }
+
+    let queue = Mutex::new(matrix.iter().enumerate().collect::<VecDeque<_>>());
+    let entries = Mutex::new(vec![None; matrix.len()]);
+
+    std::thread::scope(|scope| {
+        for slot in 0..jobs {
+            let (queue, entries, config) = (&queue, &entries, &config);
+            let target_dir = cache::matrix_target_dir(slot);
+            scope.spawn(move || loop {
+                let Some((i, job)) = queue.lock().unwrap().pop_front() else {
+                    break
+                };
+                if cancel.is_cancelled() {
+                    break;
+                }
+
+                let coordinates = format!("{} {} {}", job.target, job.profile, job.features_key());
+                info!("Building {coordinates}...");
+
+                let result = run_job(
+                    job.args(config, config_dir, cargo_jobs),
+                    target_dir.clone(),
+                    cancel,
+                );
+                let entry = match result {
+                    Ok(artifact) => {
+                        info!("Built {coordinates}");
+                        let entry = ManifestEntry {
+                            job: job.clone(),
+                            artifact: Some(artifact.path.clone()),
+                            artifact_hash: Some(artifact.hash.clone()),
+                            error: None,
+                        };
+                        if let Some(run_manifest) = run_manifest {
+                            run_manifest.record(artifact);
+                        }
+                        entry
+                    }
+                    Err(e) => {
+                        error!("Failed to build {coordinates}\n{e:}");
+                        ManifestEntry {
+                            job: job.clone(),
+                            artifact: None,
+                            artifact_hash: None,
+                            error: Some(e.to_string()),
+                        }
+                    }
+                };
+                entries.lock().unwrap()[i] = Some(entry);
+            });
+        }
+    });
+
+    if cancel.is_cancelled() {
+        return Err("Matrix build cancelled".into());
+    }
+
+    Ok(entries
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect())
+}
@@ -0,0 +1,79 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::Deserialize;
+
+use crate::paths;
+
+/// The subset of `cargo metadata` output used by the builder.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Metadata {
+    pub packages: Vec<Package>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub manifest_path: PathBuf,
+    /// `None` for local (path or workspace) packages.
+    pub source: Option<String>,
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Dependency {
+    /// Set for path dependencies.
+    pub path: Option<PathBuf>,
+}
+
+impl Package {
+    /// Directory containing the package's `Cargo.toml`.
+    pub fn dir(&self) -> &Path {
+        self.manifest_path.parent().unwrap()
+    }
+}
+
+impl Metadata {
+    /// Local packages, i.e. those built from a path rather than a registry or git source.
+    pub fn local_packages(&self) -> impl Iterator<Item = &Package> {
+        self.packages
+            .iter()
+            .filter(|package| package.source.is_none())
+    }
+
+    /// The local package named `name`.
+    pub fn local_package(&self, name: &str) -> Option<&Package> {
+        self.local_packages().find(|package| package.name == name)
+    }
+
+    /// The local package whose manifest lives in `dir`.
+    pub fn package_in(&self, dir: &Path) -> Option<&Package> {
+        let dir = paths::canonicalize(dir).ok()?;
+        self.local_packages().find(|package| {
+            paths::canonicalize(package.dir())
+                .map_or(false, |package_dir| paths::same(&package_dir, &dir))
+        })
+    }
+}
+
+/// Run `cargo metadata` for the crate at `path_to_crate`.
+pub fn metadata(path_to_crate: &Path) -> Result<Metadata, Box<dyn Error>> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--format-version", "1"])
+        .current_dir(path_to_crate)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "cargo metadata failed for {path_to_crate:?}:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        )
+        .into());
+    }
+
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
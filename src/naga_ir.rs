@@ -0,0 +1,19 @@
+//! naga's parsed representation of a module, for analysis tools that would
+//! rather walk its IR than parse SPIR-V themselves.
+
+use ron::ser::PrettyConfig;
+
+/// Parse a module with naga, and serialize the resulting IR as RON.
+pub fn to_ron(module: &[u8]) -> Result<String, String> {
+    let options = naga::front::spv::Options {
+        adjust_coordinate_space: false,
+        // Unlike WGSL output, the IR can represent whatever the module uses
+        strict_capabilities: false,
+        block_ctx_dump_prefix: None,
+    };
+    let module = naga::front::spv::parse_u8_slice(module, &options)
+        .map_err(|e| format!("naga can't read the module: {e}"))?;
+
+    ron::ser::to_string_pretty(&module, PrettyConfig::default())
+        .map_err(|e| format!("Failed to serialize naga IR: {e}"))
+}
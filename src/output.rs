@@ -0,0 +1,492 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use futures_lite::StreamExt;
+use rust_gpu_builder_shared::{RustGpuBuilderModules, RustGpuBuilderOutput};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    build_config::BuildConfig, checksum::ModuleChecksums, compress, kernels::KernelSignature,
+    provenance::Provenance, webgpu::WgslModules,
+};
+
+/// What gets written to the output path.
+///
+/// Serializes as a `RustGpuBuilderOutput` with extra fields,
+/// which consumers deserializing the plain shared type ignore.
+#[derive(Serialize)]
+pub struct Artifact<'a> {
+    pub entry_points: &'a [String],
+    /// Every entry point's module, unless --dedup-modules stored some once.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modules: Option<&'a RustGpuBuilderModules>,
+    /// The distinct modules --dedup-modules kept, named apart from `modules` so consumers
+    /// deserializing the plain shared type fail on it instead of missing the aliased entry points.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deduplicated_modules: Option<&'a RustGpuBuilderModules>,
+    /// Entry point names keyed by stage, with --group-entry-points.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stages: Option<BTreeMap<&'static str, Vec<String>>>,
+    /// Entry point of each stage of the pipelines declared with --pipeline.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pipelines: Option<BTreeMap<String, BTreeMap<&'static str, String>>>,
+    /// WGSL translation of each module, with --preset webgpu.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wgsl: Option<WgslModules>,
+    /// Parameters, bindings and workgroup size of each compute entry point, with OpenCL targets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kernels: Option<BTreeMap<String, KernelSignature>>,
+    /// Git revision of the shader crate, unless built with --no-provenance or outside a repository.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+    /// Options the artifact was built with, left out of bundles like provenance.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config: Option<BuildConfig>,
+    /// With --dedup-modules, the entry point whose module each left-out entry point shares.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_aliases: Option<BTreeMap<String, String>>,
+    /// Lets loaders detect truncated or corrupt modules before handing them to a driver.
+    pub checksums: ModuleChecksums,
+}
+
+/// The builder's own fields of a previously written artifact.
+#[derive(Deserialize)]
+struct ArtifactExtensions {
+    #[serde(default)]
+    entry_points: Vec<String>,
+    deduplicated_modules: Option<RustGpuBuilderModules>,
+    checksums: Option<ModuleChecksums>,
+    /// Missing from artifacts written before configs were recorded.
+    config: Option<BuildConfig>,
+    #[serde(default)]
+    module_aliases: BTreeMap<String, String>,
+}
+
+/// Store each distinct module of a multimodule build once, keeping it under the first entry point
+/// that has it and mapping the others to that one.
+///
+/// Returns `None` if every module is already distinct.
+pub fn dedup_modules(
+    modules: &RustGpuBuilderModules,
+) -> Option<(RustGpuBuilderModules, BTreeMap<String, String>)> {
+    let RustGpuBuilderModules::Multi(multi) = modules else {
+        return None
+    };
+
+    let mut stored = BTreeMap::<String, Vec<u8>>::new();
+    let mut aliases = BTreeMap::new();
+    for (name, module) in multi {
+        match stored.iter().find(|(_, other)| *other == module) {
+            Some((original, _)) => {
+                aliases.insert(name.clone(), original.clone());
+            }
+            None => {
+                stored.insert(name.clone(), module.clone());
+            }
+        }
+    }
+
+    (!aliases.is_empty()).then_some((RustGpuBuilderModules::Multi(stored), aliases))
+}
+
+/// Put back the modules [`dedup_modules`] left out.
+fn resolve_aliases(
+    modules: &mut RustGpuBuilderModules,
+    aliases: &BTreeMap<String, String>,
+) -> Result<(), String> {
+    let RustGpuBuilderModules::Multi(multi) = modules else {
+        return Ok(());
+    };
+    for (alias, original) in aliases {
+        let module = multi
+            .get(original)
+            .cloned()
+            .ok_or_else(|| format!("{alias:?} is stored as {original:?}, which has no module"))?;
+        multi.insert(alias.clone(), module);
+    }
+    Ok(())
+}
+
+/// Hex-encoded content hash of a serialized artifact, truncated to keep filenames readable.
+pub fn artifact_hash(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    digest[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Values substituted into the placeholders of an output path template.
+pub struct TemplateVars<'a> {
+    pub crate_name: &'a str,
+    pub target: &'a str,
+    pub profile: &'a str,
+    pub hash: &'a str,
+}
+
+/// Substitute `{crate}`, `{target}`, `{profile}` and `{hash}` in `template`.
+pub fn expand_template(template: &Path, vars: &TemplateVars) -> PathBuf {
+    let Some(template) = template.to_str() else {
+        return template.to_owned()
+    };
+
+    template
+        .replace("{crate}", vars.crate_name)
+        .replace("{target}", vars.target)
+        .replace("{profile}", vars.profile)
+        .replace("{hash}", vars.hash)
+        .into()
+}
+
+/// Path of the versioned sibling of `output_path` for the given artifact hash.
+///
+/// `shaders/out.json` becomes `shaders/out.<hash>.json`.
+pub fn versioned_path(output_path: &Path, hash: &str) -> PathBuf {
+    let stem = output_path
+        .file_stem()
+        .expect("Output path must have a file name")
+        .to_string_lossy();
+
+    let file_name = match output_path.extension() {
+        Some(extension) => format!("{stem}.{hash}.{}", extension.to_string_lossy()),
+        None => format!("{stem}.{hash}"),
+    };
+
+    output_path.with_file_name(file_name)
+}
+
+/// Write `bytes` to a hash-suffixed sibling of `output_path`,
+/// then atomically re-point `output_path` at it.
+///
+/// On unix `output_path` becomes a relative symlink, elsewhere it is replaced with a copy.
+/// Returns the path of the versioned artifact.
+pub async fn write_versioned(output_path: &Path, bytes: &[u8]) -> std::io::Result<PathBuf> {
+    let versioned_path = versioned_path(output_path, &artifact_hash(bytes));
+    async_fs::write(&versioned_path, bytes).await?;
+    update_latest(output_path, &versioned_path).await?;
+    Ok(versioned_path)
+}
+
+/// Hidden sibling of `output_path` that writes are staged in before being renamed over it.
+fn tmp_path(output_path: &Path) -> PathBuf {
+    let file_name = output_path
+        .file_name()
+        .expect("Output path must have a file name")
+        .to_string_lossy();
+    output_path.with_file_name(format!(".{file_name}.tmp"))
+}
+
+/// Write `bytes` to `output_path` via a rename, so watchers reloading it
+/// never observe a partially written artifact.
+pub async fn write_atomic(output_path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let tmp_path = tmp_path(output_path);
+    async_fs::write(&tmp_path, bytes).await?;
+    async_fs::rename(&tmp_path, output_path).await
+}
+
+/// Atomically re-point `output_path` at the provided versioned artifact.
+pub async fn update_latest(output_path: &Path, versioned_path: &Path) -> std::io::Result<()> {
+    let tmp_path = tmp_path(output_path);
+    async_fs::remove_file(&tmp_path).await.ok();
+
+    #[cfg(unix)]
+    async_fs::unix::symlink(versioned_path.file_name().unwrap(), &tmp_path).await?;
+
+    #[cfg(not(unix))]
+    async_fs::copy(versioned_path, &tmp_path).await?;
+
+    async_fs::rename(&tmp_path, output_path).await
+}
+
+/// Extract the artifact hash from `candidate` if it is a versioned sibling of `output_path`.
+fn version_hash<'a>(output_path: &Path, candidate: &'a Path) -> Option<&'a str> {
+    let stem = output_path.file_stem()?.to_str()?;
+    let file_name = candidate.file_name()?.to_str()?;
+
+    let rest = file_name.strip_prefix(stem)?.strip_prefix('.')?;
+    let hash = match output_path.extension() {
+        Some(extension) => rest.strip_suffix(extension.to_str()?)?.strip_suffix('.')?,
+        None => rest,
+    };
+
+    (hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit())).then_some(hash)
+}
+
+/// Versioned siblings of `output_path`, newest first.
+pub async fn versions(output_path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let dir = match output_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let mut versions = vec![];
+    let mut entries = async_fs::read_dir(dir).await?;
+    while let Some(entry) = entries.try_next().await? {
+        let path = entry.path();
+        if version_hash(output_path, &path).is_some() {
+            let modified = entry.metadata().await?.modified()?;
+            versions.push((modified, path));
+        }
+    }
+
+    versions.sort_by(|(a, _), (b, _)| b.cmp(a));
+    Ok(versions.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Remove all but the newest `keep` versioned siblings of `output_path`,
+/// returning the removed paths.
+pub async fn prune_versions(output_path: &Path, keep: usize) -> std::io::Result<Vec<PathBuf>> {
+    let mut removed = vec![];
+    for path in versions(output_path).await?.into_iter().skip(keep) {
+        async_fs::remove_file(&path).await?;
+        removed.push(path);
+    }
+    Ok(removed)
+}
+
+/// Index into `versions` of the build `output_path` currently points at.
+async fn current_version(output_path: &Path, versions: &[PathBuf]) -> std::io::Result<usize> {
+    let position = match async_fs::read_link(output_path).await {
+        Ok(target) => versions
+            .iter()
+            .position(|version| version.file_name() == target.file_name()),
+        // Not a symlink, so compare contents against each version instead
+        Err(_) => {
+            let current = async_fs::read(output_path).await?;
+            let mut position = None;
+            for (i, version) in versions.iter().enumerate() {
+                if async_fs::read(version).await? == current {
+                    position = Some(i);
+                    break;
+                }
+            }
+            position
+        }
+    };
+
+    position.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{output_path:?} does not point at a versioned build"),
+        )
+    })
+}
+
+/// Re-point `output_path` at the versioned build `steps` older than the current one,
+/// returning its path.
+pub async fn rollback(output_path: &Path, steps: usize) -> std::io::Result<PathBuf> {
+    let versions = versions(output_path).await?;
+    let current = current_version(output_path, &versions).await?;
+
+    let Some(target) = versions.get(current + steps) else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!(
+                "Only {} older build(s) of {output_path:?} are available",
+                versions.len() - current - 1
+            ),
+        ));
+    };
+
+    update_latest(output_path, target).await?;
+    Ok(target.clone())
+}
+
+/// Copy each raw SPIR-V module into `dir` as `<name>.spv`, returning the written paths.
+///
+/// Single module builds are named after the shader crate, multimodule builds after their
+/// entry points, with `::` path separators replaced so every module stays a direct child of `dir`.
+pub async fn write_spv_dir(
+    dir: &Path,
+    modules: &RustGpuBuilderModules,
+    crate_name: &str,
+) -> std::io::Result<Vec<PathBuf>> {
+    async_fs::create_dir_all(dir).await?;
+
+    let mut written = vec![];
+    for (name, module) in crate::reflect::named_modules(modules, crate_name) {
+        let path = dir.join(format!("{}.spv", name.replace("::", "-")));
+        async_fs::write(&path, module).await?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Deserialize an artifact, or part of one, in either output format, compressed or not.
+fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Box<dyn Error>> {
+    if compress::is_compressed(bytes) {
+        return deserialize(&compress::decompress(bytes, None)?);
+    }
+
+    // Messagepack output starts with a map header byte, never `{`
+    if bytes.first() == Some(&b'{') {
+        Ok(serde_json::from_slice(bytes)?)
+    } else {
+        Ok(rmp_serde::from_slice(bytes)?)
+    }
+}
+
+/// Read a previously written artifact, in either output format,
+/// verifying its modules against any embedded checksums.
+///
+/// Modules stored once under --dedup-modules are restored under every entry point sharing them.
+pub async fn read_artifact(path: &Path) -> Result<RustGpuBuilderOutput, Box<dyn Error>> {
+    read_artifact_with_dictionary(path, None).await
+}
+
+/// Like [`read_artifact`], decompressing with the zstd `dictionary` it may have been written with.
+pub async fn read_artifact_with_dictionary(
+    path: &Path,
+    dictionary: Option<&[u8]>,
+) -> Result<RustGpuBuilderOutput, Box<dyn Error>> {
+    let mut bytes = async_fs::read(path).await?;
+    if compress::is_compressed(&bytes) {
+        bytes = compress::decompress(&bytes, dictionary)?;
+    }
+    let extensions: ArtifactExtensions = deserialize(&bytes)?;
+    let mut output = match extensions.deduplicated_modules {
+        Some(modules) => RustGpuBuilderOutput {
+            entry_points: extensions.entry_points,
+            modules,
+        },
+        None => deserialize(&bytes)?,
+    };
+
+    if let Some(checksums) = extensions.checksums {
+        checksums
+            .verify(&output.modules)
+            .map_err(|e| format!("{path:?} is corrupt: {e}"))?;
+    }
+    resolve_aliases(&mut output.modules, &extensions.module_aliases)
+        .map_err(|e| format!("{path:?} is corrupt: {e}"))?;
+
+    Ok(output)
+}
+
+/// The options a previously written artifact was built with, if it records them.
+pub async fn read_config(
+    path: &Path,
+    dictionary: Option<&[u8]>,
+) -> Result<Option<BuildConfig>, Box<dyn Error>> {
+    let mut bytes = async_fs::read(path).await?;
+    if compress::is_compressed(&bytes) {
+        bytes = compress::decompress(&bytes, dictionary)?;
+    }
+    Ok(deserialize::<ArtifactExtensions>(&bytes)?.config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "0123456789abcdef";
+
+    #[test]
+    fn expands_templates() {
+        let vars = TemplateVars {
+            crate_name: "sky",
+            target: "spirv-unknown-vulkan1.2",
+            profile: "release",
+            hash: HASH,
+        };
+        assert_eq!(
+            expand_template(
+                Path::new("out/{crate}-{target}/{profile}.{hash}.json"),
+                &vars
+            ),
+            PathBuf::from(format!(
+                "out/sky-spirv-unknown-vulkan1.2/release.{HASH}.json"
+            ))
+        );
+        assert_eq!(
+            expand_template(Path::new("out/sky.json"), &vars),
+            PathBuf::from("out/sky.json")
+        );
+    }
+
+    #[test]
+    fn finds_version_hashes() {
+        let output_path = Path::new("shaders/out.json");
+        let versioned = versioned_path(output_path, HASH);
+        assert_eq!(versioned, PathBuf::from(format!("shaders/out.{HASH}.json")));
+        assert_eq!(version_hash(output_path, &versioned), Some(HASH));
+
+        let output_path = Path::new("shaders/out");
+        let versioned = versioned_path(output_path, HASH);
+        assert_eq!(version_hash(output_path, &versioned), Some(HASH));
+    }
+
+    #[test]
+    fn ignores_other_siblings() {
+        let output_path = Path::new("shaders/out.json");
+        for candidate in [
+            "shaders/out.json",
+            "shaders/out.rs",
+            "shaders/out.0123.json",
+            "shaders/out.0123456789abcdeg.json",
+            "shaders/other.0123456789abcdef.json",
+            "shaders/out.0123456789abcdef.msgpack",
+        ] {
+            assert_eq!(
+                version_hash(output_path, Path::new(candidate)),
+                None,
+                "{candidate}"
+            );
+        }
+    }
+
+    #[test]
+    fn dedups_identical_modules() {
+        let modules = RustGpuBuilderModules::Multi(
+            [("a", vec![1]), ("b", vec![1]), ("c", vec![2])]
+                .map(|(name, module)| (name.to_string(), module))
+                .into(),
+        );
+        let (mut stored, aliases) = dedup_modules(&modules).unwrap();
+        assert_eq!(aliases, [("b".to_string(), "a".to_string())].into());
+
+        resolve_aliases(&mut stored, &aliases).unwrap();
+        assert_eq!(stored, modules);
+    }
+
+    #[test]
+    fn reads_artifacts_compressed_with_a_dictionary() {
+        let artifact = |i: usize| {
+            let output = RustGpuBuilderOutput {
+                entry_points: vec![format!("main_fs_{i}"), format!("main_vs_{i}")],
+                modules: RustGpuBuilderModules::Single((0..256).map(|b| (b * i) as u8).collect()),
+            };
+            serde_json::to_vec(&output).unwrap()
+        };
+        let samples: Vec<_> = (0..128).map(artifact).collect();
+        let dictionary = zstd::dict::from_samples(&samples, 4096).unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "rust-gpu-builder-test-dictionary-{}.json.zst",
+            std::process::id()
+        ));
+        let compressed = compress::compress(&artifact(7), Some(&dictionary)).unwrap();
+        std::fs::write(&path, compressed).unwrap();
+
+        let output =
+            futures_lite::future::block_on(read_artifact_with_dictionary(&path, Some(&dictionary)))
+                .unwrap();
+        assert_eq!(output.entry_points, ["main_fs_7", "main_vs_7"]);
+        assert_eq!(
+            output.modules,
+            RustGpuBuilderModules::Single((0..256).map(|b| (b * 7) as u8).collect())
+        );
+        assert!(futures_lite::future::block_on(read_artifact(&path)).is_err());
+        assert!(
+            futures_lite::future::block_on(read_config(&path, Some(&dictionary)))
+                .unwrap()
+                .is_none()
+        );
+
+        std::fs::remove_file(path).ok();
+    }
+}
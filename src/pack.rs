@@ -0,0 +1,152 @@
+//! Packed artifact container, for shipping shaders to platforms that memory-map their assets.
+//!
+//! All integers are little-endian:
+//!
+//! ```text
+//! 0   magic       b"RGBPACK\0"
+//! 8   version     u32
+//! 12  modules     u32, number of index entries
+//! 16  metadata    u32, length of the metadata header in bytes
+//! 20  reserved    u32
+//! 24  index       `modules` entries of { offset: u64, len: u64 }
+//! ..  metadata    JSON, `{ "entry_points": [..], "modules": [..], "stages": {..} }`
+//! ..  data        raw SPIR-V modules, each starting on an 8 byte boundary
+//! ```
+//!
+//! `modules` in the metadata names each index entry in order,
+//! so a loader can locate any module without touching the others.
+
+use std::collections::BTreeMap;
+
+use rust_gpu_builder_shared::RustGpuBuilderOutput;
+use serde::Serialize;
+
+pub const MAGIC: &[u8; 8] = b"RGBPACK\0";
+pub const VERSION: u32 = 1;
+
+/// Size of the fixed header preceding the index.
+const HEADER_LEN: usize = 24;
+/// Size of a single index entry.
+const INDEX_ENTRY_LEN: usize = 16;
+/// Modules are aligned so they can be read as `u32` words straight out of a mapping.
+const ALIGN: usize = 8;
+
+#[derive(Serialize)]
+struct Metadata<'a> {
+    entry_points: &'a [String],
+    modules: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stages: Option<&'a BTreeMap<&'static str, Vec<String>>>,
+}
+
+fn align(len: usize) -> usize {
+    (len + ALIGN - 1) / ALIGN * ALIGN
+}
+
+/// Encode `output` as a pack, naming a single module after `crate_name`.
+pub fn encode(
+    output: &RustGpuBuilderOutput,
+    crate_name: &str,
+    stages: Option<&BTreeMap<&'static str, Vec<String>>>,
+) -> Vec<u8> {
+    let modules = crate::reflect::named_modules(&output.modules, crate_name);
+
+    let metadata = serde_json::to_vec(&Metadata {
+        entry_points: &output.entry_points,
+        modules: modules.iter().map(|(name, _)| name.as_str()).collect(),
+        stages,
+    })
+    .expect("Failed to serialize pack metadata");
+
+    let mut bytes = Vec::with_capacity(
+        HEADER_LEN
+            + INDEX_ENTRY_LEN * modules.len()
+            + metadata.len()
+            + modules
+                .iter()
+                .map(|(_, module)| align(module.len()) + ALIGN)
+                .sum::<usize>(),
+    );
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(modules.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+
+    let mut offset = align(HEADER_LEN + INDEX_ENTRY_LEN * modules.len() + metadata.len());
+    for (_, module) in &modules {
+        bytes.extend_from_slice(&(offset as u64).to_le_bytes());
+        bytes.extend_from_slice(&(module.len() as u64).to_le_bytes());
+        offset = align(offset + module.len());
+    }
+
+    bytes.extend_from_slice(&metadata);
+
+    for (_, module) in &modules {
+        bytes.resize(align(bytes.len()), 0);
+        bytes.extend_from_slice(module);
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_gpu_builder_shared::RustGpuBuilderModules;
+
+    use super::*;
+
+    fn u32_at(bytes: &[u8], at: usize) -> usize {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize
+    }
+
+    fn u64_at(bytes: &[u8], at: usize) -> usize {
+        u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap()) as usize
+    }
+
+    #[test]
+    fn lays_out_aligned_modules_behind_the_index() {
+        // Odd lengths, so every module after the first needs padding
+        let modules = [
+            ("main_cs", vec![1; 13]),
+            ("main_fs", vec![2; 5]),
+            ("main_vs", vec![3; 8]),
+        ];
+        let output = RustGpuBuilderOutput {
+            entry_points: modules.iter().map(|(name, _)| name.to_string()).collect(),
+            modules: RustGpuBuilderModules::Multi(
+                modules
+                    .iter()
+                    .map(|(name, module)| (name.to_string(), module.clone()))
+                    .collect(),
+            ),
+        };
+        let bytes = encode(&output, "shaders", None);
+
+        assert_eq!(&bytes[..8], MAGIC);
+        assert_eq!(u32_at(&bytes, 8), VERSION as usize);
+        assert_eq!(u32_at(&bytes, 12), modules.len());
+        assert_eq!(u32_at(&bytes, 20), 0);
+
+        let metadata_start = HEADER_LEN + INDEX_ENTRY_LEN * modules.len();
+        let metadata_end = metadata_start + u32_at(&bytes, 16);
+        let metadata: serde_json::Value =
+            serde_json::from_slice(&bytes[metadata_start..metadata_end]).unwrap();
+        assert_eq!(
+            metadata["modules"],
+            serde_json::json!(["main_cs", "main_fs", "main_vs"])
+        );
+
+        let mut previous_end = metadata_end;
+        for (i, (_, module)) in modules.iter().enumerate() {
+            let offset = u64_at(&bytes, HEADER_LEN + INDEX_ENTRY_LEN * i);
+            let len = u64_at(&bytes, HEADER_LEN + INDEX_ENTRY_LEN * i + 8);
+            assert_eq!(offset % ALIGN, 0);
+            assert!(offset >= previous_end);
+            assert_eq!(len, module.len());
+            assert_eq!(&bytes[offset..offset + len], &module[..]);
+            previous_end = offset + len;
+        }
+        assert_eq!(bytes.len(), previous_end);
+    }
+}
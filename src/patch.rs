@@ -0,0 +1,180 @@
+//! Binary patches between consecutive artifacts, so remote hot-reload clients can fetch
+//! a small delta instead of the whole file after every rebuild.
+//!
+//! All integers are little-endian:
+//!
+//! ```text
+//! 0   magic     b"RGBPATCH"
+//! 8   old_len   u64
+//! 16  old_hash  SHA-256 of the artifact the patch applies to
+//! 48  new_len   u64
+//! 56  new_hash  SHA-256 of the artifact the patch produces
+//! 88  ops       until the end of the patch, each one of
+//!               0u8, offset: u64, len: u64   copy `len` bytes of the old artifact from `offset`
+//!               1u8, len: u64, bytes         insert `len` literal bytes
+//! ```
+
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
+
+pub const MAGIC: &[u8; 8] = b"RGBPATCH";
+
+/// Matches shorter than this are inserted literally, as a copy op would be larger.
+const BLOCK: usize = 32;
+
+const COPY: u8 = 0;
+const INSERT: u8 = 1;
+
+/// Encode the changes turning `old` into `new`.
+pub fn diff(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut patch = Vec::with_capacity(88);
+    patch.extend_from_slice(MAGIC);
+    patch.extend_from_slice(&(old.len() as u64).to_le_bytes());
+    patch.extend_from_slice(&Sha256::digest(old));
+    patch.extend_from_slice(&(new.len() as u64).to_le_bytes());
+    patch.extend_from_slice(&Sha256::digest(new));
+
+    // Offsets of block-aligned chunks of the old artifact, keyed by their contents
+    let mut blocks = HashMap::<&[u8], usize>::new();
+    for (i, block) in old.chunks_exact(BLOCK).enumerate() {
+        blocks.entry(block).or_insert(i * BLOCK);
+    }
+
+    let mut literal = vec![];
+    let mut i = 0;
+    while i < new.len() {
+        let found = new
+            .get(i..i + BLOCK)
+            .and_then(|block| blocks.get(block).copied());
+
+        let Some(offset) = found else {
+            literal.push(new[i]);
+            i += 1;
+            continue
+        };
+
+        let len = old[offset..]
+            .iter()
+            .zip(&new[i..])
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        flush_literal(&mut patch, &mut literal);
+        patch.push(COPY);
+        patch.extend_from_slice(&(offset as u64).to_le_bytes());
+        patch.extend_from_slice(&(len as u64).to_le_bytes());
+        i += len;
+    }
+    flush_literal(&mut patch, &mut literal);
+
+    patch
+}
+
+fn flush_literal(patch: &mut Vec<u8>, literal: &mut Vec<u8>) {
+    if literal.is_empty() {
+        return;
+    }
+    patch.push(INSERT);
+    patch.extend_from_slice(&(literal.len() as u64).to_le_bytes());
+    patch.append(literal);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reference decoder, as a hot-reload client would apply a patch to its copy of `old`.
+    fn apply(old: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+        let u64_at = |at: usize| {
+            patch
+                .get(at..at + 8)
+                .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()) as usize)
+                .ok_or("Truncated patch")
+        };
+
+        if patch.get(..8) != Some(MAGIC) {
+            return Err("Not a patch".into());
+        }
+        if u64_at(8)? != old.len() || patch.get(16..48) != Some(&Sha256::digest(old)[..]) {
+            return Err("The patch applies to a different artifact".into());
+        }
+
+        let mut new = Vec::with_capacity(u64_at(48)?);
+        let mut i = 88;
+        while i < patch.len() {
+            match patch[i] {
+                COPY => {
+                    let (offset, len) = (u64_at(i + 1)?, u64_at(i + 9)?);
+                    new.extend_from_slice(
+                        old.get(offset..offset + len).ok_or("Copy out of range")?,
+                    );
+                    i += 17;
+                }
+                INSERT => {
+                    let len = u64_at(i + 1)?;
+                    new.extend_from_slice(patch.get(i + 9..i + 9 + len).ok_or("Truncated patch")?);
+                    i += 9 + len;
+                }
+                op => return Err(format!("Unknown op {op}")),
+            }
+        }
+
+        if new.len() != u64_at(48)? || patch.get(56..88) != Some(&Sha256::digest(&new)[..]) {
+            return Err("The patched artifact doesn't match".into());
+        }
+        Ok(new)
+    }
+
+    fn artifact(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    fn round_trip(old: &[u8], new: &[u8]) -> Vec<u8> {
+        let patch = diff(old, new);
+        assert_eq!(apply(old, &patch).unwrap(), new);
+        patch
+    }
+
+    #[test]
+    fn copies_identical_artifacts_whole() {
+        let old = artifact(BLOCK * 64);
+        let patch = round_trip(&old, &old);
+        assert_eq!(patch.len(), 88 + 17);
+    }
+
+    #[test]
+    fn inserts_everything_into_an_empty_artifact() {
+        round_trip(&[], &artifact(BLOCK * 4));
+        round_trip(&artifact(BLOCK * 4), &[]);
+    }
+
+    #[test]
+    fn copies_around_an_insertion_in_the_middle() {
+        let old = artifact(BLOCK * 64);
+        let mut new = old.clone();
+        new.splice(BLOCK * 20 + 5..BLOCK * 20 + 5, *b"inserted");
+        let patch = round_trip(&old, &new);
+        assert!(patch.len() < old.len() / 4);
+    }
+
+    #[test]
+    fn handles_lengths_off_the_block_size() {
+        let old = artifact(BLOCK * 10 + 13);
+        let mut new = artifact(BLOCK * 12 + 3);
+        new[BLOCK * 3] ^= 0xff;
+        round_trip(&old, &new);
+        round_trip(&new, &old);
+    }
+
+    #[test]
+    fn rejects_a_different_base() {
+        let old = artifact(BLOCK * 8);
+        let patch = diff(&old, &artifact(BLOCK * 9));
+
+        let mut other = old.clone();
+        other[0] ^= 0xff;
+        assert!(apply(&other, &patch).is_err());
+        assert!(apply(&old[1..], &patch).is_err());
+    }
+}
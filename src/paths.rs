@@ -0,0 +1,137 @@
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+/// Canonicalize a path, avoiding the `\\?\` verbatim form on Windows
+/// since watcher events and user-supplied paths never use it.
+pub fn canonicalize(path: impl AsRef<Path>) -> io::Result<PathBuf> {
+    dunce::canonicalize(path)
+}
+
+/// Whether two path components name the same entry.
+///
+/// Windows filesystems are case-insensitive, so events may not match the case of the watched path.
+fn same_component(a: Component, b: Component) -> bool {
+    if cfg!(windows) {
+        a.as_os_str()
+            .to_string_lossy()
+            .to_lowercase()
+            .eq(&b.as_os_str().to_string_lossy().to_lowercase())
+    } else {
+        a == b
+    }
+}
+
+/// Whether `a` and `b` are the same path.
+pub fn same(a: &Path, b: &Path) -> bool {
+    a.components().count() == b.components().count() && starts_with(a, b)
+}
+
+/// Whether `path` is `base` or lies inside it.
+pub fn starts_with(path: &Path, base: &Path) -> bool {
+    let mut path = path.components();
+    base.components().all(|base| {
+        path.next()
+            .map_or(false, |component| same_component(component, base))
+    })
+}
+
+/// `path` made absolute against the current directory, without resolving symlinks.
+pub fn absolute(path: &Path) -> io::Result<PathBuf> {
+    Ok(std::env::current_dir()?.join(path))
+}
+
+/// The directory containing `path`, which is `.` for bare file names.
+pub fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// `dir` or its closest ancestor that exists, i.e. where creating `dir` would start.
+pub fn nearest_existing(dir: &Path) -> Option<&Path> {
+    dir.ancestors()
+        .find(|dir| !dir.as_os_str().is_empty() && dir.is_dir())
+}
+
+/// Check files can be created in `dir`, by creating and removing one.
+pub fn probe_writable(dir: &Path) -> io::Result<()> {
+    let probe = dir.join(format!(".rust-gpu-builder-probe-{}", std::process::id()));
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(&probe)
+}
+
+/// Canonical targets of symlinks found anywhere below `root` that point outside of it,
+/// including links found inside those targets.
+pub fn symlink_targets(root: &Path) -> Vec<PathBuf> {
+    let mut targets: Vec<PathBuf> = vec![];
+    let mut pending = vec![root.to_owned()];
+
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue
+        };
+
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue
+            };
+
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_symlink() {
+                let Ok(target) = canonicalize(entry.path()) else {
+                    continue
+                };
+
+                // Skip links back into anything already covered, which also avoids cycles
+                let covered = starts_with(&target, root)
+                    || targets.iter().any(|known| starts_with(&target, known));
+                if covered {
+                    continue;
+                }
+
+                if target.is_dir() {
+                    pending.push(target.clone());
+                }
+                targets.push(target);
+            }
+        }
+    }
+
+    targets
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_linked_files_and_directories_outside_the_root() {
+        let root = std::env::temp_dir().join(format!(
+            "rust-gpu-builder-test-symlink-targets-{}",
+            std::process::id()
+        ));
+        std::fs::remove_dir_all(&root).ok();
+        std::fs::create_dir_all(root.join("w/src")).unwrap();
+        std::fs::create_dir_all(root.join("outside/textures")).unwrap();
+        std::fs::write(root.join("outside/lut.bin"), b"lut").unwrap();
+        std::os::unix::fs::symlink("../outside/lut.bin", root.join("w/lut.bin")).unwrap();
+        std::os::unix::fs::symlink("../../outside/textures", root.join("w/src/textures")).unwrap();
+        // Links within the root or into a target already found add nothing
+        std::os::unix::fs::symlink("src", root.join("w/again")).unwrap();
+        std::os::unix::fs::symlink("../lut.bin", root.join("outside/textures/lut.bin")).unwrap();
+
+        let root = canonicalize(root).unwrap();
+        let mut targets = symlink_targets(&root.join("w"));
+        targets.sort();
+        assert_eq!(
+            targets,
+            [root.join("outside/lut.bin"), root.join("outside/textures")]
+        );
+
+        std::fs::remove_dir_all(root).ok();
+    }
+}
@@ -0,0 +1,292 @@
+//! Vulkan pipeline cache priming, so the first run on a known device doesn't stall compiling pipelines.
+//!
+//! Only compute pipelines are primed: graphics pipelines also depend on vertex input, render pass
+//! and blend state that only the application knows.
+
+use std::{collections::BTreeMap, error::Error, ffi::CString, path::Path, path::PathBuf};
+
+use ash::vk;
+use rspirv::{
+    dr::{Module, Operand},
+    spirv::{Decoration, Dim, ExecutionModel, Op, StorageClass},
+};
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+use tracing::warn;
+
+use crate::reflect;
+
+/// Descriptor type and count of a binding, as a pipeline layout needs to declare it.
+fn descriptor(module: &Module, variable: u32) -> Option<(vk::DescriptorType, u32)> {
+    let pointer = reflect::global(module, variable)?.result_type?;
+    let (class, mut pointee) = match reflect::global(module, pointer)?.operands.as_slice() {
+        [Operand::StorageClass(class), Operand::IdRef(pointee)] => (*class, *pointee),
+        _ => return None,
+    };
+
+    let mut count = 1;
+    let mut inst = reflect::global(module, pointee)?;
+    match (inst.class.opcode, inst.operands.as_slice()) {
+        (Op::TypeArray, [Operand::IdRef(element), Operand::IdRef(length)]) => {
+            if let Some(Operand::LiteralInt32(length)) =
+                reflect::global(module, *length).and_then(|inst| inst.operands.first())
+            {
+                count = *length;
+            }
+            pointee = *element;
+            inst = reflect::global(module, pointee)?;
+        }
+        (Op::TypeRuntimeArray, [Operand::IdRef(element)]) => {
+            pointee = *element;
+            inst = reflect::global(module, pointee)?;
+        }
+        _ => (),
+    }
+
+    let ty = match (class, inst.class.opcode) {
+        (StorageClass::StorageBuffer, _) => vk::DescriptorType::STORAGE_BUFFER,
+        (StorageClass::Uniform, _) => {
+            if reflect::has_decoration(module, pointee, Decoration::BufferBlock) {
+                vk::DescriptorType::STORAGE_BUFFER
+            } else {
+                vk::DescriptorType::UNIFORM_BUFFER
+            }
+        }
+        (StorageClass::UniformConstant, Op::TypeSampler) => vk::DescriptorType::SAMPLER,
+        (StorageClass::UniformConstant, Op::TypeSampledImage) => {
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+        }
+        (StorageClass::UniformConstant, Op::TypeImage) => {
+            let dim = inst.operands.get(1);
+            let storage = matches!(inst.operands.get(5), Some(Operand::LiteralInt32(2)));
+            match (dim, storage) {
+                (Some(Operand::Dim(Dim::DimBuffer)), true) => {
+                    vk::DescriptorType::STORAGE_TEXEL_BUFFER
+                }
+                (Some(Operand::Dim(Dim::DimBuffer)), false) => {
+                    vk::DescriptorType::UNIFORM_TEXEL_BUFFER
+                }
+                (Some(Operand::Dim(Dim::DimSubpassData)), _) => {
+                    vk::DescriptorType::INPUT_ATTACHMENT
+                }
+                (_, true) => vk::DescriptorType::STORAGE_IMAGE,
+                (_, false) => vk::DescriptorType::SAMPLED_IMAGE,
+            }
+        }
+        (StorageClass::UniformConstant, Op::TypeAccelerationStructureKHR) => {
+            vk::DescriptorType::ACCELERATION_STRUCTURE_KHR
+        }
+        _ => return None,
+    };
+
+    Some((ty, count))
+}
+
+/// The compute entry points of a module, alongside its descriptor sets.
+struct ComputeModule {
+    words: Vec<u32>,
+    entry_points: Vec<CString>,
+    sets: BTreeMap<u32, Vec<vk::DescriptorSetLayoutBinding>>,
+    push_constants: bool,
+}
+
+impl ComputeModule {
+    fn new(bytes: &[u8]) -> Option<Self> {
+        let module = reflect::parse(bytes).ok()?;
+
+        let entry_points = reflect::entry_points(&module)
+            .into_iter()
+            .filter(|(model, _)| *model == ExecutionModel::GLCompute)
+            .filter_map(|(_, name)| CString::new(name).ok())
+            .collect::<Vec<_>>();
+        if entry_points.is_empty() {
+            return None;
+        }
+
+        let mut sets = BTreeMap::<u32, Vec<_>>::new();
+        for binding in reflect::bindings(&module) {
+            let Some((ty, count)) = descriptor(&module, binding.variable) else {
+                continue
+            };
+            sets.entry(binding.set).or_default().push(
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(binding.binding)
+                    .descriptor_type(ty)
+                    .descriptor_count(count)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build(),
+            );
+        }
+
+        let push_constants = module.types_global_values.iter().any(|inst| {
+            inst.class.opcode == Op::Variable
+                && matches!(
+                    inst.operands.first(),
+                    Some(Operand::StorageClass(StorageClass::PushConstant))
+                )
+        });
+
+        Some(ComputeModule {
+            words: bytes
+                .chunks_exact(4)
+                .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+                .collect(),
+            entry_points,
+            sets,
+            push_constants,
+        })
+    }
+}
+
+/// Create every compute pipeline on `device`, returning the resulting cache data.
+///
+/// # Safety
+/// `device` must be a valid device created from a physical device with the provided limits.
+unsafe fn prime_device(
+    device: &ash::Device,
+    limits: &vk::PhysicalDeviceLimits,
+    modules: &[ComputeModule],
+) -> Result<Vec<u8>, vk::Result> {
+    let cache = device.create_pipeline_cache(&vk::PipelineCacheCreateInfo::default(), None)?;
+
+    for module in modules {
+        let shader = device.create_shader_module(
+            &vk::ShaderModuleCreateInfo::builder().code(&module.words),
+            None,
+        )?;
+
+        // Pipeline layouts need every set up to the highest one, even if unused
+        let highest_set = module.sets.keys().last().map_or(0, |set| set + 1);
+        let mut set_layouts = vec![];
+        for set in 0..highest_set {
+            let bindings = module.sets.get(&set).map(Vec::as_slice).unwrap_or(&[]);
+            set_layouts.push(device.create_descriptor_set_layout(
+                &vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings),
+                None,
+            )?);
+        }
+
+        let push_constant_ranges = [vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            offset: 0,
+            size: limits.max_push_constants_size,
+        }];
+        let layout = device.create_pipeline_layout(
+            &vk::PipelineLayoutCreateInfo::builder()
+                .set_layouts(&set_layouts)
+                .push_constant_ranges(if module.push_constants {
+                    &push_constant_ranges
+                } else {
+                    &[]
+                }),
+            None,
+        )?;
+
+        for entry_point in &module.entry_points {
+            let info = vk::ComputePipelineCreateInfo::builder()
+                .stage(
+                    vk::PipelineShaderStageCreateInfo::builder()
+                        .stage(vk::ShaderStageFlags::COMPUTE)
+                        .module(shader)
+                        .name(entry_point)
+                        .build(),
+                )
+                .layout(layout)
+                .build();
+
+            match device.create_compute_pipelines(cache, &[info], None) {
+                Ok(pipelines) => {
+                    for pipeline in pipelines {
+                        device.destroy_pipeline(pipeline, None);
+                    }
+                }
+                Err((_, e)) => warn!("Failed to prime pipeline {entry_point:?}: {e}"),
+            }
+        }
+
+        device.destroy_pipeline_layout(layout, None);
+        for set_layout in set_layouts {
+            device.destroy_descriptor_set_layout(set_layout, None);
+        }
+        device.destroy_shader_module(shader, None);
+    }
+
+    let data = device.get_pipeline_cache_data(cache);
+    device.destroy_pipeline_cache(cache, None);
+    data
+}
+
+/// Path of the pipeline cache for the given device, next to `output_path`.
+///
+/// `shaders/out.bin` becomes `shaders/out.10de-2684.vkpipelinecache`.
+fn cache_path(output_path: &Path, properties: &vk::PhysicalDeviceProperties) -> PathBuf {
+    output_path.with_extension(format!(
+        "{:04x}-{:04x}.vkpipelinecache",
+        properties.vendor_id, properties.device_id
+    ))
+}
+
+/// Create each compute pipeline in `modules` on every Vulkan device present,
+/// writing a pipeline cache next to `output_path` per device.
+///
+/// Returns the written paths.
+pub fn prime(
+    modules: &RustGpuBuilderModules,
+    output_path: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let modules = reflect::named_modules(modules, "")
+        .into_iter()
+        .filter_map(|(_, bytes)| ComputeModule::new(bytes))
+        .collect::<Vec<_>>();
+    if modules.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let entry = unsafe { ash::Entry::load() }
+        .map_err(|e| format!("Failed to load the Vulkan loader: {e}"))?;
+
+    let app_info = vk::ApplicationInfo::builder().api_version(vk::API_VERSION_1_1);
+    let instance = unsafe {
+        entry.create_instance(
+            &vk::InstanceCreateInfo::builder().application_info(&app_info),
+            None,
+        )
+    }?;
+
+    let mut written = vec![];
+    let result = (|| -> Result<(), Box<dyn Error>> {
+        for physical_device in unsafe { instance.enumerate_physical_devices() }? {
+            let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+
+            let Some(queue_family) =
+                unsafe { instance.get_physical_device_queue_family_properties(physical_device) }
+                    .iter()
+                    .position(|family| family.queue_flags.contains(vk::QueueFlags::COMPUTE)) else {
+                continue
+            };
+
+            let priorities = [1.0];
+            let queue_info = vk::DeviceQueueCreateInfo::builder()
+                .queue_family_index(queue_family as u32)
+                .queue_priorities(&priorities)
+                .build();
+            let device = unsafe {
+                instance.create_device(
+                    physical_device,
+                    &vk::DeviceCreateInfo::builder().queue_create_infos(&[queue_info]),
+                    None,
+                )
+            }?;
+
+            let data = unsafe { prime_device(&device, &properties.limits, &modules) };
+            unsafe { device.destroy_device(None) };
+
+            let path = cache_path(output_path, &properties);
+            std::fs::write(&path, data?)?;
+            written.push(path);
+        }
+        Ok(())
+    })();
+
+    unsafe { instance.destroy_instance(None) };
+    result.map(|_| written)
+}
@@ -0,0 +1,618 @@
+//! Render pipelines declared with --pipeline, checked after every compile so an entry point
+//! missing from a pipeline, or a stage reading what the one before didn't write, fails the
+//! build instead of pipeline creation.
+
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use rspirv::{
+    dr::{Module, Operand},
+    spirv::{Decoration, ExecutionModel, Op, StorageClass, Word},
+};
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+
+use crate::{
+    reflect,
+    rename::{self, EntryPointRename},
+    stages,
+};
+
+/// Stages a render pipeline can have, in the order data flows through them.
+const STAGES: [&str; 7] = [
+    "task",
+    "mesh",
+    "vertex",
+    "tessellation_control",
+    "tessellation_evaluation",
+    "geometry",
+    "fragment",
+];
+
+/// A render pipeline given to --pipeline.
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    pub name: String,
+    /// Entry point of each stage, in pipeline order.
+    pub stages: Vec<(&'static str, String)>,
+}
+
+impl FromStr for Pipeline {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((name, stages)) = s.split_once(':') else {
+            return Err(
+                "Expected <name>:<stage>=<entry point>,..., e.g. pbr:vertex=pbr_vs,fragment=pbr_fs"
+                    .to_string(),
+            );
+        };
+        if name.is_empty() {
+            return Err("Pipeline name can't be empty".to_string());
+        }
+
+        let mut parsed = Vec::<(&'static str, String)>::new();
+        for stage in stages.split(',') {
+            let Some((stage, entry_point)) = stage.split_once('=').filter(|(_, ep)| !ep.is_empty())
+            else {
+                return Err(format!("Expected <stage>=<entry point>, got {stage:?}"));
+            };
+            let Some(stage) = STAGES.into_iter().find(|known| *known == stage) else {
+                return Err(format!(
+                    "Unknown stage {stage:?}, expected one of {}",
+                    STAGES.join(", ")
+                ));
+            };
+            if parsed.iter().any(|(other, _)| *other == stage) {
+                return Err(format!("{name} has more than one {stage} stage"));
+            }
+            parsed.push((stage, entry_point.to_string()));
+        }
+        parsed.sort_by_key(|(stage, _)| STAGES.iter().position(|known| known == stage));
+
+        Ok(Pipeline {
+            name: name.to_string(),
+            stages: parsed,
+        })
+    }
+}
+
+/// The pipelines as recorded in the artifact, each stage's entry point keyed by stage.
+pub fn record(pipelines: &[Pipeline]) -> BTreeMap<String, BTreeMap<&'static str, String>> {
+    pipelines
+        .iter()
+        .map(|pipeline| {
+            (
+                pipeline.name.clone(),
+                pipeline.stages.iter().cloned().collect(),
+            )
+        })
+        .collect()
+}
+
+/// Everything wrong with the declared pipelines.
+#[derive(Debug, Clone)]
+pub struct PipelineReport(pub Vec<String>);
+
+impl fmt::Display for PipelineReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} pipeline error(s):", self.0.len())?;
+        for error in &self.0 {
+            write!(f, "\n  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// An entry point as found in the compiled modules.
+struct Stage<'a> {
+    model: ExecutionModel,
+    module: &'a Module,
+    function: Word,
+}
+
+/// A non-builtin input or output variable of a stage.
+struct Variable {
+    id: Word,
+    name: Option<String>,
+    ty: String,
+    /// How many locations the variable takes up, from its own onwards.
+    span: u32,
+    patch: bool,
+    flat: bool,
+    /// Whether it holds integers or doubles, which fragment shaders can't interpolate.
+    integer: bool,
+    /// Where the variable was declared, if the module records it.
+    source: Option<String>,
+}
+
+impl fmt::Display for Variable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "`{name}: {}`", self.ty)?,
+            None => write!(f, "`%{}: {}`", self.id, self.ty)?,
+        }
+        if let Some(source) = &self.source {
+            write!(f, " ({source})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Check every stage of the `pipelines` exists in `modules` with the right execution model,
+/// and reads at each location and component what the stage before it wrote there, per
+/// vertex or per patch alike.
+///
+/// Entry points are named as they appear in the output, after `rules` are applied.
+pub fn check(
+    pipelines: &[Pipeline],
+    modules: &RustGpuBuilderModules,
+    rules: &[EntryPointRename],
+) -> Result<(), PipelineReport> {
+    if pipelines.is_empty() {
+        return Ok(());
+    }
+
+    let parsed = reflect::named_modules(modules, "")
+        .into_iter()
+        .filter_map(|(_, module)| reflect::parse(module).ok())
+        .collect::<Vec<_>>();
+    let mut entry_points = BTreeMap::new();
+    for module in &parsed {
+        let functions = reflect::entry_point_functions(module);
+        for (model, name) in reflect::entry_points(module) {
+            if let Some(&function) = functions.get(&name) {
+                entry_points.insert(
+                    rename::rename(rules, &name),
+                    Stage {
+                        model,
+                        module,
+                        function,
+                    },
+                );
+            }
+        }
+    }
+
+    let mut errors = vec![];
+    for pipeline in pipelines {
+        let mut found = vec![];
+        for (stage, name) in &pipeline.stages {
+            match entry_points.get(name) {
+                None => errors.push(format!(
+                    "{}: no entry point {name:?} for its {stage} stage",
+                    pipeline.name
+                )),
+                Some(entry_point) if stages::stage_name(entry_point.model) != *stage => errors
+                    .push(format!(
+                        "{}: {name} is a {} entry point, not {stage}",
+                        pipeline.name,
+                        stages::stage_name(entry_point.model)
+                    )),
+                Some(entry_point) => found.push((*stage, name, entry_point)),
+            }
+        }
+        // Mismatched interfaces would only be noise next to a missing stage
+        if found.len() < pipeline.stages.len() {
+            continue;
+        }
+
+        // Task shaders hand mesh shaders a payload rather than located variables
+        let located = found.iter().filter(|(stage, ..)| *stage != "task");
+        for ((_, out_name, out_stage), (_, in_name, in_stage)) in
+            located.clone().zip(located.skip(1))
+        {
+            let outputs = interface(out_stage, StorageClass::Output);
+            for ((location, component), input) in interface(in_stage, StorageClass::Input) {
+                let at = place(location, component);
+                let Some(output) = outputs.get(&(location, component)) else {
+                    // Arrays, matrices and structs write past their own location
+                    let covering = outputs.iter().find(|((start, output_component), output)| {
+                        *start == location && *output_component != component
+                            || *start < location && location < start + output.span
+                    });
+                    errors.push(match covering {
+                        Some(((start, output_component), output)) if *start == location => format!(
+                            "{}: {in_name} reads {input} at {at}, but {out_name} writes {output} at {}",
+                            pipeline.name,
+                            place(*start, *output_component)
+                        ),
+                        Some(((start, _), output)) => format!(
+                            "{}: {in_name} reads {input} at {at}, inside {output} that {out_name} writes at location {start}",
+                            pipeline.name
+                        ),
+                        None => format!(
+                            "{}: {in_name} reads {input} at {at}, which {out_name} doesn't write",
+                            pipeline.name
+                        ),
+                    });
+                    continue;
+                };
+
+                if output.ty != input.ty {
+                    errors.push(format!(
+                        "{}: {out_name} writes {output} at {at}, but {in_name} reads {input}",
+                        pipeline.name
+                    ));
+                }
+                if output.patch != input.patch {
+                    let rate = |variable: &Variable| {
+                        if variable.patch {
+                            "per patch"
+                        } else {
+                            "per vertex"
+                        }
+                    };
+                    errors.push(format!(
+                        "{}: {out_name} writes {output} at {at} {}, but {in_name} reads {input} {}",
+                        pipeline.name,
+                        rate(output),
+                        rate(&input)
+                    ));
+                }
+                if in_stage.model == ExecutionModel::Fragment && input.integer && !input.flat {
+                    errors.push(format!(
+                        "{}: {in_name} reads {input} at {at} without Flat, but it can't be interpolated",
+                        pipeline.name
+                    ));
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(PipelineReport(errors))
+    }
+}
+
+/// The located inputs and outputs of the entry point implemented by `function`, each as
+/// `location L: type`, in location order.
+pub fn signature(module: &Module, model: ExecutionModel, function: Word) -> [Vec<String>; 2] {
+    let stage = Stage {
+        model,
+        module,
+        function,
+    };
+    [StorageClass::Input, StorageClass::Output].map(|class| {
+        interface(&stage, class)
+            .into_iter()
+            .map(|((location, component), variable)| {
+                let rate = if variable.patch { "patch " } else { "" };
+                format!("{rate}{}: {}", place(location, component), variable.ty)
+            })
+            .collect()
+    })
+}
+
+/// Whether a stage's variables of `class` hold one element per vertex.
+fn arrayed(model: ExecutionModel, class: StorageClass) -> bool {
+    match class {
+        StorageClass::Input => matches!(
+            model,
+            ExecutionModel::TessellationControl
+                | ExecutionModel::TessellationEvaluation
+                | ExecutionModel::Geometry
+        ),
+        _ => matches!(
+            model,
+            ExecutionModel::TessellationControl | ExecutionModel::MeshNV
+        ),
+    }
+}
+
+/// `location L`, and its component if it isn't the first.
+fn place(location: u32, component: u32) -> String {
+    match component {
+        0 => format!("location {location}"),
+        _ => format!("location {location} component {component}"),
+    }
+}
+
+/// The located, non-builtin variables of `class` in a stage's interface, keyed by location
+/// and component.
+fn interface(stage: &Stage, class: StorageClass) -> BTreeMap<(u32, u32), Variable> {
+    let module = stage.module;
+    let Some(entry_point) = module.entry_points.iter().find(
+        |inst| matches!(inst.operands.get(1), Some(Operand::IdRef(id)) if *id == stage.function),
+    ) else {
+        return BTreeMap::new();
+    };
+
+    let mut variables = BTreeMap::new();
+    for operand in entry_point.operands.iter().skip(3) {
+        let Operand::IdRef(id) = operand else {
+            continue;
+        };
+        let Some(variable) = reflect::global(module, *id) else {
+            continue;
+        };
+        if variable.class.opcode != Op::Variable
+            || variable.operands.first() != Some(&Operand::StorageClass(class))
+            || reflect::has_decoration(module, *id, Decoration::BuiltIn)
+        {
+            continue;
+        }
+        let Some(location) = reflect::decoration_value(module, *id, Decoration::Location) else {
+            continue;
+        };
+        let component =
+            reflect::decoration_value(module, *id, Decoration::Component).unwrap_or_default();
+        let patch = reflect::has_decoration(module, *id, Decoration::Patch);
+
+        // Per-vertex variables are compared by their elements
+        let mut ty = variable
+            .result_type
+            .and_then(|pointer| reflect::global(module, pointer))
+            .and_then(|pointer| match pointer.operands.get(1) {
+                Some(Operand::IdRef(pointee)) => Some(*pointee),
+                _ => None,
+            });
+        if arrayed(stage.model, class) && !patch {
+            ty = ty.map(|ty| element(module, ty).unwrap_or(ty));
+        }
+
+        variables.insert(
+            (location, component),
+            Variable {
+                id: *id,
+                name: reflect::name_of(module, *id),
+                ty: ty
+                    .map(|ty| reflect::type_name(module, ty))
+                    .unwrap_or_default(),
+                span: ty.map_or(1, |ty| span(module, ty)),
+                patch,
+                flat: reflect::has_decoration(module, *id, Decoration::Flat),
+                integer: ty.map_or(false, |ty| integer(module, ty)),
+                source: source(module, *id),
+            },
+        );
+    }
+    variables
+}
+
+/// Element type of the array type `ty`.
+fn element(module: &Module, ty: Word) -> Option<Word> {
+    let array = reflect::global(module, ty).filter(|inst| inst.class.opcode == Op::TypeArray)?;
+    match array.operands.first() {
+        Some(Operand::IdRef(element)) => Some(*element),
+        _ => None,
+    }
+}
+
+/// How many locations a variable of type `ty` takes up.
+fn span(module: &Module, ty: Word) -> u32 {
+    let Some(inst) = reflect::global(module, ty) else {
+        return 1;
+    };
+    match (inst.class.opcode, inst.operands.as_slice()) {
+        // Vectors of more than two doubles spill into a second location
+        (Op::TypeVector, [Operand::IdRef(component), Operand::LiteralInt32(count)]) => {
+            let wide = matches!(
+                reflect::global(module, *component).map(|inst| inst.operands.as_slice()),
+                Some([Operand::LiteralInt32(64), ..])
+            );
+            if wide && *count > 2 {
+                2
+            } else {
+                1
+            }
+        }
+        (Op::TypeMatrix, [Operand::IdRef(column), Operand::LiteralInt32(count)]) => {
+            count * span(module, *column)
+        }
+        (Op::TypeArray, [Operand::IdRef(element), Operand::IdRef(length)]) => {
+            let length =
+                match reflect::global(module, *length).and_then(|inst| inst.operands.first()) {
+                    Some(Operand::LiteralInt32(length)) => *length,
+                    _ => 1,
+                };
+            length * span(module, *element)
+        }
+        (Op::TypeStruct, members) => members
+            .iter()
+            .map(|member| match member {
+                Operand::IdRef(member) => span(module, *member),
+                _ => 0,
+            })
+            .sum(),
+        _ => 1,
+    }
+}
+
+/// Whether `ty` holds any integers or doubles.
+fn integer(module: &Module, ty: Word) -> bool {
+    let Some(inst) = reflect::global(module, ty) else {
+        return false;
+    };
+    match (inst.class.opcode, inst.operands.as_slice()) {
+        (Op::TypeInt, _) => true,
+        (Op::TypeFloat, [Operand::LiteralInt32(width), ..]) => *width == 64,
+        (Op::TypeVector | Op::TypeMatrix | Op::TypeArray, [Operand::IdRef(inner), ..]) => {
+            integer(module, *inner)
+        }
+        (Op::TypeStruct, members) => members
+            .iter()
+            .any(|member| matches!(member, Operand::IdRef(member) if integer(module, *member))),
+        _ => false,
+    }
+}
+
+/// Source line of the global `id`, from the `OpLine` in effect where it's declared.
+fn source(module: &Module, id: Word) -> Option<String> {
+    let mut location = None;
+    for inst in &module.types_global_values {
+        match inst.class.opcode {
+            Op::Line => location = reflect::line(module, inst),
+            Op::NoLine => location = None,
+            _ if inst.result_id == Some(id) => return location,
+            _ => (),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use rspirv::{binary::Assemble, dr::Builder, spirv};
+
+    use super::*;
+
+    #[derive(Copy, Clone)]
+    enum Ty {
+        F32,
+        U32,
+        Vec4,
+        Mat4,
+    }
+
+    /// A module whose vertex entry point `vs` writes `outputs` and fragment entry point `fs`
+    /// reads `inputs`, given as type, location and whether the input is Flat.
+    fn module(outputs: &[(Ty, u32)], inputs: &[(Ty, u32, bool)]) -> RustGpuBuilderModules {
+        let mut b = Builder::new();
+        b.capability(spirv::Capability::Shader);
+        b.memory_model(spirv::AddressingModel::Logical, spirv::MemoryModel::GLSL450);
+        let void = b.type_void();
+        let function_type = b.type_function(void, vec![]);
+
+        let ty = |b: &mut Builder, ty: Ty| {
+            let f32_type = b.type_float(32);
+            match ty {
+                Ty::F32 => f32_type,
+                Ty::U32 => b.type_int(32, 0),
+                Ty::Vec4 => b.type_vector(f32_type, 4),
+                Ty::Mat4 => {
+                    let column = b.type_vector(f32_type, 4);
+                    b.type_matrix(column, 4)
+                }
+            }
+        };
+        let variable = |b: &mut Builder, class, name: String, ty, location| {
+            let pointer = b.type_pointer(None, class, ty);
+            let variable = b.variable(pointer, None, class, None);
+            b.name(variable, name);
+            b.decorate(
+                variable,
+                Decoration::Location,
+                vec![Operand::LiteralInt32(location)],
+            );
+            variable
+        };
+
+        let mut written = vec![];
+        for &(output, location) in outputs {
+            let output = ty(&mut b, output);
+            written.push(variable(
+                &mut b,
+                StorageClass::Output,
+                format!("out_{location}"),
+                output,
+                location,
+            ));
+        }
+        let mut read = vec![];
+        for &(input, location, flat) in inputs {
+            let input = ty(&mut b, input);
+            let input = variable(
+                &mut b,
+                StorageClass::Input,
+                format!("in_{location}"),
+                input,
+                location,
+            );
+            if flat {
+                b.decorate(input, Decoration::Flat, vec![]);
+            }
+            read.push(input);
+        }
+
+        for (name, model, variables) in [
+            ("vs", ExecutionModel::Vertex, written),
+            ("fs", ExecutionModel::Fragment, read),
+        ] {
+            let function = b
+                .begin_function(void, None, spirv::FunctionControl::NONE, function_type)
+                .unwrap();
+            b.begin_block(None).unwrap();
+            b.ret().unwrap();
+            b.end_function().unwrap();
+            b.entry_point(model, function, name, variables);
+        }
+
+        let words = b.module().assemble();
+        RustGpuBuilderModules::Single(words.iter().flat_map(|word| word.to_le_bytes()).collect())
+    }
+
+    fn errors(modules: &RustGpuBuilderModules, pipeline: &str) -> Vec<String> {
+        match check(&[pipeline.parse().unwrap()], modules, &[]) {
+            Ok(()) => vec![],
+            Err(report) => report.0,
+        }
+    }
+
+    #[test]
+    fn parses_pipelines_in_stage_order() {
+        let pipeline: Pipeline = "pbr:fragment=fs,vertex=vs".parse().unwrap();
+        assert_eq!(pipeline.name, "pbr");
+        assert_eq!(
+            pipeline.stages,
+            [("vertex", "vs".to_string()), ("fragment", "fs".to_string())]
+        );
+
+        assert!("pbr".parse::<Pipeline>().is_err());
+        assert!("pbr:pixel=fs".parse::<Pipeline>().is_err());
+        assert!("pbr:vertex=a,vertex=b".parse::<Pipeline>().is_err());
+    }
+
+    #[test]
+    fn accepts_matching_stages() {
+        let modules = module(
+            &[(Ty::Vec4, 0), (Ty::U32, 1)],
+            &[(Ty::Vec4, 0, false), (Ty::U32, 1, true)],
+        );
+        assert_eq!(
+            errors(&modules, "pbr:vertex=vs,fragment=fs"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn reports_missing_and_misplaced_entry_points() {
+        let modules = module(&[], &[]);
+        assert_eq!(
+            errors(&modules, "pbr:vertex=fs,fragment=missing"),
+            [
+                "pbr: fs is a fragment entry point, not vertex",
+                "pbr: no entry point \"missing\" for its fragment stage",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_mismatched_interfaces() {
+        let modules = module(
+            &[(Ty::Vec4, 0), (Ty::U32, 2)],
+            &[
+                (Ty::F32, 0, false),
+                (Ty::Vec4, 1, false),
+                (Ty::U32, 2, false),
+            ],
+        );
+        assert_eq!(
+            errors(&modules, "pbr:vertex=vs,fragment=fs"),
+            [
+                "pbr: vs writes `out_0: vec4<f32>` at location 0, but fs reads `in_0: f32`",
+                "pbr: fs reads `in_1: vec4<f32>` at location 1, which vs doesn't write",
+                "pbr: fs reads `in_2: u32` at location 2 without Flat, but it can't be interpolated",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_reads_inside_wider_outputs() {
+        let modules = module(&[(Ty::Mat4, 0)], &[(Ty::Vec4, 1, false)]);
+        assert_eq!(
+            errors(&modules, "pbr:vertex=vs,fragment=fs"),
+            ["pbr: fs reads `in_1: vec4<f32>` at location 1, inside `out_0: mat4<vec4<f32>>` that vs writes at location 0"]
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! External post-processing steps run as emitters, for packing formats the builder doesn't know.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use crate::{
+    emit::{EmitInput, Emitted, Emitter},
+    OutputFormat,
+};
+
+/// An `<extension>=<command>` plugin.
+///
+/// The command runs through the platform shell with the artifact as JSON on its stdin,
+/// and whatever it prints to stdout is written to `<output>.<extension>`.
+#[derive(Debug, Clone)]
+pub struct Plugin {
+    extension: String,
+    command: String,
+}
+
+impl FromStr for Plugin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (extension, command) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Expected <extension>=<command>, got {s:?}"))?;
+
+        if extension.is_empty() || extension.contains(['/', '\\']) {
+            return Err(format!("Invalid plugin extension {extension:?}"));
+        }
+
+        Ok(Plugin {
+            extension: extension.to_string(),
+            command: command.to_string(),
+        })
+    }
+}
+
+impl Emitter for Plugin {
+    fn name(&self) -> &str {
+        &self.extension
+    }
+
+    fn emit(&self, input: &EmitInput) -> Result<Vec<Emitted>, String> {
+        #[cfg(windows)]
+        let mut command = Command::new("cmd");
+        #[cfg(windows)]
+        command.args(["/C", &self.command]);
+
+        #[cfg(not(windows))]
+        let mut command = Command::new("sh");
+        #[cfg(not(windows))]
+        command.args(["-c", &self.command]);
+
+        let mut child = command
+            .env("RUST_GPU_BUILDER_CRATE", input.args.crate_name())
+            .env("RUST_GPU_BUILDER_OUTPUT", input.output_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run `{}`: {e}", self.command))?;
+
+        // Written from another thread, as a plugin may start printing before it has read everything
+        let json = OutputFormat::Json.serialize(input.artifact);
+        let mut stdin = child.stdin.take().unwrap();
+        let writer = std::thread::spawn(move || stdin.write_all(&json));
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to run `{}`: {e}", self.command))?;
+        // A plugin that doesn't need the artifact may exit without reading it
+        writer.join().unwrap().ok();
+
+        if !output.status.success() {
+            return Err(format!("`{}` exited with {}", self.command, output.status));
+        }
+
+        Ok(vec![Emitted {
+            path: input.output_path.with_extension(&self.extension),
+            bytes: output.stdout,
+        }])
+    }
+}
@@ -0,0 +1,54 @@
+//! Running build workers at reduced CPU and IO priority, so a long rebuild doesn't make
+//! an editor or game running on the same machine stutter.
+//!
+//! Priority is inherited, so the cargo and rustc processes a worker spawns run at it too.
+
+use std::process::Command;
+
+/// Niceness workers run at, the same `nice` defaults to.
+#[cfg(unix)]
+const NICENESS: libc::c_int = 10;
+
+/// Start `command` at reduced priority.
+#[cfg(unix)]
+pub fn lower(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    // Only async-signal-safe calls, as this runs between fork and exec. Failing to
+    // lower the priority isn't worth failing the build over, so errors are ignored.
+    unsafe {
+        command.pre_exec(|| {
+            libc::setpriority(libc::PRIO_PROCESS, 0, NICENESS);
+            #[cfg(target_os = "linux")]
+            lower_io();
+            Ok(())
+        });
+    }
+}
+
+/// Drop the calling process to the lowest priority of the best-effort IO class,
+/// like `ionice -c2 -n7`. The idle class would starve builds on a busy disk.
+#[cfg(target_os = "linux")]
+unsafe fn lower_io() {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_BE: libc::c_int = 2;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    const LOWEST: libc::c_int = 7;
+
+    libc::syscall(
+        libc::SYS_ioprio_set,
+        IOPRIO_WHO_PROCESS,
+        0,
+        IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT | LOWEST,
+    );
+}
+
+/// Start `command` at reduced priority.
+#[cfg(windows)]
+pub fn lower(command: &mut Command) {
+    use std::os::windows::process::CommandExt;
+
+    const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x4000;
+
+    command.creation_flags(BELOW_NORMAL_PRIORITY_CLASS);
+}
@@ -0,0 +1,120 @@
+//! The shader crates a watch-mode process builds: the one on its command line,
+//! plus any registered at runtime through the control API.
+//!
+//! Every project is built by the same queue and executor, one at a time into the shared
+//! spirv-builder target directory, so dependencies common to several crates are only built once.
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use serde::Serialize;
+
+use crate::ShaderBuilder;
+
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub args: ShaderBuilder,
+    /// Whether the project was given on the command line, which keeps it registered.
+    pub pinned: bool,
+}
+
+/// A project, as listed by `GET /projects`.
+#[derive(Debug, Serialize)]
+pub struct Listing {
+    pub name: String,
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    pub path: String,
+    pub watch_paths: Vec<String>,
+    pub pinned: bool,
+}
+
+/// Projects by name, shared between the server and the message bus.
+#[derive(Debug, Clone, Default)]
+pub struct Projects(Arc<Mutex<BTreeMap<String, Project>>>);
+
+impl Projects {
+    pub fn get(&self, name: &str) -> Option<Project> {
+        self.0.lock().unwrap().get(name).cloned()
+    }
+
+    /// Name of the project `selector` names, by project name, crate name or crate path,
+    /// or of the only project if there's no selector.
+    pub fn select(&self, selector: Option<&str>) -> Result<String, String> {
+        let projects = self.0.lock().unwrap();
+        let Some(selector) = selector else {
+            return match projects.keys().collect::<Vec<_>>()[..] {
+                [name] => Ok(name.clone()),
+                _ => Err(format!(
+                    "{} projects are registered, select one with \"crate\"",
+                    projects.len()
+                )),
+            };
+        };
+
+        if projects.contains_key(selector) {
+            return Ok(selector.to_string());
+        }
+        projects
+            .iter()
+            .find(|(_, project)| {
+                selector == project.args.crate_name()
+                    || Path::new(selector) == project.args.path_to_crate
+            })
+            .map(|(name, _)| name.clone())
+            .ok_or_else(|| format!("No project matches {selector:?}"))
+    }
+
+    /// Add a project, unless its name or crate is already taken.
+    ///
+    /// A crate can only be registered once, as builds are queued by crate.
+    pub fn register(&self, name: String, project: Project) -> Result<(), String> {
+        let mut projects = self.0.lock().unwrap();
+        if projects.contains_key(&name) {
+            return Err(format!("A project named {name:?} is already registered"));
+        }
+        if let Some((other, _)) = projects
+            .iter()
+            .find(|(_, other)| other.args.path_to_crate == project.args.path_to_crate)
+        {
+            return Err(format!(
+                "{:?} is already registered as {other:?}",
+                project.args.path_to_crate
+            ));
+        }
+        projects.insert(name, project);
+        Ok(())
+    }
+
+    pub fn unregister(&self, name: &str) -> Result<(), String> {
+        let mut projects = self.0.lock().unwrap();
+        match projects.get(name) {
+            None => Err(format!("No project named {name:?}")),
+            Some(project) if project.pinned => Err(format!(
+                "{name:?} was given on the command line, so can't be unregistered"
+            )),
+            Some(_) => {
+                projects.remove(name);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn list(&self) -> Vec<Listing> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, project)| Listing {
+                name: name.clone(),
+                crate_name: project.args.crate_name(),
+                path: project.args.path_to_crate.display().to_string(),
+                watch_paths: project.args.watch_paths.clone().unwrap_or_default(),
+                pinned: project.pinned,
+            })
+            .collect()
+    }
+}
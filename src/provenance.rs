@@ -0,0 +1,40 @@
+use std::{path::Path, process::Command};
+
+use serde::Serialize;
+
+/// The git revision a shader crate was built from.
+#[derive(Debug, Clone, Serialize)]
+pub struct Provenance {
+    pub commit: String,
+    /// Whether the working tree had uncommitted changes, so the build may not match `commit`.
+    pub dirty: bool,
+    /// `None` on a detached HEAD.
+    pub branch: Option<String>,
+}
+
+/// Run git in `dir`, returning its trimmed stdout if it succeeded.
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Provenance of the crate at `path_to_crate`, if it's inside a git repository with at least one commit.
+pub fn detect(path_to_crate: &Path) -> Option<Provenance> {
+    let commit = git(path_to_crate, &["rev-parse", "HEAD"])?;
+    let dirty = git(path_to_crate, &["status", "--porcelain"]).map_or(true, |s| !s.is_empty());
+    let branch = git(path_to_crate, &["rev-parse", "--abbrev-ref", "HEAD"])
+        .filter(|branch| branch != "HEAD");
+
+    Some(Provenance {
+        commit,
+        dirty,
+        branch,
+    })
+}
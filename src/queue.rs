@@ -0,0 +1,222 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Why a build was queued. Higher priorities are built first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// A watched file changed.
+    Watch,
+    /// Someone explicitly asked for the build, e.g. over HTTP.
+    Requested,
+}
+
+/// The next action the message bus should take for a [`BuildQueue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Next<T> {
+    /// Start building the given crate.
+    Build(PathBuf, T),
+    /// Every pending crate is rate limited, check again after the given duration.
+    Wait(Duration),
+    /// The concurrency limit is reached, or nothing is queued.
+    Idle,
+}
+
+/// What became of a build pushed onto a [`BuildQueue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pushed<T> {
+    /// Nothing was pending for the crate, so the build was queued.
+    Queued,
+    /// A build was already pending for the crate and the two were merged,
+    /// leaving this payload unbuilt.
+    Coalesced(T),
+}
+
+#[derive(Debug)]
+struct Pending<T> {
+    crate_path: PathBuf,
+    priority: Priority,
+    payload: T,
+}
+
+/// Queue of pending watch-mode builds, keyed by shader crate.
+///
+/// A crate is queued at most once, so a burst of change events arriving mid-build
+/// (e.g. during a `git checkout`) collapses into a single follow-up rebuild.
+/// When builds for the same crate coalesce, the newest payload of the highest priority wins,
+/// so a watch-triggered rebuild never replaces options someone asked for.
+///
+/// Pending crates build highest priority first, then in the order they were queued,
+/// with at most `max_concurrent` building at once and never the same crate twice.
+#[derive(Debug)]
+pub struct BuildQueue<T> {
+    min_interval: Duration,
+    max_concurrent: usize,
+    pending: Vec<Pending<T>>,
+    building: BTreeSet<PathBuf>,
+    last_started: BTreeMap<PathBuf, Instant>,
+}
+
+impl<T> BuildQueue<T> {
+    pub fn new(min_interval: Duration, max_concurrent: usize) -> Self {
+        BuildQueue {
+            min_interval,
+            max_concurrent: max_concurrent.max(1),
+            pending: vec![],
+            building: BTreeSet::new(),
+            last_started: BTreeMap::new(),
+        }
+    }
+
+    /// Queue a build of the provided crate, merging it with one already pending.
+    pub fn push(&mut self, crate_path: PathBuf, priority: Priority, payload: T) -> Pushed<T> {
+        let Some(pending) = self.pending.iter_mut().find(|p| p.crate_path == crate_path) else {
+            self.pending.push(Pending {
+                crate_path,
+                priority,
+                payload,
+            });
+            return Pushed::Queued
+        };
+
+        if priority >= pending.priority {
+            pending.priority = priority;
+            Pushed::Coalesced(std::mem::replace(&mut pending.payload, payload))
+        } else {
+            Pushed::Coalesced(payload)
+        }
+    }
+
+    /// Mark the build of the provided crate as finished.
+    pub fn finish(&mut self, crate_path: &Path) {
+        self.building.remove(crate_path);
+    }
+
+    /// Pop the next crate to build, if below the concurrency limit and its rebuild interval has elapsed.
+    pub fn next(&mut self) -> Next<T> {
+        if self.building.len() >= self.max_concurrent {
+            return Next::Idle;
+        }
+
+        let mut wait = None::<Duration>;
+        let mut best = None::<usize>;
+        for (i, pending) in self.pending.iter().enumerate() {
+            if self.building.contains(&pending.crate_path) {
+                continue;
+            }
+
+            if let Some(elapsed) = self
+                .last_started
+                .get(&pending.crate_path)
+                .map(Instant::elapsed)
+            {
+                if elapsed < self.min_interval {
+                    let remaining = self.min_interval - elapsed;
+                    wait = Some(wait.map_or(remaining, |wait| wait.min(remaining)));
+                    continue;
+                }
+            }
+
+            // Ties go to the earliest queued
+            if best.map_or(true, |best| pending.priority > self.pending[best].priority) {
+                best = Some(i);
+            }
+        }
+
+        match (best, wait) {
+            (Some(i), _) => {
+                let Pending {
+                    crate_path,
+                    payload,
+                    ..
+                } = self.pending.remove(i);
+                self.last_started.insert(crate_path.clone(), Instant::now());
+                self.building.insert(crate_path.clone());
+                Next::Build(crate_path, payload)
+            }
+            (None, Some(wait)) => Next::Wait(wait),
+            (None, None) => Next::Idle,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue() -> BuildQueue<&'static str> {
+        BuildQueue::new(Duration::ZERO, 1)
+    }
+
+    #[test]
+    fn coalesces_builds_of_one_crate() {
+        let mut queue = queue();
+        assert_eq!(
+            queue.push("a".into(), Priority::Watch, "first"),
+            Pushed::Queued
+        );
+        assert_eq!(
+            queue.push("a".into(), Priority::Watch, "second"),
+            Pushed::Coalesced("first")
+        );
+        assert_eq!(queue.next(), Next::Build("a".into(), "second"));
+        queue.finish(Path::new("a"));
+        assert_eq!(queue.next(), Next::Idle);
+    }
+
+    #[test]
+    fn keeps_requested_options_over_watch_rebuilds() {
+        let mut queue = queue();
+        queue.push("a".into(), Priority::Requested, "requested");
+        assert_eq!(
+            queue.push("a".into(), Priority::Watch, "watch"),
+            Pushed::Coalesced("watch")
+        );
+        assert_eq!(queue.next(), Next::Build("a".into(), "requested"));
+    }
+
+    #[test]
+    fn builds_higher_priorities_first_then_in_order() {
+        let mut queue = BuildQueue::new(Duration::ZERO, 3);
+        queue.push("a".into(), Priority::Watch, "a");
+        queue.push("b".into(), Priority::Watch, "b");
+        queue.push("c".into(), Priority::Requested, "c");
+        assert_eq!(queue.next(), Next::Build("c".into(), "c"));
+        assert_eq!(queue.next(), Next::Build("a".into(), "a"));
+        assert_eq!(queue.next(), Next::Build("b".into(), "b"));
+    }
+
+    #[test]
+    fn limits_concurrent_builds() {
+        let mut queue = queue();
+        queue.push("a".into(), Priority::Watch, "a");
+        queue.push("b".into(), Priority::Watch, "b");
+        assert_eq!(queue.next(), Next::Build("a".into(), "a"));
+        assert_eq!(queue.next(), Next::Idle);
+        queue.finish(Path::new("a"));
+        assert_eq!(queue.next(), Next::Build("b".into(), "b"));
+    }
+
+    #[test]
+    fn never_builds_a_crate_twice_at_once() {
+        let mut queue = BuildQueue::new(Duration::ZERO, 2);
+        queue.push("a".into(), Priority::Watch, "first");
+        assert_eq!(queue.next(), Next::Build("a".into(), "first"));
+        queue.push("a".into(), Priority::Watch, "second");
+        assert_eq!(queue.next(), Next::Idle);
+        queue.finish(Path::new("a"));
+        assert_eq!(queue.next(), Next::Build("a".into(), "second"));
+    }
+
+    #[test]
+    fn rate_limits_rebuilds() {
+        let mut queue = BuildQueue::new(Duration::from_secs(60), 1);
+        queue.push("a".into(), Priority::Watch, "first");
+        assert_eq!(queue.next(), Next::Build("a".into(), "first"));
+        queue.finish(Path::new("a"));
+        queue.push("a".into(), Priority::Watch, "second");
+        assert!(matches!(queue.next(), Next::Wait(wait) if wait <= Duration::from_secs(60)));
+    }
+}
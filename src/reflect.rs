@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+
+use rspirv::{
+    dr::{Instruction, Module, Operand},
+    spirv::{Decoration, ExecutionMode, ExecutionModel, Op, Word},
+};
+
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+
+/// Parse a SPIR-V binary into rspirv's data representation.
+pub fn parse(bytes: &[u8]) -> Result<Module, rspirv::binary::ParseState> {
+    rspirv::dr::load_bytes(bytes)
+}
+
+/// Pair each module with a display name: `single_name` for a single module,
+/// or the entry point name for multimodule builds.
+pub fn named_modules<'a>(
+    modules: &'a RustGpuBuilderModules,
+    single_name: &str,
+) -> Vec<(String, &'a [u8])> {
+    match modules {
+        RustGpuBuilderModules::Single(module) => vec![(single_name.to_string(), module.as_slice())],
+        RustGpuBuilderModules::Multi(multi) => multi
+            .iter()
+            .map(|(name, module)| (name.clone(), module.as_slice()))
+            .collect(),
+    }
+}
+
+/// A descriptor binding declared by a module.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Binding {
+    pub set: u32,
+    pub binding: u32,
+    /// Result id of the bound variable.
+    pub variable: Word,
+}
+
+/// Literal operand of an `OpDecorate` carrying the given decoration, keyed by target id.
+fn decorations(module: &Module, decoration: Decoration) -> BTreeMap<Word, u32> {
+    module
+        .annotations
+        .iter()
+        .filter(|inst| inst.class.opcode == Op::Decorate)
+        .filter_map(|inst| match inst.operands.as_slice() {
+            [Operand::IdRef(target), Operand::Decoration(d), Operand::LiteralInt32(value), ..]
+                if *d == decoration =>
+            {
+                Some((*target, *value))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Literal operand of the given decoration on `id`, e.g. its `Location`.
+pub fn decoration_value(module: &Module, id: Word, decoration: Decoration) -> Option<u32> {
+    decorations(module, decoration).get(&id).copied()
+}
+
+/// All descriptor bindings declared by a module, ordered by set and binding.
+pub fn bindings(module: &Module) -> Vec<Binding> {
+    let sets = decorations(module, Decoration::DescriptorSet);
+    let bindings = decorations(module, Decoration::Binding);
+
+    let mut out = sets
+        .iter()
+        .filter_map(|(variable, set)| {
+            Some(Binding {
+                set: *set,
+                binding: *bindings.get(variable)?,
+                variable: *variable,
+            })
+        })
+        .collect::<Vec<_>>();
+    out.sort();
+    out
+}
+
+/// Names of the entry points declared by a module.
+pub fn entry_point_names(module: &Module) -> Vec<String> {
+    module
+        .entry_points
+        .iter()
+        .filter_map(|inst| match inst.operands.get(2) {
+            Some(Operand::LiteralString(name)) => Some(name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Names of the entry points declared by a module, alongside their execution models.
+pub fn entry_points(module: &Module) -> Vec<(ExecutionModel, String)> {
+    module
+        .entry_points
+        .iter()
+        .filter_map(|inst| match (inst.operands.first(), inst.operands.get(2)) {
+            (Some(Operand::ExecutionModel(model)), Some(Operand::LiteralString(name))) => {
+                Some((*model, name.clone()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether any instruction inside a function body references `id`.
+pub fn is_statically_used(module: &Module, id: Word) -> bool {
+    module
+        .functions
+        .iter()
+        .flat_map(|function| function.all_inst_iter())
+        .any(|inst| {
+            inst.operands
+                .iter()
+                .any(|operand| matches!(operand, Operand::IdRef(operand) if *operand == id))
+        })
+}
+
+/// Debug name of `id`, if the module carries one.
+pub fn name_of(module: &Module, id: Word) -> Option<String> {
+    module
+        .debug_names
+        .iter()
+        .find_map(|inst| match (inst.class.opcode, inst.operands.as_slice()) {
+            (Op::Name, [Operand::IdRef(target), Operand::LiteralString(name)]) if *target == id => {
+                Some(name.clone())
+            }
+            _ => None,
+        })
+}
+
+/// Capabilities declared by a module, by name.
+pub fn capabilities(module: &Module) -> Vec<String> {
+    module
+        .capabilities
+        .iter()
+        .filter_map(|inst| match inst.operands.first() {
+            Some(Operand::Capability(capability)) => Some(format!("{capability:?}")),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The type, constant or global variable instruction defining `id`.
+pub fn global(module: &Module, id: Word) -> Option<&Instruction> {
+    module
+        .types_global_values
+        .iter()
+        .find(|inst| inst.result_id == Some(id))
+}
+
+/// Short description of the type `id`, e.g. `vec4<f32>` or `ptr<StorageBuffer, [u32]>`.
+pub fn type_name(module: &Module, id: Word) -> String {
+    let Some(inst) = global(module, id) else {
+        return format!("%{id}")
+    };
+
+    let operands = inst.operands.as_slice();
+    match (inst.class.opcode, operands) {
+        (Op::TypeVoid, _) => "void".to_string(),
+        (Op::TypeBool, _) => "bool".to_string(),
+        (Op::TypeInt, [Operand::LiteralInt32(width), Operand::LiteralInt32(signed)]) => {
+            format!("{}{width}", if *signed == 0 { 'u' } else { 'i' })
+        }
+        (Op::TypeFloat, [Operand::LiteralInt32(width), ..]) => format!("f{width}"),
+        (Op::TypeVector, [Operand::IdRef(component), Operand::LiteralInt32(count)]) => {
+            format!("vec{count}<{}>", type_name(module, *component))
+        }
+        (Op::TypeMatrix, [Operand::IdRef(column), Operand::LiteralInt32(count)]) => {
+            format!("mat{count}<{}>", type_name(module, *column))
+        }
+        (Op::TypeArray, [Operand::IdRef(element), Operand::IdRef(length)]) => {
+            let length = global(module, *length)
+                .and_then(|inst| match inst.operands.first() {
+                    Some(Operand::LiteralInt32(length)) => Some(length.to_string()),
+                    _ => None,
+                })
+                .unwrap_or_else(|| "?".to_string());
+            format!("[{}; {length}]", type_name(module, *element))
+        }
+        (Op::TypeRuntimeArray, [Operand::IdRef(element)]) => {
+            format!("[{}]", type_name(module, *element))
+        }
+        (Op::TypeStruct, _) => name_of(module, id).unwrap_or_else(|| format!("struct %{id}")),
+        (Op::TypePointer, [Operand::StorageClass(class), Operand::IdRef(pointee)]) => {
+            format!("ptr<{class:?}, {}>", type_name(module, *pointee))
+        }
+        (Op::TypeImage, _) => "image".to_string(),
+        (Op::TypeSampler, _) => "sampler".to_string(),
+        (Op::TypeSampledImage, _) => "sampled_image".to_string(),
+        _ => format!("%{id}"),
+    }
+}
+
+/// Id of the function implementing an entry point, keyed by entry point name.
+pub fn entry_point_functions(module: &Module) -> BTreeMap<String, Word> {
+    module
+        .entry_points
+        .iter()
+        .filter_map(|inst| match (inst.operands.get(1), inst.operands.get(2)) {
+            (Some(Operand::IdRef(function)), Some(Operand::LiteralString(name))) => {
+                Some((name.clone(), *function))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The `LocalSize` execution mode of the entry point implemented by `function`, if declared.
+pub fn local_size(module: &Module, function: Word) -> Option<[u32; 3]> {
+    module
+        .execution_modes
+        .iter()
+        .find_map(|inst| match inst.operands.as_slice() {
+            [Operand::IdRef(target), Operand::ExecutionMode(ExecutionMode::LocalSize), Operand::LiteralInt32(x), Operand::LiteralInt32(y), Operand::LiteralInt32(z)]
+                if *target == function =>
+            {
+                Some([*x, *y, *z])
+            }
+            _ => None,
+        })
+}
+
+/// `file:line:column` of an `OpLine`, naming the file by its `OpString`.
+pub fn line(module: &Module, inst: &Instruction) -> Option<String> {
+    let [Operand::IdRef(file), Operand::LiteralInt32(line), Operand::LiteralInt32(column)] =
+        inst.operands.as_slice() else {
+        return None
+    };
+    let file = module
+        .debug_string_source
+        .iter()
+        .find(|string| string.result_id == Some(*file))
+        .and_then(|string| match string.operands.first() {
+            Some(Operand::LiteralString(file)) => Some(file.clone()),
+            _ => None,
+        })
+        .unwrap_or_else(|| format!("%{file}"));
+    Some(format!("{file}:{line}:{column}"))
+}
+
+/// Whether `id` carries the given decoration.
+pub fn has_decoration(module: &Module, id: Word, decoration: Decoration) -> bool {
+    module.annotations.iter().any(|inst| {
+        inst.class.opcode == Op::Decorate
+            && matches!(
+                inst.operands.as_slice(),
+                [Operand::IdRef(target), Operand::Decoration(d), ..] if *target == id && *d == decoration
+            )
+    })
+}
@@ -0,0 +1,85 @@
+//! The metadata a build reflects out of its modules, for the `reflect` subcommand to print
+//! for an existing artifact or third-party SPIR-V without a rebuild.
+
+use std::{collections::BTreeMap, error::Error, path::Path};
+
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+use serde::Serialize;
+
+use crate::{
+    kernels::{self, KernelBinding, KernelSignature},
+    output, reflect, stages,
+};
+
+/// SPIR-V's magic number, as the first word of a module in either byte order.
+const MAGIC: [[u8; 4]; 2] = [[0x03, 0x02, 0x23, 0x07], [0x07, 0x23, 0x02, 0x03]];
+
+/// Everything reflected out of an artifact's modules, as `reflect` prints it.
+#[derive(Debug, Serialize)]
+pub struct Reflection {
+    pub entry_points: Vec<String>,
+    /// Entry point names keyed by stage, as --group-entry-points records them.
+    pub stages: BTreeMap<&'static str, Vec<String>>,
+    /// Parameters, bindings and workgroup size of each compute entry point or OpenCL kernel,
+    /// as recorded for OpenCL targets.
+    pub kernels: BTreeMap<String, KernelSignature>,
+    pub modules: BTreeMap<String, ModuleReflection>,
+}
+
+/// What one module declares, keyed by entry point in multimodule artifacts.
+#[derive(Debug, Serialize)]
+pub struct ModuleReflection {
+    pub size: usize,
+    pub capabilities: Vec<String>,
+    /// Every descriptor binding the module declares, used or not.
+    pub bindings: Vec<KernelBinding>,
+}
+
+/// Reflect the artifact at `path`, or the module if it's raw SPIR-V.
+pub async fn reflect(path: &Path, dictionary: Option<&[u8]>) -> Result<Reflection, Box<dyn Error>> {
+    let bytes = async_fs::read(path)
+        .await
+        .map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+
+    let raw = bytes
+        .get(..4)
+        .map_or(false, |magic| MAGIC.iter().any(|m| m == magic));
+    let (modules, entry_points) = if raw {
+        let module =
+            reflect::parse(&bytes).map_err(|e| format!("Failed to parse {path:?}: {e}"))?;
+        let entry_points = reflect::entry_point_names(&module);
+        (RustGpuBuilderModules::Single(bytes), entry_points)
+    } else {
+        let output = output::read_artifact_with_dictionary(path, dictionary).await?;
+        (output.modules, output.entry_points)
+    };
+
+    let single_name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut reflected = BTreeMap::new();
+    for (name, module) in reflect::named_modules(&modules, &single_name) {
+        let parsed =
+            reflect::parse(module).map_err(|e| format!("Failed to parse module {name}: {e}"))?;
+        reflected.insert(
+            name,
+            ModuleReflection {
+                size: module.len(),
+                capabilities: reflect::capabilities(&parsed),
+                bindings: reflect::bindings(&parsed)
+                    .into_iter()
+                    .map(|binding| KernelBinding::new(&parsed, binding))
+                    .collect(),
+            },
+        );
+    }
+
+    // Names in artifacts already had any --entry-point-rename rules applied
+    Ok(Reflection {
+        entry_points,
+        stages: stages::group(&modules, &[]),
+        kernels: kernels::signatures(&modules, &[]),
+        modules: reflected,
+    })
+}
@@ -0,0 +1,135 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use regex::Regex;
+use spirv_builder::{CompileResult, ModuleResult};
+
+/// A `<regex>=<replacement>` rule applied to entry point names.
+///
+/// The replacement may refer to capture groups as `$1` or `${name}`.
+#[derive(Debug, Clone)]
+pub struct EntryPointRename {
+    regex: Regex,
+    replacement: String,
+}
+
+impl FromStr for EntryPointRename {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (regex, replacement) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Expected <regex>=<replacement>, got {s:?}"))?;
+
+        let regex = Regex::new(regex).map_err(|e| format!("Invalid regex {regex:?}: {e:}"))?;
+
+        Ok(EntryPointRename {
+            regex,
+            replacement: replacement.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for EntryPointRename {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.regex, self.replacement)
+    }
+}
+
+/// Apply each rule to `name` in turn.
+pub fn rename(rules: &[EntryPointRename], name: &str) -> String {
+    rules.iter().fold(name.to_string(), |name, rule| {
+        rule.regex
+            .replace_all(&name, rule.replacement.as_str())
+            .into_owned()
+    })
+}
+
+/// Rename the entry points of a compile result, including the keys of multimodule output.
+///
+/// Fails if two entry points end up with the same name.
+pub fn apply(rules: &[EntryPointRename], result: CompileResult) -> Result<CompileResult, String> {
+    if rules.is_empty() {
+        return Ok(result);
+    }
+
+    let mut entry_points = vec![];
+    let mut originals = BTreeMap::<String, &str>::new();
+    for entry_point in &result.entry_points {
+        let name = rename(rules, entry_point);
+        if let Some(other) = originals.insert(name.clone(), entry_point) {
+            return Err(format!(
+                "Entry points {other:?} and {entry_point:?} were both renamed to {name:?}"
+            ));
+        }
+        entry_points.push(name);
+    }
+
+    let module = match result.module {
+        ModuleResult::SingleModule(path) => ModuleResult::SingleModule(path),
+        ModuleResult::MultiModule(multi) => ModuleResult::MultiModule(
+            multi
+                .into_iter()
+                .map(|(name, path)| (rename(rules, &name), path))
+                .collect(),
+        ),
+    };
+
+    Ok(CompileResult {
+        entry_points,
+        module,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn rules(rules: &[&str]) -> Vec<EntryPointRename> {
+        rules.iter().map(|rule| rule.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn applies_rules_in_turn() {
+        let rules = rules(&["^main_(.*)$=$1", "::=_"]);
+        assert_eq!(rename(&rules, "main_sky::fs"), "sky_fs");
+        assert_eq!(rename(&rules, "other"), "other");
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        assert!("no-replacement".parse::<EntryPointRename>().is_err());
+        assert!("(=x".parse::<EntryPointRename>().is_err());
+    }
+
+    #[test]
+    fn renames_multimodule_keys() {
+        let result = CompileResult {
+            entry_points: vec!["main_vs".to_string(), "main_fs".to_string()],
+            module: ModuleResult::MultiModule(
+                [
+                    ("main_vs".to_string(), PathBuf::from("vs.spv")),
+                    ("main_fs".to_string(), PathBuf::from("fs.spv")),
+                ]
+                .into(),
+            ),
+        };
+        let result = apply(&rules(&["^main_="]), result).unwrap();
+        assert_eq!(result.entry_points, ["vs", "fs"]);
+        let ModuleResult::MultiModule(multi) = result.module else {
+            panic!("Expected a multimodule result")
+        };
+        assert_eq!(multi.keys().collect::<Vec<_>>(), ["fs", "vs"]);
+    }
+
+    #[test]
+    fn rejects_collisions() {
+        let result = CompileResult {
+            entry_points: vec!["a_main".to_string(), "b_main".to_string()],
+            module: ModuleResult::SingleModule(PathBuf::from("shader.spv")),
+        };
+        let e = apply(&rules(&["^._="]), result).unwrap_err();
+        assert!(e.contains("both renamed to \"main\""), "{e}");
+    }
+}
@@ -0,0 +1,117 @@
+//! Restarting a watch session when the builder's own executable is replaced, so a daemon
+//! left running while tooling updates the binary doesn't keep building with the old one.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use async_io::Timer;
+use notify::{RecursiveMode, Watcher};
+use tracing::{error, info, warn};
+
+use crate::{async_watcher, cancel::CancelToken};
+
+/// How long the new executable must go unchanged before it counts as completely written.
+const SETTLE: Duration = Duration::from_millis(500);
+
+/// Watches the running executable, shutting the session down for a restart once it's replaced.
+#[derive(Debug, Clone)]
+pub struct SelfWatch {
+    exe: PathBuf,
+    requested: Arc<AtomicBool>,
+}
+
+impl SelfWatch {
+    pub fn new() -> std::io::Result<Self> {
+        Ok(SelfWatch {
+            // Resolved now, as /proc/self/exe points at a deleted file once it's replaced
+            exe: std::env::current_exe()?,
+            requested: Arc::default(),
+        })
+    }
+
+    pub fn exe(&self) -> &Path {
+        &self.exe
+    }
+
+    /// Whether the executable changed and the session should restart once it winds down.
+    pub fn requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    /// Cancel `shutdown` once the executable has been replaced.
+    pub async fn run(self, shutdown: CancelToken) {
+        let (mut watcher, rx) = match async_watcher() {
+            Ok(pair) => pair,
+            Err(e) => {
+                warn!("Failed to watch {:?} for --auto-restart: {e}", self.exe);
+                return;
+            }
+        };
+        // Installers usually rename a new file over the old one, which a watch
+        // on the file itself would miss
+        let dir = self.exe.parent().unwrap_or(Path::new("."));
+        if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+            warn!("Failed to watch {dir:?} for --auto-restart: {e}");
+            return;
+        }
+
+        let started = stamp(&self.exe);
+        while let Ok(event) = rx.recv().await {
+            let Ok(event) = event else { continue };
+            if !event.paths.contains(&self.exe) {
+                continue;
+            }
+
+            // Wait for the write to finish, then check it wasn't just touched
+            let mut last = stamp(&self.exe);
+            loop {
+                Timer::after(SETTLE).await;
+                let now = stamp(&self.exe);
+                if now == last {
+                    break;
+                }
+                last = now;
+            }
+            if last.is_none() || last == started {
+                continue;
+            }
+
+            info!("{:?} changed, restarting", self.exe);
+            self.requested.store(true, Ordering::SeqCst);
+            shutdown.cancel();
+            return;
+        }
+    }
+
+    /// Replace this process with the new executable, run with the same arguments.
+    pub fn exec(&self) -> ! {
+        let mut command = std::process::Command::new(&self.exe);
+        command.args(std::env::args_os().skip(1));
+
+        #[cfg(unix)]
+        let e = {
+            use std::os::unix::process::CommandExt;
+            command.exec()
+        };
+        #[cfg(not(unix))]
+        let e = match command.status() {
+            Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+            Err(e) => e,
+        };
+
+        error!("Failed to restart {:?}: {e}", self.exe);
+        std::process::exit(1);
+    }
+}
+
+/// Size and modification time of `path`, to notice when it changes.
+fn stamp(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
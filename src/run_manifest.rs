@@ -0,0 +1,135 @@
+//! An index of every artifact a run wrote, so packaging steps can read one file
+//! instead of globbing the output directory.
+
+use std::{error::Error, path::PathBuf, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    lock::{self, FileLock},
+    output, ShaderBuilder,
+};
+
+/// An artifact, as listed in the manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestArtifact {
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    pub target: String,
+    pub profile: String,
+    pub path: PathBuf,
+    pub hash: String,
+    /// Size of the artifact as written, after any compression.
+    pub size: u64,
+    pub entry_points: Vec<String>,
+}
+
+impl ManifestArtifact {
+    /// The artifact an earlier run wrote to `path`, built with `args`.
+    pub async fn existing(
+        args: &ShaderBuilder,
+        path: PathBuf,
+        hash: String,
+    ) -> Result<Self, Box<dyn Error>> {
+        let size = async_fs::metadata(&path).await?.len();
+        let output = output::read_artifact(&path).await?;
+        Ok(ManifestArtifact {
+            crate_name: args.crate_name(),
+            target: args.target.clone(),
+            profile: args.profile().to_string(),
+            path,
+            hash,
+            size,
+            entry_points: output.entry_points,
+        })
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ManifestFile {
+    artifacts: Vec<ManifestArtifact>,
+}
+
+/// Handle to a run manifest, saved after every artifact recorded in it.
+///
+/// Builder instances sharing a manifest merge their artifacts into it under a lock,
+/// and only the first of a set of overlapping runs starts it over.
+#[derive(Debug, Clone)]
+pub struct RunManifest {
+    path: PathBuf,
+    /// Held for the whole run, so runs started alongside it add to its manifest.
+    _run: Option<Arc<FileLock>>,
+}
+
+impl RunManifest {
+    /// Start an empty manifest at `path`, replacing one left by an earlier run,
+    /// or join the run of another instance still writing to it.
+    pub fn new(path: PathBuf) -> Self {
+        let run_lock = lock::sibling(&path, "run.lock");
+        let mut manifest = RunManifest { path, _run: None };
+        match FileLock::try_exclusive(&run_lock) {
+            Ok(Some(_first)) => manifest.update(|file| *file = ManifestFile::default()),
+            Ok(None) => info!(
+                "Another run is writing to {:?}, adding to its manifest",
+                manifest.path
+            ),
+            Err(e) => {
+                warn!("Failed to lock {run_lock:?}, other runs may overwrite the manifest: {e}")
+            }
+        }
+
+        // A run starting in between can't clear artifacts, as none have been recorded yet
+        match FileLock::shared(&run_lock) {
+            Ok(run) => manifest._run = Some(Arc::new(run)),
+            Err(e) => {
+                warn!("Failed to lock {run_lock:?}, other runs may overwrite the manifest: {e}")
+            }
+        }
+        manifest
+    }
+
+    /// Add an artifact, in place of any previously written to the same path.
+    pub fn record(&self, artifact: ManifestArtifact) {
+        self.update(|file| {
+            match file
+                .artifacts
+                .iter_mut()
+                .find(|other| other.path == artifact.path)
+            {
+                Some(other) => *other = artifact,
+                None => file.artifacts.push(artifact),
+            }
+        });
+    }
+
+    /// Change the manifest as other instances last saved it, holding its lock until it's saved again.
+    fn update(&self, change: impl FnOnce(&mut ManifestFile)) {
+        let lock_path = lock::sibling(&self.path, "lock");
+        let _lock = FileLock::exclusive(&lock_path)
+            .map_err(|e| {
+                warn!("Failed to lock {lock_path:?}, writing the manifest without it: {e}")
+            })
+            .ok();
+
+        let mut file = match std::fs::read(&self.path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!(
+                    "Failed to read run manifest {:?}, starting it over: {e}",
+                    self.path
+                );
+                ManifestFile::default()
+            }),
+            Err(_) => ManifestFile::default(),
+        };
+        change(&mut file);
+
+        let bytes = serde_json::to_vec_pretty(&file).expect("Failed to serialize run manifest");
+        let temp = self.path.with_extension("tmp");
+        if let Err(e) =
+            std::fs::write(&temp, bytes).and_then(|_| std::fs::rename(&temp, &self.path))
+        {
+            warn!("Failed to save run manifest to {:?}: {e}", self.path);
+        }
+    }
+}
@@ -0,0 +1,766 @@
+//! HTTP control API for watch mode, so tools can trigger rebuilds without touching watched files.
+//!
+//! `POST /build` queues a build and returns its job ID, and `GET /jobs/<id>` reports how it went.
+//! `GET`, `POST` and `DELETE` on `/projects` list, register and unregister the crates being built,
+//! `GET /history` lists the latest builds, and `POST /rollback` re-points versioned output at
+//! an older build.
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_channel::Sender;
+use async_io::{Async, Timer};
+use clap::{Arg, CommandFactory};
+use futures_lite::{
+    future,
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tracing::{info, warn};
+
+use crate::{
+    history::History,
+    output,
+    projects::{Project, Projects},
+    timing::Timings,
+    webhook::BuildEvent,
+    webhook::BuildStatus,
+    Cli, Msg, ShaderBuilder,
+};
+
+pub type JobId = u64;
+
+/// Number of jobs kept for `GET /jobs/<id>`, after which the oldest are forgotten.
+const MAX_JOBS: usize = 1024;
+
+/// How long a client has to send its request before the connection is dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest request body accepted.
+const MAX_BODY: usize = 1024 * 1024;
+
+/// Options `POST /build` may override. The API has no authentication, so anything that runs
+/// a command, fetches a source, reads a file or posts somewhere stays on the command line.
+const OVERRIDABLE: &[&str] = &[
+    "release",
+    "target",
+    "capability",
+    "no-default-capabilities",
+    "multimodule",
+    "spirv-metadata",
+    "deny-warnings",
+    "relax-struct-store",
+    "relax-logical-pointer",
+    "relax-block-layout",
+    "uniform-buffer-standard-layout",
+    "scalar-block-layout",
+    "skip-block-layout",
+    "preserve-bindings",
+    "print-metadata",
+    "output-format",
+    "compress",
+    "dedup-modules",
+    "split-debug-info",
+    "group-entry-points",
+    "entry-point-rename",
+    "pipeline",
+    "no-provenance",
+    "explain",
+    "max-diagnostics",
+    "max-instructions",
+    "max-module-bytes",
+    "max-bindings-per-set",
+    "unused-bindings",
+    "deny-lints",
+    "deny-op",
+    "deny-feature",
+    "validate",
+    "validate-env",
+    "offline",
+    "frozen",
+    "locked",
+    "profile-opt-level",
+    "debug-assertions",
+    "lto",
+    "cargo-jobs",
+    "low-priority",
+    "emit",
+    "preset",
+    "prime-pipeline-cache",
+];
+
+/// Options `POST /projects` may give besides the overridable ones, for the same reason.
+/// Process-wide ones like --serve and --state-file can only be given on the command line too.
+const REGISTRABLE: &[&str] = &[
+    "package",
+    "no-create-dirs",
+    "versioned-output",
+    "keep-artifacts",
+    "spv-dir",
+    "watch-paths",
+    "follow-symlinks",
+    "watch-strategy",
+    "heartbeat",
+    "background-startup-build",
+    "min-rebuild-interval",
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Building,
+    Succeeded,
+    Failed,
+    Cancelled,
+    /// A newer request for the same crate replaced this one before it started building.
+    Superseded,
+}
+
+/// A build requested over HTTP, as reported by `GET /jobs/<id>`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: JobId,
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    pub state: JobState,
+    pub duration_ms: Option<u128>,
+    /// Milliseconds spent in each phase, once built.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phases: Option<Timings>,
+    pub artifact_hash: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct JobsInner {
+    next_id: JobId,
+    jobs: BTreeMap<JobId, Job>,
+}
+
+/// Requested builds, shared between the server and the message bus.
+#[derive(Debug, Clone, Default)]
+pub struct Jobs(Arc<Mutex<JobsInner>>);
+
+impl Jobs {
+    fn queue(&self, crate_name: String) -> JobId {
+        let mut inner = self.0.lock().unwrap();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.jobs.insert(
+            id,
+            Job {
+                id,
+                crate_name,
+                state: JobState::Queued,
+                duration_ms: None,
+                phases: None,
+                artifact_hash: None,
+                error: None,
+            },
+        );
+        while inner.jobs.len() > MAX_JOBS {
+            inner.jobs.pop_first();
+        }
+        id
+    }
+
+    fn get(&self, id: JobId) -> Option<Job> {
+        self.0.lock().unwrap().jobs.get(&id).cloned()
+    }
+
+    fn update(&self, id: JobId, f: impl FnOnce(&mut Job)) {
+        if let Some(job) = self.0.lock().unwrap().jobs.get_mut(&id) {
+            f(job);
+        }
+    }
+
+    /// Mark a job as building.
+    pub fn start(&self, id: JobId) {
+        self.update(id, |job| job.state = JobState::Building);
+    }
+
+    /// Mark a queued job as replaced by a newer one.
+    pub fn supersede(&self, id: JobId) {
+        self.update(id, |job| job.state = JobState::Superseded);
+    }
+
+    /// Record the outcome of a job, or `None` if its build was cancelled.
+    pub fn finish(&self, id: JobId, event: Option<&BuildEvent>) {
+        self.update(id, |job| match event {
+            Some(event) => {
+                job.state = match event.status {
+                    BuildStatus::Succeeded => JobState::Succeeded,
+                    BuildStatus::Failed => JobState::Failed,
+                };
+                job.duration_ms = Some(event.duration_ms);
+                job.phases = event.phases.clone();
+                job.artifact_hash = event.artifact_hash.clone();
+                job.error = event.error.clone();
+            }
+            None => job.state = JobState::Cancelled,
+        });
+    }
+}
+
+/// Body of `POST /build`, which may be empty to rebuild the only project with its own options.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BuildRequest {
+    /// Project name, crate name or crate path the build is for.
+    #[serde(rename = "crate")]
+    crate_selector: Option<String>,
+    /// Build options to override, keyed by their long command line name,
+    /// e.g. `{"release": true, "capability": ["Int8"]}`.
+    #[serde(default)]
+    options: Map<String, Value>,
+}
+
+/// Body of `POST /projects`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegisterRequest {
+    /// Name to refer to the project by, which defaults to its crate's name.
+    name: Option<String>,
+    /// Build arguments as given on the command line, starting with the crate's path,
+    /// e.g. `["shaders/sky", "-w", "shaders/sky/src", "--release"]`.
+    args: Vec<String>,
+}
+
+/// Body of `POST /rollback`, which may be empty to roll the only project back by one build.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RollbackRequest {
+    /// Project name, crate name or crate path to roll back.
+    #[serde(rename = "crate")]
+    crate_selector: Option<String>,
+    /// How many builds to step back from the current one, 1 by default.
+    steps: Option<usize>,
+}
+
+/// Whether `token` on the command line sets `arg`, and if so whether its value is in a separate token.
+fn sets(arg: &Arg, token: &str) -> Option<bool> {
+    if let Some(long) = arg.get_long() {
+        match token.strip_prefix("--").and_then(|t| t.strip_prefix(long)) {
+            Some("") => return Some(true),
+            Some(rest) if rest.starts_with('=') => return Some(false),
+            _ => (),
+        }
+    }
+    if let Some(short) = arg.get_short() {
+        if !token.starts_with("--") {
+            match token.strip_prefix('-').and_then(|t| t.strip_prefix(short)) {
+                Some("") => return Some(true),
+                Some(_) => return Some(false),
+                None => (),
+            }
+        }
+    }
+    None
+}
+
+/// Whether `arg` takes its value from the token after it when not given with `=`.
+fn takes_separate_value(arg: &Arg) -> bool {
+    arg.get_action().takes_values() && !arg.is_require_equals_set()
+}
+
+/// Parse a JSON request body, or `None` if it's empty.
+fn parse_body<T: serde::de::DeserializeOwned>(body: &[u8]) -> serde_json::Result<Option<T>> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    serde_json::from_slice(body).map(Some)
+}
+
+/// Check that the command line `args` for a new project only sets options allowed over HTTP.
+fn check_registrable(args: &[String]) -> Result<(), String> {
+    let mut cmd = Cli::command();
+    cmd.build();
+
+    let mut tokens = args.iter();
+    while let Some(token) = tokens.next() {
+        if token == "--" {
+            break;
+        }
+        // Anything else is positional or unknown, which clap rejects
+        let Some((arg, separate_value)) = cmd
+            .get_arguments()
+            .find_map(|arg| Some((arg, sets(arg, token)?)))
+        else {
+            continue
+        };
+        let long = arg.get_long().unwrap_or_default();
+        if !OVERRIDABLE.contains(&long) && !REGISTRABLE.contains(&long) {
+            return Err(format!(
+                "--{long} can't be set over HTTP, only on the command line"
+            ));
+        }
+        if separate_value && takes_separate_value(arg) {
+            tokens.next();
+        }
+    }
+    Ok(())
+}
+
+/// Replace the options in `args` that `options` overrides.
+fn override_args(args: &[OsString], options: &Map<String, Value>) -> Result<Vec<OsString>, String> {
+    let mut cmd = Cli::command();
+    cmd.build();
+
+    let mut overridden = vec![];
+    let mut added: Vec<OsString> = vec![];
+    for (key, value) in options {
+        let long = key.replace('_', "-");
+        let arg = cmd
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(long.as_str()))
+            .ok_or_else(|| format!("Unknown option {key:?}"))?;
+        if !OVERRIDABLE.contains(&long.as_str()) {
+            return Err(format!("{key:?} can't be overridden over HTTP"));
+        }
+
+        if arg.get_action().takes_values() {
+            let values = match value {
+                Value::Array(values) => values.iter().collect(),
+                value => vec![value],
+            };
+            for value in values {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    Value::Number(n) => n.to_string(),
+                    Value::Bool(b) => b.to_string(),
+                    value => return Err(format!("Invalid value for {key:?}: {value}")),
+                };
+                if arg.is_require_equals_set() {
+                    added.push(format!("--{long}={value}").into());
+                } else {
+                    added.push(format!("--{long}").into());
+                    added.push(value.into());
+                }
+            }
+        } else {
+            match value {
+                Value::Bool(true) => added.push(format!("--{long}").into()),
+                Value::Bool(false) => (),
+                _ => return Err(format!("{key:?} is a flag, expected true or false")),
+            }
+        }
+        overridden.push(arg);
+    }
+
+    let mut kept = vec![];
+    let mut tokens = args.iter();
+    while let Some(token) = tokens.next() {
+        let text = token.to_string_lossy();
+        match overridden
+            .iter()
+            .find_map(|arg| Some((arg, sets(arg, &text)?)))
+        {
+            Some((arg, separate_value)) => {
+                if separate_value && takes_separate_value(arg) {
+                    tokens.next();
+                }
+            }
+            None => kept.push(token.clone()),
+        }
+    }
+    kept.extend(added);
+    Ok(kept)
+}
+
+struct Response {
+    status: u16,
+    body: Value,
+}
+
+impl Response {
+    fn ok(status: u16, body: Value) -> Self {
+        Response { status, body }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Response {
+            status,
+            body: json!({ "error": message.into() }),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            201 => "Created",
+            202 => "Accepted",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            413 => "Payload Too Large",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// State the server needs to answer requests.
+pub struct Server {
+    pub(crate) projects: Projects,
+    pub(crate) jobs: Jobs,
+    pub(crate) history: History,
+    pub(crate) change_tx: Sender<Msg>,
+}
+
+impl Server {
+    /// The project a request is for.
+    fn select(&self, selector: Option<&str>) -> Result<Project, Response> {
+        let project = match self.projects.select(selector) {
+            Ok(name) => self.projects.get(&name),
+            Err(e) if selector.is_some() => return Err(Response::error(404, e)),
+            Err(e) => return Err(Response::error(400, e)),
+        };
+        project.ok_or_else(|| Response::error(404, "The project was just unregistered"))
+    }
+
+    async fn post_build(&self, body: &[u8]) -> Response {
+        let request = match parse_body::<BuildRequest>(body) {
+            Ok(request) => request.unwrap_or_default(),
+            Err(e) => return Response::error(400, format!("Invalid build request: {e}")),
+        };
+
+        let project = match self.select(request.crate_selector.as_deref()) {
+            Ok(project) => project,
+            Err(response) => return response,
+        };
+
+        let args = match override_args(&project.args.worker_args, &request.options) {
+            // Resolving the source can clone, unpack or run cargo metadata
+            Ok(args) => {
+                blocking::unblock(move || ShaderBuilder::from_args(args).map_err(|e| e.to_string()))
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        let args = match args {
+            Ok(args) => args,
+            Err(e) => return Response::error(400, e),
+        };
+
+        let id = self.jobs.queue(args.crate_name());
+        info!("Build requested over HTTP as job {id}");
+        self.change_tx
+            .send(Msg::Request(id, Box::new(args)))
+            .await
+            .ok();
+
+        Response::ok(202, json!({ "id": id }))
+    }
+
+    async fn post_project(&self, body: &[u8]) -> Response {
+        let request = match parse_body::<RegisterRequest>(body) {
+            Ok(Some(request)) => request,
+            Ok(None) => return Response::error(400, "Expected a body with the project's \"args\""),
+            Err(e) => return Response::error(400, format!("Invalid project: {e}")),
+        };
+
+        if let Err(e) = check_registrable(&request.args) {
+            return Response::error(400, e);
+        }
+        let args = request.args.iter().map(OsString::from).collect();
+        let args =
+            blocking::unblock(move || ShaderBuilder::from_args(args).map_err(|e| e.to_string()));
+        let args = match args.await {
+            Ok(args) => args,
+            Err(e) => return Response::error(400, e),
+        };
+
+        let name = request.name.unwrap_or_else(|| args.crate_name());
+        let project = Project {
+            args,
+            pinned: false,
+        };
+        if let Err(e) = self.projects.register(name.clone(), project) {
+            return Response::error(409, e);
+        }
+
+        info!("Registered project {name:?}");
+        self.change_tx.send(Msg::Register(name.clone())).await.ok();
+        Response::ok(201, json!({ "name": name }))
+    }
+
+    async fn post_rollback(&self, body: &[u8]) -> Response {
+        let request = match parse_body::<RollbackRequest>(body) {
+            Ok(request) => request.unwrap_or_default(),
+            Err(e) => return Response::error(400, format!("Invalid rollback request: {e}")),
+        };
+        let project = match self.select(request.crate_selector.as_deref()) {
+            Ok(project) => project,
+            Err(response) => return response,
+        };
+
+        let args = &project.args;
+        if !args.versioned_output {
+            return Response::error(
+                400,
+                format!("{} isn't built with --versioned-output", args.crate_name()),
+            );
+        }
+        let Some(output_path) = args
+            .artifact_path("{hash}")
+            .filter(|path| !path.to_string_lossy().contains("{hash}"))
+        else {
+            return Response::error(
+                400,
+                format!("{} has no output path without a {{hash}} to roll back", args.crate_name()),
+            );
+        };
+
+        match output::rollback(&output_path, request.steps.unwrap_or(1)).await {
+            Ok(versioned_path) => {
+                info!("Rolled {output_path:?} back to {versioned_path:?} over HTTP");
+                Response::ok(
+                    200,
+                    json!({ "output_path": output_path, "versioned_path": versioned_path }),
+                )
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Response::error(409, e.to_string())
+            }
+            Err(e) => Response::error(500, format!("Failed to roll back {output_path:?}: {e}")),
+        }
+    }
+
+    async fn delete_project(&self, name: &str) -> Response {
+        if let Err(e) = self.projects.unregister(name) {
+            return Response::error(
+                if self.projects.get(name).is_some() {
+                    400
+                } else {
+                    404
+                },
+                e,
+            );
+        }
+
+        info!("Unregistered project {name:?}");
+        self.change_tx
+            .send(Msg::Unregister(name.to_string()))
+            .await
+            .ok();
+        Response::ok(200, json!({ "name": name }))
+    }
+
+    async fn respond(&self, method: &str, path: &str, body: &[u8]) -> Response {
+        let path = path.split('?').next().unwrap_or_default();
+        match (method, path) {
+            ("POST", "/build") => self.post_build(body).await,
+            (_, "/build") => Response::error(405, "Use POST to request a build"),
+            ("GET", "/projects") => {
+                Response::ok(200, serde_json::to_value(self.projects.list()).unwrap())
+            }
+            ("POST", "/projects") => self.post_project(body).await,
+            (_, "/projects") => {
+                Response::error(405, "Use GET to list projects or POST to register one")
+            }
+            ("GET", "/history") => {
+                Response::ok(200, serde_json::to_value(self.history.list()).unwrap())
+            }
+            (_, "/history") => Response::error(405, "Use GET to list recent builds"),
+            ("POST", "/rollback") => self.post_rollback(body).await,
+            (_, "/rollback") => Response::error(405, "Use POST to roll back versioned output"),
+            ("DELETE", path) if path.starts_with("/projects/") => {
+                self.delete_project(&path["/projects/".len()..]).await
+            }
+            ("GET", path) if path.starts_with("/jobs/") => {
+                let job = path["/jobs/".len()..]
+                    .parse()
+                    .ok()
+                    .and_then(|id| self.jobs.get(id));
+                match job {
+                    Some(job) => Response::ok(200, serde_json::to_value(job).unwrap()),
+                    None => Response::error(404, "No such job"),
+                }
+            }
+            _ => Response::error(404, format!("No route for {method} {path}")),
+        }
+    }
+
+    async fn handle(&self, stream: &Async<TcpStream>) -> std::io::Result<()> {
+        let mut reader = BufReader::new(stream);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).await?;
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (
+            parts.next().unwrap_or_default().to_string(),
+            parts.next().unwrap_or_default().to_string(),
+        );
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+
+        let response = if content_length > MAX_BODY {
+            Response::error(
+                413,
+                format!("Request bodies are limited to {MAX_BODY} bytes"),
+            )
+        } else {
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).await?;
+            self.respond(&method, &path, &body).await
+        };
+
+        let body = response.body.to_string();
+        let head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response.status,
+            response.reason(),
+            body.len()
+        );
+        let mut stream = stream;
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body.as_bytes()).await?;
+        stream.flush().await
+    }
+
+    /// Answer requests on `listener` until the task is dropped.
+    ///
+    /// Connections are handled one at a time, as each only queues or looks up a job.
+    pub async fn run(self, listener: Async<TcpListener>) {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("Failed to accept connection: {e}");
+                    continue;
+                }
+            };
+            let handled = future::or(async { Some(self.handle(&stream).await) }, async {
+                Timer::after(REQUEST_TIMEOUT).await;
+                None
+            })
+            .await;
+
+            match handled {
+                Some(Ok(())) => (),
+                Some(Err(e)) => warn!("Failed to answer {peer}: {e}"),
+                None => warn!("Dropped {peer}, which took too long to send its request"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    fn options(json: &str) -> Map<String, Value> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn allowlists_existing_options() {
+        let mut cmd = Cli::command();
+        cmd.build();
+        for name in OVERRIDABLE.iter().chain(REGISTRABLE) {
+            assert!(
+                cmd.get_arguments().any(|arg| arg.get_long() == Some(name)),
+                "--{name} doesn't exist"
+            );
+        }
+    }
+
+    #[test]
+    fn overrides_options() {
+        let overridden = override_args(
+            &args(&["shader", "out.json", "--target=spirv-unknown-vulkan1.1", "--release"]),
+            &options(r#"{"target": "spirv-unknown-vulkan1.2", "release": false, "capability": ["Int8", "Int16"]}"#),
+        )
+        .unwrap();
+        assert_eq!(
+            overridden,
+            args(&[
+                "shader",
+                "out.json",
+                "--capability",
+                "Int8",
+                "--capability",
+                "Int16",
+                "--target",
+                "spirv-unknown-vulkan1.2",
+            ])
+        );
+    }
+
+    #[test]
+    fn overrides_short_and_optional_value_options() {
+        let overridden = override_args(
+            &args(&[
+                "-t",
+                "spirv-unknown-vulkan1.1",
+                "--debug-assertions",
+                "shader",
+                "out.json",
+            ]),
+            &options(r#"{"target": "spirv-unknown-vulkan1.2", "debug_assertions": false}"#),
+        )
+        .unwrap();
+        assert_eq!(
+            overridden,
+            args(&[
+                "shader",
+                "out.json",
+                "--debug-assertions=false",
+                "--target",
+                "spirv-unknown-vulkan1.2",
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_options_that_cant_be_overridden() {
+        let shader = args(&["shader", "out.json"]);
+        for option in [
+            r#"{"pre_build": "true"}"#,
+            r#"{"emit_plugin": "ext=cat"}"#,
+            r#"{"env": "KEY=VALUE"}"#,
+            r#"{"webhook": "http://localhost"}"#,
+        ] {
+            assert!(
+                override_args(&shader, &options(option)).is_err(),
+                "{option}"
+            );
+        }
+        assert!(override_args(&shader, &options(r#"{"no_such_option": true}"#)).is_err());
+        assert!(override_args(&shader, &options(r#"{"release": "yes"}"#)).is_err());
+    }
+
+    #[test]
+    fn checks_project_arguments() {
+        let check = |args: &[&str]| {
+            check_registrable(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+        };
+        assert!(check(&["shader", "out.json", "-w", "shader/src", "--release"]).is_ok());
+        assert!(check(&["shader", "out.json", "-p", "--pre-build"]).is_ok());
+        assert!(check(&["shader", "out.json", "--pre-build", "make"]).is_err());
+        assert!(check(&["shader", "out.json", "--env=KEY=VALUE"]).is_err());
+        assert!(check(&["shader", "out.json", "--serve", "8450"]).is_err());
+    }
+}
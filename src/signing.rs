@@ -0,0 +1,107 @@
+//! Detached ed25519 signatures of artifacts, so runtimes fetching them over the network
+//! can reject tampered or corrupted blobs.
+//!
+//! The signature covers the artifact's bytes exactly as written, and lives in a
+//! `<output>.sig` sidecar holding the 64 raw signature bytes. Keys are stored hex-encoded.
+
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+/// Path of the signature sidecar of `output_path`.
+pub fn signature_path(output_path: &Path) -> PathBuf {
+    output_path.with_extension("sig")
+}
+
+fn decode_hex<const N: usize>(path: &Path) -> Result<[u8; N], Box<dyn Error>> {
+    let text = std::fs::read_to_string(path)?;
+    let text = text.trim();
+    if text.len() != N * 2 || !text.is_ascii() {
+        return Err(format!("{path:?} isn't a hex-encoded {N} byte key").into());
+    }
+
+    let mut bytes = [0; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[i * 2..i * 2 + 2], 16)
+            .map_err(|_| format!("{path:?} isn't a hex-encoded {N} byte key"))?;
+    }
+    Ok(bytes)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub fn load_signing_key(path: &Path) -> Result<SigningKey, Box<dyn Error>> {
+    Ok(SigningKey::from_bytes(&decode_hex(path)?))
+}
+
+pub fn load_verifying_key(path: &Path) -> Result<VerifyingKey, Box<dyn Error>> {
+    Ok(VerifyingKey::from_bytes(&decode_hex(path)?)?)
+}
+
+/// Generate a key pair, writing the secret key to `path` and the public key
+/// beside it with a `.pub` extension.
+///
+/// Returns the public key's path.
+pub fn generate(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let mut seed = [0; 32];
+    getrandom::getrandom(&mut seed).map_err(|e| format!("Failed to generate a key: {e}"))?;
+    let key = SigningKey::from_bytes(&seed);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    std::io::Write::write_all(
+        &mut options
+            .open(path)
+            .map_err(|e| format!("Failed to create {path:?}: {e}"))?,
+        encode_hex(&seed).as_bytes(),
+    )?;
+
+    let public_path = path.with_extension("pub");
+    std::fs::write(&public_path, encode_hex(key.verifying_key().as_bytes()))?;
+    Ok(public_path)
+}
+
+pub fn sign(key: &SigningKey, artifact: &[u8]) -> [u8; 64] {
+    key.sign(artifact).to_bytes()
+}
+
+/// Why an artifact failed signature verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureError {
+    /// The sidecar isn't 64 bytes, e.g. because it was truncated.
+    Malformed(usize),
+    /// The artifact or signature was modified, or signed with another key.
+    Invalid,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::Malformed(len) => {
+                write!(f, "Signature is {len} bytes, expected 64")
+            }
+            SignatureError::Invalid => write!(f, "Signature doesn't match the artifact"),
+        }
+    }
+}
+
+impl Error for SignatureError {}
+
+/// Check `signature` is `key`'s signature of the artifact bytes.
+///
+/// Only depends on ed25519-dalek, so runtimes can copy it as is.
+pub fn verify(key: &VerifyingKey, artifact: &[u8], signature: &[u8]) -> Result<(), SignatureError> {
+    let signature: [u8; 64] = signature
+        .try_into()
+        .map_err(|_| SignatureError::Malformed(signature.len()))?;
+    key.verify_strict(artifact, &Signature::from_bytes(&signature))
+        .map_err(|_| SignatureError::Invalid)
+}
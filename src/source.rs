@@ -0,0 +1,192 @@
+//! Shader crates fetched from a git repository or unpacked from a tarball,
+//! instead of being read from a path on disk.
+//!
+//! Sources are prepared once per builder process, in a temporary directory its build workers
+//! find through the environment, and removed when the process exits.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    sync::Mutex,
+};
+
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+/// Set to the id of the process that prepares sources, and inherited by its build workers.
+const SESSION_ENV: &str = "RUST_GPU_BUILDER_SOURCE_SESSION";
+
+/// Written once a source is fully prepared, so a half-fetched one is never built.
+const COMPLETE: &str = ".rust-gpu-builder-complete";
+
+/// Serializes preparation, as matrix jobs resolve their sources concurrently.
+static PREPARING: Mutex<()> = Mutex::new(());
+
+/// A git repository at some revision, parsed from `<url>#<rev>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitSource {
+    pub url: String,
+    /// Commit, tag or branch to check out, or `None` for the default branch.
+    pub rev: Option<String>,
+}
+
+impl FromStr for GitSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (url, rev) = match s.rsplit_once('#') {
+            Some((url, rev)) if !rev.is_empty() => (url, Some(rev.to_string())),
+            Some((url, _)) => (url, None),
+            None => (s, None),
+        };
+        if url.is_empty() {
+            return Err("Expected <url>#<rev>".to_string());
+        }
+
+        Ok(GitSource {
+            url: url.to_string(),
+            rev,
+        })
+    }
+}
+
+impl GitSource {
+    /// Repository name from the url, e.g. `shaders` for `https://example.com/shaders.git`.
+    fn name(&self) -> &str {
+        let name = self.url.trim_end_matches('/').rsplit(['/', ':']).next();
+        let name = name.unwrap_or_default().trim_end_matches(".git");
+        if name.is_empty() {
+            "repository"
+        } else {
+            name
+        }
+    }
+}
+
+/// Directory this builder process and its workers keep sources in.
+fn session_dir() -> PathBuf {
+    let session = std::env::var(SESSION_ENV).unwrap_or_else(|_| {
+        let session = std::process::id().to_string();
+        std::env::set_var(SESSION_ENV, &session);
+        session
+    });
+    std::env::temp_dir().join(format!("rust-gpu-builder-sources-{session}"))
+}
+
+/// Prepare the source identified by `spec` into `<session>/<hash of spec>/<name>` with `fill`,
+/// unless that's already done, returning the directory.
+fn prepare(
+    spec: &str,
+    name: &str,
+    fill: impl FnOnce(&Path) -> Result<(), String>,
+) -> Result<PathBuf, String> {
+    let _preparing = PREPARING.lock().unwrap();
+
+    let hash = Sha256::digest(spec.as_bytes())[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    let base = session_dir().join(hash);
+    let dir = base.join(name);
+    if base.join(COMPLETE).exists() {
+        return Ok(dir);
+    }
+
+    // Start over from whatever a failed attempt left behind
+    std::fs::remove_dir_all(&base).ok();
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {dir:?}: {e}"))?;
+    fill(&dir)?;
+    std::fs::write(base.join(COMPLETE), spec)
+        .map_err(|e| format!("Failed to prepare {spec}: {e}"))?;
+    Ok(dir)
+}
+
+fn run(command: &mut Command, what: &str) -> Result<(), String> {
+    let output = command
+        .output()
+        .map_err(|e| format!("Failed to run {what}: {e}"))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{what} failed ({})\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ))
+    }
+}
+
+/// Clone the repository and check out its revision, returning the checkout's root.
+pub fn checkout_git(source: &GitSource) -> Result<PathBuf, String> {
+    let spec = format!("git {}#{}", source.url, source.rev.as_deref().unwrap_or(""));
+    prepare(&spec, source.name(), |dir| {
+        info!("Cloning {}...", source.url);
+        run(
+            Command::new("git")
+                .args(["clone", "--quiet", "--no-checkout", &source.url])
+                .arg(dir),
+            "git clone",
+        )?;
+
+        let rev = source.rev.as_deref().unwrap_or("HEAD");
+        run(
+            Command::new("git")
+                .args(["checkout", "--quiet", "--detach", rev, "--"])
+                .current_dir(dir),
+            &format!("git checkout {rev}"),
+        )
+    })
+}
+
+/// Unpack a tarball, in any compression `tar` detects, returning the root of its contents.
+///
+/// Archives holding a single top-level directory, as GitHub's do, are rooted inside it.
+pub fn unpack_tar(path: &Path) -> Result<PathBuf, String> {
+    let absolute = crate::paths::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let name = name.split('.').next().filter(|name| !name.is_empty());
+
+    let spec = format!("tar {}", absolute.display());
+    let dir = prepare(&spec, name.unwrap_or("crate"), |dir| {
+        info!("Unpacking {path:?}...");
+        run(
+            Command::new("tar")
+                .arg("-xf")
+                .arg(&absolute)
+                .arg("-C")
+                .arg(dir),
+            "tar",
+        )
+    })?;
+
+    let entries = std::fs::read_dir(&dir)
+        .map_err(|e| format!("Failed to read {dir:?}: {e}"))?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    match entries.as_slice() {
+        [single] if single.is_dir() => Ok(single.clone()),
+        _ => Ok(dir),
+    }
+}
+
+/// Removes the sources prepared by this process when dropped.
+///
+/// Build workers inherit their parent's session, so only the process that started it cleans up.
+pub struct Session;
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if std::env::var(SESSION_ENV).ok() != Some(std::process::id().to_string()) {
+            return;
+        }
+        let dir = session_dir();
+        if let Err(e) = std::fs::remove_dir_all(&dir) {
+            warn!("Failed to remove fetched sources in {dir:?}: {e}");
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+
+use rspirv::spirv::ExecutionModel;
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+
+use crate::{
+    reflect,
+    rename::{self, EntryPointRename},
+};
+
+/// Key an execution model is grouped under.
+pub fn stage_name(model: ExecutionModel) -> &'static str {
+    match model {
+        ExecutionModel::Vertex => "vertex",
+        ExecutionModel::TessellationControl => "tessellation_control",
+        ExecutionModel::TessellationEvaluation => "tessellation_evaluation",
+        ExecutionModel::Geometry => "geometry",
+        ExecutionModel::Fragment => "fragment",
+        ExecutionModel::GLCompute => "compute",
+        ExecutionModel::Kernel => "kernel",
+        ExecutionModel::TaskNV => "task",
+        ExecutionModel::MeshNV => "mesh",
+        ExecutionModel::RayGenerationNV => "ray_generation",
+        ExecutionModel::IntersectionNV => "intersection",
+        ExecutionModel::AnyHitNV => "any_hit",
+        ExecutionModel::ClosestHitNV => "closest_hit",
+        ExecutionModel::MissNV => "miss",
+        ExecutionModel::CallableNV => "callable",
+    }
+}
+
+/// Group the entry points declared by `modules` by stage,
+/// naming them as they appear in the output after `rules` are applied.
+pub fn group(
+    modules: &RustGpuBuilderModules,
+    rules: &[EntryPointRename],
+) -> BTreeMap<&'static str, Vec<String>> {
+    let mut stages = BTreeMap::<&'static str, Vec<String>>::new();
+
+    for (_, module) in reflect::named_modules(modules, "") {
+        let Ok(module) = reflect::parse(module) else {
+            continue
+        };
+
+        for (model, name) in reflect::entry_points(&module) {
+            let name = rename::rename(rules, &name);
+            let names = stages.entry(stage_name(model)).or_default();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+
+    stages
+}
@@ -0,0 +1,242 @@
+//! Builder state persisted across restarts, so unchanged crates don't need a cold rebuild.
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{
+    paths,
+    timing::Timings,
+    webhook::{BuildEvent, BuildStatus},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrateStatus {
+    /// The last build succeeded, and its sources haven't changed since as far as the builder knows.
+    Clean,
+    /// Sources changed after the last build.
+    Dirty,
+    /// The last build failed.
+    Failed,
+}
+
+/// What the builder knows about a crate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateState {
+    pub status: CrateStatus,
+    /// Fingerprint of the sources and arguments the last build started from.
+    pub fingerprint: Option<String>,
+    pub artifact: Option<PathBuf>,
+    pub artifact_hash: Option<String>,
+    pub builds: u64,
+    pub failures: u64,
+    pub last_duration_ms: Option<u128>,
+    /// Milliseconds spent in each phase of the last build that compiled.
+    #[serde(default)]
+    pub last_phases: Option<Timings>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StateFile {
+    /// Keyed by the crate's path, as passed on the command line.
+    crates: BTreeMap<PathBuf, CrateState>,
+}
+
+/// Handle to a state file, saved after every change.
+#[derive(Debug, Clone)]
+pub struct State {
+    path: PathBuf,
+    /// Files and directories whose contents builds depend on.
+    sources: Vec<PathBuf>,
+    file: Arc<Mutex<StateFile>>,
+}
+
+impl State {
+    /// Load the state at `path`, starting afresh if it's missing or unreadable.
+    pub fn load(path: PathBuf, sources: Vec<PathBuf>) -> Self {
+        let file = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable state file {path:?}: {e}");
+                StateFile::default()
+            }),
+            Err(_) => StateFile::default(),
+        };
+
+        // Absolute, so the state file can be excluded from the sources however it was given
+        let absolute = |path: PathBuf| paths::absolute(&path).unwrap_or(path);
+        State {
+            path: absolute(path),
+            sources: sources.into_iter().map(absolute).collect(),
+            file: Arc::new(Mutex::new(file)),
+        }
+    }
+
+    /// Fingerprint of the sources as they are now, when built with `args`.
+    pub fn fingerprint(&self, args: &[OsString]) -> String {
+        // The state file may well live inside the crate
+        let exclude = [self.path.clone(), self.temp_path()];
+        fingerprint(&self.sources, &exclude, args)
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        self.path.with_extension("tmp")
+    }
+
+    fn save(&self, file: &StateFile) {
+        let bytes = serde_json::to_vec_pretty(file).expect("Failed to serialize state");
+        let temp = self.temp_path();
+        if let Err(e) =
+            std::fs::write(&temp, bytes).and_then(|_| std::fs::rename(&temp, &self.path))
+        {
+            warn!("Failed to save state to {:?}: {e}", self.path);
+        }
+    }
+
+    fn update(&self, crate_path: &Path, f: impl FnOnce(&mut CrateState)) {
+        let mut file = self.file.lock().unwrap();
+        let state = file
+            .crates
+            .entry(crate_path.to_path_buf())
+            .or_insert(CrateState {
+                status: CrateStatus::Dirty,
+                fingerprint: None,
+                artifact: None,
+                artifact_hash: None,
+                builds: 0,
+                failures: 0,
+                last_duration_ms: None,
+                last_phases: None,
+            });
+        f(state);
+        self.save(&file);
+    }
+
+    pub fn get(&self, crate_path: &Path) -> Option<CrateState> {
+        self.file.lock().unwrap().crates.get(crate_path).cloned()
+    }
+
+    /// The crate's state, if it was clean when last built from `fingerprint`
+    /// and its artifact is still on disk.
+    pub fn unchanged(&self, crate_path: &Path, fingerprint: &str) -> Option<CrateState> {
+        self.get(crate_path).filter(|state| {
+            state.status == CrateStatus::Clean
+                && state.fingerprint.as_deref() == Some(fingerprint)
+                && state.artifact.as_ref().map_or(false, |path| path.exists())
+        })
+    }
+
+    /// Mark a crate as changed since its last build, if it isn't already.
+    pub fn mark_dirty(&self, crate_path: &Path) {
+        if self
+            .get(crate_path)
+            .map_or(true, |state| state.status == CrateStatus::Clean)
+        {
+            self.update(crate_path, |state| state.status = CrateStatus::Dirty);
+        }
+    }
+
+    /// Record the outcome of a build started from `fingerprint`.
+    pub fn record(
+        &self,
+        crate_path: &Path,
+        fingerprint: String,
+        event: &BuildEvent,
+        artifact: Option<PathBuf>,
+    ) {
+        self.update(crate_path, |state| {
+            state.builds += 1;
+            state.last_duration_ms = Some(event.duration_ms);
+            if event.phases.is_some() {
+                state.last_phases = event.phases.clone();
+            }
+            state.fingerprint = Some(fingerprint);
+            match event.status {
+                BuildStatus::Succeeded => {
+                    // Changes during the build will mark it dirty again afterwards
+                    state.status = CrateStatus::Clean;
+                    state.artifact = artifact;
+                    state.artifact_hash = event.artifact_hash.clone();
+                }
+                BuildStatus::Failed => {
+                    state.status = CrateStatus::Failed;
+                    state.failures += 1;
+                }
+            }
+        });
+    }
+}
+
+/// Hash the path, size and modification time of every file under `dir` into `hasher`,
+/// skipping hidden and `target` directories and anything in `exclude`.
+fn hash_dir(dir: &Path, exclude: &[PathBuf], hasher: &mut Sha256) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return
+    };
+    let mut entries = entries.flatten().collect::<Vec<_>>();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let Ok(metadata) = entry.metadata() else {
+            continue
+        };
+        if exclude.iter().any(|exclude| paths::same(exclude, &path)) {
+            continue;
+        }
+
+        if metadata.is_dir() {
+            let name = entry.file_name();
+            if name != "target" && !name.to_string_lossy().starts_with('.') {
+                hash_dir(&path, exclude, hasher);
+            }
+            continue;
+        }
+
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |time| time.as_nanos());
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update(format!("\0{}\0{modified}\n", metadata.len()));
+    }
+}
+
+/// Fingerprint of the files under `paths` and the build arguments, which changes
+/// whenever a rebuild could produce a different artifact.
+///
+/// Files are compared by size and modification time, so touching one counts as a change.
+fn fingerprint(paths: &[PathBuf], exclude: &[PathBuf], args: &[OsString]) -> String {
+    let mut hasher = Sha256::new();
+    for arg in args {
+        hasher.update(arg.to_string_lossy().as_bytes());
+        hasher.update([0]);
+    }
+    for path in paths {
+        hasher.update(b"\n");
+        if path.is_dir() {
+            hash_dir(path, exclude, &mut hasher);
+        } else if let Ok(metadata) = path.metadata() {
+            hasher.update(format!(
+                "{}\0{:?}",
+                metadata.len(),
+                metadata.modified().ok()
+            ));
+        }
+    }
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
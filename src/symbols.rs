@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+
+use rspirv::{
+    binary::Assemble,
+    dr::{Module, Operand},
+    spirv::{Op, Word},
+};
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+use serde::Serialize;
+
+use crate::reflect;
+
+/// Source location an instruction was generated from.
+#[derive(Debug, Clone, Serialize)]
+pub struct LineEntry {
+    /// Index of the instruction within its function in the stripped module, counting from `OpFunction`.
+    pub instruction: usize,
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// Debug information split out of a single module.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ModuleSymbols {
+    /// `OpName`s, keyed by id.
+    pub names: BTreeMap<Word, String>,
+    /// `OpMemberName`s, keyed by struct id and member index.
+    pub member_names: BTreeMap<Word, BTreeMap<u32, String>>,
+    /// Source text embedded with `OpSource`, keyed by file name.
+    pub sources: BTreeMap<String, String>,
+    /// Line map of each function, keyed by function id.
+    pub lines: BTreeMap<Word, Vec<LineEntry>>,
+    /// The module before stripping, for tools such as RenderDoc that read debug info from SPIR-V.
+    pub unstripped: Vec<u8>,
+}
+
+/// Debug information of every module of a build, keyed like [`reflect::named_modules`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Symbols {
+    pub modules: BTreeMap<String, ModuleSymbols>,
+}
+
+fn is_line(op: Op) -> bool {
+    matches!(op, Op::Line | Op::NoLine)
+}
+
+/// Strip names, sources and line information from `bytes`, returning the stripped module
+/// and what was removed. Modules that fail to parse are returned unchanged without symbols.
+fn split_module(bytes: &[u8]) -> (Vec<u8>, Option<ModuleSymbols>) {
+    let Ok(mut module) = reflect::parse(bytes) else {
+        return (bytes.to_vec(), None)
+    };
+
+    let mut symbols = ModuleSymbols {
+        unstripped: bytes.to_vec(),
+        ..Default::default()
+    };
+
+    let strings = module
+        .debug_string_source
+        .iter()
+        .filter_map(|inst| match (inst.class.opcode, inst.operands.as_slice()) {
+            (Op::String, [Operand::LiteralString(s)]) => Some((inst.result_id?, s.clone())),
+            _ => None,
+        })
+        .collect::<BTreeMap<_, _>>();
+    let file = |id: &Word| strings.get(id).cloned().unwrap_or_else(|| format!("%{id}"));
+
+    for inst in &module.debug_string_source {
+        if let (Op::Source, [_, _, Operand::IdRef(id), Operand::LiteralString(source), ..]) =
+            (inst.class.opcode, inst.operands.as_slice())
+        {
+            symbols.sources.insert(file(id), source.clone());
+        }
+    }
+
+    for inst in &module.debug_names {
+        match (inst.class.opcode, inst.operands.as_slice()) {
+            (Op::Name, [Operand::IdRef(id), Operand::LiteralString(name)]) => {
+                symbols.names.insert(*id, name.clone());
+            }
+            (
+                Op::MemberName,
+                [Operand::IdRef(id), Operand::LiteralInt32(member), Operand::LiteralString(name)],
+            ) => {
+                symbols
+                    .member_names
+                    .entry(*id)
+                    .or_default()
+                    .insert(*member, name.clone());
+            }
+            _ => (),
+        }
+    }
+
+    for function in &mut module.functions {
+        let Some(id) = function.def_id() else {
+            continue
+        };
+
+        // `OpFunction` and its parameters come first
+        let mut index = 1 + function.parameters.len();
+        let mut current = None;
+        let mut entries = vec![];
+        for block in &mut function.blocks {
+            // The block's label
+            index += 1;
+            for inst in &block.instructions {
+                match (inst.class.opcode, inst.operands.as_slice()) {
+                    (
+                        Op::Line,
+                        [Operand::IdRef(id), Operand::LiteralInt32(line), Operand::LiteralInt32(column)],
+                    ) => current = Some((file(id), *line, *column)),
+                    (Op::NoLine, _) => current = None,
+                    _ => {
+                        if let Some((file, line, column)) = &current {
+                            entries.push(LineEntry {
+                                instruction: index,
+                                file: file.clone(),
+                                line: *line,
+                                column: *column,
+                            });
+                        }
+                        index += 1;
+                    }
+                }
+            }
+            block
+                .instructions
+                .retain(|inst| !is_line(inst.class.opcode));
+        }
+
+        if !entries.is_empty() {
+            symbols.lines.insert(id, entries);
+        }
+    }
+
+    strip(&mut module);
+    let stripped = module
+        .assemble()
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect();
+
+    (stripped, Some(symbols))
+}
+
+/// Remove the module-level debug sections, once their contents have been collected.
+fn strip(module: &mut Module) {
+    module.debug_string_source.clear();
+    module.debug_names.clear();
+    module.debug_module_processed.clear();
+    module
+        .types_global_values
+        .retain(|inst| !is_line(inst.class.opcode));
+}
+
+/// Strip debug information from every module, returning the stripped modules
+/// alongside the symbols they had.
+pub fn split(
+    modules: &RustGpuBuilderModules,
+    single_name: &str,
+) -> (RustGpuBuilderModules, Symbols) {
+    let mut symbols = Symbols::default();
+
+    let mut split = |name: String, bytes: &[u8]| {
+        let (stripped, module_symbols) = split_module(bytes);
+        if let Some(module_symbols) = module_symbols {
+            symbols.modules.insert(name, module_symbols);
+        }
+        stripped
+    };
+
+    let stripped = match modules {
+        RustGpuBuilderModules::Single(module) => {
+            RustGpuBuilderModules::Single(split(single_name.to_string(), module))
+        }
+        RustGpuBuilderModules::Multi(multi) => RustGpuBuilderModules::Multi(
+            multi
+                .iter()
+                .map(|(name, module)| (name.clone(), split(name.clone(), module)))
+                .collect(),
+        ),
+    };
+
+    (stripped, symbols)
+}
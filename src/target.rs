@@ -0,0 +1,302 @@
+use spirv_builder::Capability;
+
+/// Whether `target` compiles for an OpenCL environment, e.g. `spirv-unknown-opencl1.2`.
+pub fn is_opencl(target: &str) -> bool {
+    target
+        .strip_prefix("spirv-unknown-")
+        .unwrap_or(target)
+        .starts_with("opencl")
+}
+
+/// The kind of environment a target compiles for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Environment {
+    /// `spvX.Y`, SPIR-V without a client API.
+    Universal,
+    Vulkan,
+    OpenGl,
+    OpenCl {
+        embedded: bool,
+    },
+}
+
+/// The environment and its `(major, minor)` version that `target` compiles for,
+/// e.g. Vulkan 1.1 for `spirv-unknown-vulkan1.1spv1.4`.
+pub fn environment(target: &str) -> Option<(Environment, (u32, u32))> {
+    let env = target.strip_prefix("spirv-unknown-").unwrap_or(target);
+    let (environment, version) = [
+        ("spv", Environment::Universal),
+        ("vulkan", Environment::Vulkan),
+        ("opengl", Environment::OpenGl),
+        ("opencl", Environment::OpenCl { embedded: false }),
+    ]
+    .into_iter()
+    .find_map(|(prefix, environment)| Some((environment, env.strip_prefix(prefix)?)))?;
+
+    let environment = match environment {
+        Environment::OpenCl { .. } => Environment::OpenCl {
+            embedded: version.ends_with("embedded"),
+        },
+        environment => environment,
+    };
+    let (major, rest) = version.split_once('.')?;
+    let minor = rest.split(|c: char| !c.is_ascii_digit()).next()?;
+    Some((environment, (major.parse().ok()?, minor.parse().ok()?)))
+}
+
+/// Capabilities enabled for `target` in addition to those passed with --capability,
+/// unless --no-default-capabilities is given.
+///
+/// OpenCL environments only accept kernels, which need `Kernel` declared. The scalar type
+/// capabilities are those the environment allows without an extension. rust-gpu's linker
+/// removes them again from modules that don't use the types, so they only spare crates that do
+/// from a missing capability error.
+pub fn default_capabilities(target: &str) -> &'static [Capability] {
+    match environment(target) {
+        Some((Environment::OpenCl { embedded: false }, _)) => &[
+            Capability::Kernel,
+            Capability::Int8,
+            Capability::Int16,
+            Capability::Int64,
+        ],
+        // The embedded profile makes 64-bit integers optional
+        Some((Environment::OpenCl { embedded: true }, _)) => {
+            &[Capability::Kernel, Capability::Int8, Capability::Int16]
+        }
+        Some((Environment::Universal, _)) => &[
+            Capability::Int8,
+            Capability::Int16,
+            Capability::Int64,
+            Capability::Float16,
+            Capability::Float64,
+        ],
+        // 8-bit integers and half floats became core in Vulkan 1.2
+        Some((Environment::Vulkan, version)) if version >= (1, 2) => &[
+            Capability::Int8,
+            Capability::Int16,
+            Capability::Int64,
+            Capability::Float16,
+            Capability::Float64,
+        ],
+        Some((Environment::Vulkan, _)) => {
+            &[Capability::Int16, Capability::Int64, Capability::Float64]
+        }
+        Some((Environment::OpenGl, _)) => &[Capability::Float64],
+        None => &[],
+    }
+}
+
+/// SPIR-V version `target` emits.
+fn spirv_version(environment: Environment, (major, minor): (u32, u32), target: &str) -> (u32, u32) {
+    match environment {
+        Environment::Universal => (major, minor),
+        Environment::Vulkan if target.ends_with("spv1.4") => (1, 4),
+        Environment::Vulkan => match (major, minor) {
+            (1, 0) => (1, 0),
+            (1, 1) => (1, 3),
+            _ => (1, 5),
+        },
+        Environment::OpenGl => (1, 0),
+        Environment::OpenCl { .. } if (major, minor) >= (2, 2) => (1, 2),
+        Environment::OpenCl { .. } => (1, 0),
+    }
+}
+
+/// Capabilities only OpenCL environments accept.
+const KERNEL_ONLY: &[Capability] = &[
+    Capability::Kernel,
+    Capability::Addresses,
+    Capability::Vector16,
+    Capability::Float16Buffer,
+    Capability::ImageBasic,
+    Capability::ImageReadWrite,
+    Capability::ImageMipmap,
+    Capability::Pipes,
+    Capability::DeviceEnqueue,
+    Capability::GenericPointer,
+];
+
+/// Capabilities OpenCL environments reject.
+const SHADER_ONLY: &[Capability] = &[
+    Capability::Shader,
+    Capability::Geometry,
+    Capability::Tessellation,
+    Capability::RayTracingKHR,
+    Capability::RayQueryKHR,
+    Capability::MeshShadingNV,
+];
+
+/// Capabilities that only Vulkan and plain SPIR-V targets support.
+const VULKAN_ONLY: &[Capability] = &[
+    Capability::RayTracingKHR,
+    Capability::RayQueryKHR,
+    Capability::MeshShadingNV,
+];
+
+/// The oldest SPIR-V version that can declare each capability, with or without an extension.
+const MIN_SPIRV: &[(Capability, (u32, u32))] = &[
+    (Capability::GroupNonUniform, (1, 3)),
+    (Capability::GroupNonUniformVote, (1, 3)),
+    (Capability::GroupNonUniformArithmetic, (1, 3)),
+    (Capability::GroupNonUniformBallot, (1, 3)),
+    (Capability::GroupNonUniformShuffle, (1, 3)),
+    (Capability::GroupNonUniformShuffleRelative, (1, 3)),
+    (Capability::GroupNonUniformClustered, (1, 3)),
+    (Capability::GroupNonUniformQuad, (1, 3)),
+    (Capability::RayTracingKHR, (1, 4)),
+    (Capability::RayQueryKHR, (1, 4)),
+];
+
+/// The oldest Vulkan target emitting at least SPIR-V `version`.
+fn vulkan_target(version: (u32, u32)) -> &'static str {
+    match version {
+        (1, 0) => "vulkan1.0",
+        (1, 1..=3) => "vulkan1.1",
+        (1, 4) => "vulkan1.1spv1.4",
+        _ => "vulkan1.2",
+    }
+}
+
+/// Reject capabilities `target` can't declare, before the compiler or validator fails on them
+/// with a less helpful error.
+pub fn check_capabilities(target: &str, capabilities: &[Capability]) -> Result<(), String> {
+    let Some((environment, version)) = self::environment(target) else {
+        return Ok(())
+    };
+    let spirv = spirv_version(environment, version, target);
+
+    let mut errors = vec![];
+    for capability in capabilities {
+        let opencl = matches!(environment, Environment::OpenCl { .. });
+        if !opencl && KERNEL_ONLY.contains(capability) {
+            errors.push(format!("{capability:?} requires an OpenCL target"));
+        } else if opencl && SHADER_ONLY.contains(capability) {
+            errors.push(format!("{capability:?} isn't supported by OpenCL targets"));
+        } else if environment == Environment::OpenGl && VULKAN_ONLY.contains(capability) {
+            errors.push(format!(
+                "{capability:?} requires a Vulkan or SPIR-V target, not OpenGL"
+            ));
+        } else if let Some((_, min)) = MIN_SPIRV
+            .iter()
+            .find(|(required, min)| required == capability && spirv < *min)
+        {
+            let required = match environment {
+                Environment::Vulkan => {
+                    format!("{}+ (SPIR-V {}.{})", vulkan_target(*min), min.0, min.1)
+                }
+                Environment::Universal => format!("spv{}.{}+", min.0, min.1),
+                _ => format!("SPIR-V {}.{}", min.0, min.1),
+            };
+            errors.push(format!(
+                "{capability:?} requires {required}, but {target} emits SPIR-V {}.{}",
+                spirv.0, spirv.1
+            ));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emits_each_targets_spirv_version() {
+        for (target, spirv) in [
+            ("spirv-unknown-spv1.3", (1, 3)),
+            ("spirv-unknown-vulkan1.0", (1, 0)),
+            ("spirv-unknown-vulkan1.1", (1, 3)),
+            ("spirv-unknown-vulkan1.1spv1.4", (1, 4)),
+            ("spirv-unknown-vulkan1.2", (1, 5)),
+            ("spirv-unknown-opengl4.5", (1, 0)),
+            ("spirv-unknown-opencl1.2", (1, 0)),
+            ("spirv-unknown-opencl2.2", (1, 2)),
+        ] {
+            let (environment, version) = environment(target).unwrap();
+            assert_eq!(
+                spirv_version(environment, version, target),
+                spirv,
+                "{target}"
+            );
+        }
+    }
+
+    #[test]
+    fn requires_spirv_1_4_for_ray_tracing() {
+        let ray_tracing = [Capability::RayTracingKHR];
+        assert_eq!(
+            check_capabilities("spirv-unknown-vulkan1.1", &ray_tracing),
+            Err("RayTracingKHR requires vulkan1.1spv1.4+ (SPIR-V 1.4), \
+                 but spirv-unknown-vulkan1.1 emits SPIR-V 1.3"
+                .to_string())
+        );
+        assert_eq!(
+            check_capabilities("spirv-unknown-spv1.3", &ray_tracing),
+            Err(
+                "RayTracingKHR requires spv1.4+, but spirv-unknown-spv1.3 emits SPIR-V 1.3"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            check_capabilities("spirv-unknown-vulkan1.1spv1.4", &ray_tracing),
+            Ok(())
+        );
+        assert_eq!(
+            check_capabilities("spirv-unknown-spv1.4", &ray_tracing),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn keeps_kernel_and_shader_capabilities_apart() {
+        assert!(check_capabilities("spirv-unknown-vulkan1.2", &[Capability::Kernel]).is_err());
+        assert!(check_capabilities("spirv-unknown-opencl1.2", &[Capability::Shader]).is_err());
+        assert!(check_capabilities("spirv-unknown-opengl4.5", &[Capability::RayQueryKHR]).is_err());
+        assert_eq!(
+            check_capabilities("spirv-unknown-opencl1.2", &[Capability::Kernel]),
+            Ok(())
+        );
+        assert_eq!(
+            check_capabilities("spirv-unknown-vulkan1.2", &[Capability::Shader]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn leaves_unknown_targets_to_the_compiler() {
+        assert_eq!(
+            check_capabilities("spirv-unknown-webgpu0", &[Capability::Kernel]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn defaults_capabilities_by_environment() {
+        use Capability::*;
+
+        for (target, expected) in [
+            ("spirv-unknown-opencl1.2", &[Kernel, Int8, Int16, Int64][..]),
+            ("spirv-unknown-opencl1.2embedded", &[Kernel, Int8, Int16]),
+            (
+                "spirv-unknown-spv1.3",
+                &[Int8, Int16, Int64, Float16, Float64],
+            ),
+            ("spirv-unknown-vulkan1.1", &[Int16, Int64, Float64]),
+            ("spirv-unknown-vulkan1.1spv1.4", &[Int16, Int64, Float64]),
+            (
+                "spirv-unknown-vulkan1.2",
+                &[Int8, Int16, Int64, Float16, Float64],
+            ),
+            ("spirv-unknown-opengl4.5", &[Float64]),
+            ("spirv-unknown-webgpu0", &[]),
+            ("opencl", &[]),
+        ] {
+            assert_eq!(default_capabilities(target), expected, "{target}");
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// A part of a build, in the order they happen.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Cargo compiling the shader crate's dependencies, `core` and spirv-std included,
+    /// or just checking them when nothing needs recompiling.
+    Dependencies,
+    /// rustc_codegen_spirv compiling and linking the shader crate, its spirv-opt passes included.
+    Codegen,
+    /// Budgets, lints, validation and debug info splitting of the compiled modules.
+    Postprocess,
+    /// Extracting entry point stages, kernel signatures, WGSL and provenance.
+    Reflection,
+    /// Serializing the artifact and writing it with its sidecars.
+    Serialization,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [
+        Phase::Dependencies,
+        Phase::Codegen,
+        Phase::Postprocess,
+        Phase::Reflection,
+        Phase::Serialization,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Dependencies => "dependencies",
+            Phase::Codegen => "codegen",
+            Phase::Postprocess => "postprocess",
+            Phase::Reflection => "reflection",
+            Phase::Serialization => "serialization",
+        }
+    }
+}
+
+/// Time spent in each phase of a build.
+///
+/// Serializes as milliseconds keyed by phase.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "BTreeMap<Phase, u128>", from = "BTreeMap<Phase, u128>")]
+pub struct Timings(BTreeMap<Phase, Duration>);
+
+impl Timings {
+    pub fn add(&mut self, phase: Phase, duration: Duration) {
+        *self.0.entry(phase).or_default() += duration;
+    }
+
+    /// Add the time since `lap` to `phase`, and restart `lap`.
+    pub fn lap(&mut self, phase: Phase, lap: &mut Instant) {
+        let now = Instant::now();
+        self.add(phase, now - *lap);
+        *lap = now;
+    }
+
+    pub fn get(&self, phase: Phase) -> Duration {
+        self.0.get(&phase).copied().unwrap_or_default()
+    }
+}
+
+impl From<Timings> for BTreeMap<Phase, u128> {
+    fn from(timings: Timings) -> Self {
+        timings
+            .0
+            .into_iter()
+            .map(|(phase, duration)| (phase, duration.as_millis()))
+            .collect()
+    }
+}
+
+impl From<BTreeMap<Phase, u128>> for Timings {
+    fn from(millis: BTreeMap<Phase, u128>) -> Self {
+        Timings(
+            millis
+                .into_iter()
+                .map(|(phase, ms)| (phase, Duration::from_millis(ms as u64)))
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phases = self
+            .0
+            .iter()
+            .map(|(phase, duration)| format!("{} {:.2?}", phase.name(), duration))
+            .collect::<Vec<_>>();
+        write!(f, "{}", phases.join(", "))
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+
+use clap::Args;
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+use spirv_tools::{
+    binary::Binary,
+    val::{self, Validator, ValidatorOptions},
+    TargetEnv,
+};
+
+use crate::reflect;
+
+/// Re-validation of the compiled modules against the environments they will run in.
+#[derive(Debug, Clone, Default, Args)]
+#[command(next_help_heading = "Validation")]
+pub struct Validation {
+    /// Run spirv-val on every module after a successful compile,
+    /// against the environment implied by --target.
+    #[arg(long, default_value = "false")]
+    pub validate: bool,
+    /// Validate against this environment instead of the one implied by --target,
+    /// e.g. `vulkan1.1` for a driver that only supports Vulkan 1.1. Implies --validate.
+    ///
+    /// Can be specified multiple times to validate against each environment.
+    #[arg(long, value_parser = parse_env)]
+    pub validate_env: Vec<TargetEnv>,
+}
+
+/// Clap value parser for `TargetEnv`.
+fn parse_env(s: &str) -> Result<TargetEnv, String> {
+    s.parse()
+        .map_err(|_| format!("Unrecognized validation environment {s:?}"))
+}
+
+/// The environment a rust-gpu target compiles for, e.g. `vulkan1.2` for `spirv-unknown-vulkan1.2`.
+pub fn target_env(target: &str) -> Result<TargetEnv, String> {
+    let env = target.strip_prefix("spirv-unknown-").unwrap_or(target);
+    env.parse()
+        .map_err(|_| format!("Can't derive a validation environment from target {target:?}"))
+}
+
+/// Modules which failed validation.
+#[derive(Debug, Clone)]
+pub struct ValidationReport(pub Vec<String>);
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} validation error(s):", self.0.len())?;
+        for error in &self.0 {
+            write!(f, "\n  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Validation {
+    /// Environments to validate against, if validation is enabled.
+    pub fn envs(&self, target: &str) -> Result<Vec<TargetEnv>, String> {
+        if !self.validate_env.is_empty() {
+            Ok(self.validate_env.clone())
+        } else if self.validate {
+            Ok(vec![target_env(target)?])
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    /// Validate each module against each environment.
+    pub fn check(
+        &self,
+        modules: &RustGpuBuilderModules,
+        single_name: &str,
+        target: &str,
+        options: &ValidatorOptions,
+    ) -> Result<(), ValidationReport> {
+        let envs = self.envs(target).map_err(|e| ValidationReport(vec![e]))?;
+
+        let mut errors = vec![];
+        for env in envs {
+            let validator = val::create(Some(env));
+            for (name, bytes) in reflect::named_modules(modules, single_name) {
+                let binary = match Binary::try_from(bytes.to_vec()) {
+                    Ok(binary) => binary,
+                    Err(e) => {
+                        errors.push(format!("{name}: {e}"));
+                        continue;
+                    }
+                };
+                if let Err(e) = validator.validate(binary, Some(options.clone())) {
+                    errors.push(format!("{name} ({env}): {e}"));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationReport(errors))
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+
+use rust_gpu_builder_shared::RustGpuBuilderModules;
+use serde::Serialize;
+use spirv_builder::Capability;
+
+use crate::reflect;
+
+/// Target used by `--preset webgpu`.
+///
+/// wgpu consumes SPIR-V 1.3 through naga, so this is the newest target it accepts.
+pub const TARGET: &str = "spirv-unknown-vulkan1.1";
+
+/// Capabilities with a WGSL equivalent, which are all --capability may enable under the preset.
+const CAPABILITIES: &[Capability] = &[
+    Capability::Shader,
+    Capability::Matrix,
+    Capability::Sampled1D,
+    Capability::Image1D,
+    Capability::ImageQuery,
+    Capability::DerivativeControl,
+    Capability::SampledCubeArray,
+    Capability::StorageImageExtendedFormats,
+];
+
+/// WebGPU's guaranteed minimum limits.
+const MAX_BIND_GROUPS: u32 = 4;
+const MAX_BINDINGS_PER_BIND_GROUP: u32 = 1000;
+const MAX_WORKGROUP_SIZE: [u32; 3] = [256, 256, 64];
+const MAX_WORKGROUP_INVOCATIONS: u32 = 256;
+
+/// Reject capabilities that WebGPU can't express.
+pub fn check_capabilities(capabilities: &[Capability]) -> Result<(), String> {
+    let rejected = capabilities
+        .iter()
+        .filter(|capability| !CAPABILITIES.contains(capability))
+        .map(|capability| format!("{capability:?}"))
+        .collect::<Vec<_>>();
+
+    if rejected.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "WebGPU does not support capabilities {}",
+            rejected.join(", ")
+        ))
+    }
+}
+
+/// WGSL translations of a build's modules, in the same shape as `RustGpuBuilderModules`.
+#[derive(Debug, Clone, Serialize)]
+pub enum WgslModules {
+    Single(String),
+    Multi(BTreeMap<String, String>),
+}
+
+/// Translate a module to WGSL through naga, checking it against WebGPU's limits on the way.
+fn to_wgsl(module: &[u8]) -> Result<String, Vec<String>> {
+    let options = naga::front::spv::Options {
+        // wgpu expects the coordinate space rust-gpu already emits
+        adjust_coordinate_space: false,
+        strict_capabilities: true,
+        block_ctx_dump_prefix: None,
+    };
+    let module = naga::front::spv::parse_u8_slice(module, &options)
+        .map_err(|e| vec![format!("naga can't read the module: {e}")])?;
+
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .map_err(|e| vec![format!("naga rejected the module: {}", e.as_inner())])?;
+
+    let mut errors = vec![];
+    for (_, global) in module.global_variables.iter() {
+        let Some(binding) = &global.binding else {
+            continue
+        };
+        if binding.group >= MAX_BIND_GROUPS {
+            errors.push(format!(
+                "bind group {} exceeds the limit of {MAX_BIND_GROUPS}",
+                binding.group
+            ));
+        }
+        if binding.binding >= MAX_BINDINGS_PER_BIND_GROUP {
+            errors.push(format!(
+                "binding {} in group {} exceeds the limit of {MAX_BINDINGS_PER_BIND_GROUP} per group",
+                binding.binding, binding.group
+            ));
+        }
+    }
+    for entry_point in &module.entry_points {
+        if entry_point.stage != naga::ShaderStage::Compute {
+            continue;
+        }
+        let size = entry_point.workgroup_size;
+        let invocations = size.iter().product::<u32>();
+        if size
+            .iter()
+            .zip(MAX_WORKGROUP_SIZE)
+            .any(|(size, max)| *size > max)
+            || invocations > MAX_WORKGROUP_INVOCATIONS
+        {
+            errors.push(format!(
+                "{}: workgroup size {size:?} exceeds the limit of {MAX_WORKGROUP_SIZE:?} \
+                 and {MAX_WORKGROUP_INVOCATIONS} invocations",
+                entry_point.name
+            ));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    naga::back::wgsl::write_string(&module, &info, naga::back::wgsl::WriterFlags::empty())
+        .map_err(|e| vec![format!("naga can't write WGSL: {e}")])
+}
+
+/// Translate every module to WGSL, collecting the reasons any of them aren't web-ready.
+pub fn translate(
+    modules: &RustGpuBuilderModules,
+    single_name: &str,
+) -> Result<WgslModules, Vec<String>> {
+    let mut errors = vec![];
+    let mut translated = BTreeMap::new();
+    for (name, module) in reflect::named_modules(modules, single_name) {
+        match to_wgsl(module) {
+            Ok(wgsl) => {
+                translated.insert(name, wgsl);
+            }
+            Err(e) => errors.extend(e.into_iter().map(|e| format!("{name}: {e}"))),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(match modules {
+        RustGpuBuilderModules::Single(_) => {
+            WgslModules::Single(translated.into_values().next().unwrap())
+        }
+        RustGpuBuilderModules::Multi(_) => WgslModules::Multi(translated),
+    })
+}
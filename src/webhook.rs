@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use async_io::Timer;
+use serde::Serialize;
+use tracing::{error, warn};
+
+use crate::timing::Timings;
+
+/// Number of times a webhook delivery is attempted before giving up.
+const ATTEMPTS: u32 = 4;
+
+/// Delay before the first retry, doubled after each subsequent failure.
+const BACKOFF: Duration = Duration::from_millis(500);
+
+/// Outcome of a build.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildStatus {
+    Succeeded,
+    Failed,
+}
+
+/// JSON payload posted to webhooks after every build.
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildEvent {
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    pub status: BuildStatus,
+    pub duration_ms: u128,
+    /// Milliseconds spent in each phase of the build, if it compiled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phases: Option<Timings>,
+    pub artifact_hash: Option<String>,
+    pub error: Option<String>,
+}
+
+/// POST `event` to `url`, retrying with exponential backoff on failure.
+pub async fn post(url: String, event: impl Serialize) {
+    let body = serde_json::to_string(&event).expect("Failed to serialize event");
+
+    let mut backoff = BACKOFF;
+    for attempt in 1..=ATTEMPTS {
+        let result = blocking::unblock({
+            let url = url.clone();
+            let body = body.clone();
+            move || {
+                ureq::post(&url)
+                    .set("Content-Type", "application/json")
+                    .send_string(&body)
+            }
+        })
+        .await;
+
+        match result {
+            Ok(_) => return,
+            // Client errors won't be fixed by retrying
+            Err(ureq::Error::Status(code, _)) if (400..500).contains(&code) => {
+                error!("Webhook {url:} rejected event with status {code:}");
+                return;
+            }
+            Err(e) if attempt < ATTEMPTS => {
+                warn!("Webhook {url:} failed ({e:}), retrying in {backoff:?}");
+                Timer::after(backoff).await;
+                backoff *= 2;
+            }
+            Err(e) => error!("Webhook {url:} failed after {ATTEMPTS:} attempts: {e:}"),
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    ffi::OsString,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use is_terminal::IsTerminal;
+use spirv_builder::CompileResult;
+
+use crate::{
+    cancel::CancelToken,
+    console,
+    diagnostics::DiagnosticFilter,
+    ice, paths, priority,
+    timing::{Phase, Timings},
+    BuildError, ShaderBuilder,
+};
+
+/// Set on build worker processes, naming the file their result should be written to.
+const RESULT_ENV: &str = "RUST_GPU_BUILDER_WORKER_RESULT";
+
+/// Set on build worker processes to compile into another spirv-builder target directory.
+const TARGET_DIR_ENV: &str = "RUST_GPU_BUILDER_WORKER_TARGET_DIR";
+
+/// What a worker reports back to its parent.
+type WorkerResult = Result<CompileResult, String>;
+
+/// A successful compile, with how long cargo spent on the crate's dependencies and on the crate.
+#[derive(Debug)]
+pub struct Compiled {
+    pub result: CompileResult,
+    pub timings: Timings,
+}
+
+/// If this process was spawned as a build worker, the file to write its result to.
+pub fn result_path() -> Option<PathBuf> {
+    std::env::var_os(RESULT_ENV).map(PathBuf::from)
+}
+
+/// If this process was spawned as a build worker, the target directory to compile into
+/// instead of the shared one.
+pub fn target_dir() -> Option<PathBuf> {
+    std::env::var_os(TARGET_DIR_ENV).map(PathBuf::from)
+}
+
+/// Compile in-process and report the result to the parent, then exit.
+pub fn run(args: &ShaderBuilder, result_path: &Path) -> ! {
+    let result: WorkerResult = args.compile().map_err(|e| e.to_string());
+    let result = serde_json::to_vec(&result).expect("Failed to serialize build result");
+    std::fs::write(result_path, result).expect("Failed to write build result");
+    std::process::exit(0)
+}
+
+/// Compile in a re-executed copy of this process.
+///
+/// spirv-builder passes cargo's stderr straight through, so running it in a child
+/// is the only way to see that output. It is still forwarded to stderr as it arrives,
+/// and scanned for internal compiler errors once the build fails.
+///
+/// Cargo's `Compiling` line for the shader crate at `path_to_crate` splits the time spent
+/// on its dependencies from codegen of the crate itself.
+///
+/// The worker leads its own process group, so cancelling `cancel` takes cargo down with it.
+/// With `low_priority`, it and everything it spawns run at reduced CPU and IO priority.
+/// Only the first `max_diagnostics` diagnostics of each kind are forwarded.
+/// With a `target_dir`, it compiles there rather than in the shared spirv-builder target directory.
+pub fn build(
+    args: impl IntoIterator<Item = OsString>,
+    path_to_crate: &Path,
+    target_dir: Option<&Path>,
+    low_priority: bool,
+    max_diagnostics: Option<usize>,
+    cancel: &CancelToken,
+) -> Result<Compiled, BuildError> {
+    static WORKERS: AtomicUsize = AtomicUsize::new(0);
+
+    let result_path = std::env::temp_dir().join(format!(
+        "rust-gpu-builder-{}-{}.json",
+        std::process::id(),
+        WORKERS.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let exe = std::env::current_exe()
+        .map_err(|e| BuildError::Worker(format!("Failed to locate own executable: {e}")))?;
+
+    let mut command = Command::new(exe);
+    command
+        .args(args)
+        .env(RESULT_ENV, &result_path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped());
+
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    if let Some(target_dir) = target_dir {
+        command.env(TARGET_DIR_ENV, target_dir);
+    }
+
+    if low_priority {
+        priority::lower(&mut command);
+    }
+
+    // Cargo only colors diagnostics when writing to a terminal, which the pipe isn't
+    if let Some(color) = console::cargo_color() {
+        command.env("CARGO_TERM_COLOR", color);
+    } else if std::env::var_os("CARGO_TERM_COLOR").is_none() && std::io::stderr().is_terminal() {
+        command.env("CARGO_TERM_COLOR", "always");
+    }
+
+    // Cargo ends the line with the crate's canonical path in parentheses
+    let compiling = paths::canonicalize(path_to_crate)
+        .map(|path| format!("({})", path.display()))
+        .ok();
+
+    let start = Instant::now();
+    let mut child = command
+        .spawn()
+        .map_err(|e| BuildError::Worker(format!("Failed to spawn build worker: {e}")))?;
+    let guard = cancel.register(child.id());
+
+    let mut diagnostics = DiagnosticFilter::new(max_diagnostics);
+    let mut captured = vec![];
+    let mut scanned = 0;
+    let mut codegen_started = None;
+    let mut pipe = child.stderr.take().unwrap();
+    let mut buf = [0; 4096];
+    loop {
+        match pipe.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                diagnostics.write(&buf[..n], &mut std::io::stderr());
+                captured.extend_from_slice(&buf[..n]);
+            }
+        }
+
+        // Only complete lines, which a read may have split
+        if let (None, Some(compiling)) = (codegen_started, &compiling) {
+            if let Some(end) = captured[scanned..].iter().rposition(|&b| b == b'\n') {
+                let lines = String::from_utf8_lossy(&captured[scanned..scanned + end]);
+                if lines.lines().any(|line| {
+                    line.contains("Compiling") && line.trim_end().ends_with(compiling.as_str())
+                }) {
+                    codegen_started = Some(Instant::now());
+                }
+                scanned += end + 1;
+            }
+        }
+    }
+    diagnostics.finish(&mut std::io::stderr());
+
+    let status = child
+        .wait()
+        .map_err(|e| BuildError::Worker(format!("Failed to wait for build worker: {e}")))?;
+    drop(guard);
+
+    // If the crate was fresh, cargo only spent its time checking dependencies
+    let finished = Instant::now();
+    let codegen_started = codegen_started.unwrap_or(finished);
+    let mut timings = Timings::default();
+    timings.add(Phase::Dependencies, codegen_started - start);
+    timings.add(Phase::Codegen, finished - codegen_started);
+
+    let result = std::fs::read(&result_path);
+    std::fs::remove_file(&result_path).ok();
+
+    if cancel.is_cancelled() {
+        return Err(BuildError::Cancelled);
+    }
+
+    let failed = |error| match ice::detect(&String::from_utf8_lossy(&captured)) {
+        Some(summary) => BuildError::Ice(summary),
+        None => error,
+    };
+
+    match result {
+        Ok(result) => match serde_json::from_slice::<WorkerResult>(&result) {
+            Ok(Ok(result)) => Ok(Compiled { result, timings }),
+            Ok(Err(message)) => Err(failed(BuildError::Spirv(message))),
+            Err(e) => Err(BuildError::Worker(format!(
+                "Malformed build worker result: {e}"
+            ))),
+        },
+        Err(_) => Err(failed(BuildError::Worker(format!(
+            "Build worker exited with {status}"
+        )))),
+    }
+}